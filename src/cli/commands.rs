@@ -1,57 +1,653 @@
 use std::collections::{HashMap, HashSet};
 use std::io::{self, Error, ErrorKind};
+use std::path::Path;
 use std::process;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::thread;
 
 use arc_swap::ArcSwap;
+use macaddr::MacAddr6;
 
-use crate::fib::Fib;
-use crate::network::interface::{InterfaceView, IntfCmd, PortMode};
-use super::shell::{CliMode, IntfsViewMap};
+use crate::fib::{FibFilter, OverflowPolicy, DEFAULT_AGING_TIME};
+use crate::fib::events::SubscriptionId;
+use crate::fib::moves::FlapAction;
+use crate::lag::{LacpMode, LoadBalance, MAX_PORT_CHANNELS};
+use crate::network::frame::DOT1Q_TPID;
+use crate::network::interface::{InterfaceView, IntfCmd, PortMode, ViolationAction, DEFAULT_VLAN};
+use crate::stp::{PortGuard, StpVersion, DEFAULT_PORT_COST, DEFAULT_PORT_PRIORITY};
+use crate::stp::mst::{MAX_MST_INSTANCE_ID, REGION_NAME_LEN};
+use crate::vlan::{format_vlan_list, PrivateVlan, VlanState};
+use crate::Switch;
+use super::shell::CliMode;
 
 pub struct Command<'a> {
   pub pattern: &'a [&'a str] ,
   description: &'a str,
-  handler: fn(&IntfsViewMap, &Arc<Fib>, &ArcSwap<CliMode>, Arc<InterfaceView>, &mut HashMap<CliMode, HashSet<String>>, HashMap<String, String>),
+  handler: for<'b> fn(&Switch<'b>, &ArcSwap<CliMode>, Arc<InterfaceView<'b>>, &mut HashMap<CliMode, HashSet<String>>, HashMap<String, String>),
 }
 
+// Allowing every vlan on a trunk does not fill the vlan database
+const MISSING_VLANS_NOTE: &str = "Vlans missing from the vlan database are dropped until created with \"vlan <vlan_id>\"";
+
+// FIB events subscription of the terminal
+static FIB_MONITOR: Mutex<Option<SubscriptionId>> = Mutex::new(None);
+
 pub const GENERAL_COMMANDS: &[Command] = &[
   Command {
     pattern: &["show", "interfaces"],
     description: "Show all interfaces information",
-    handler: | intfs_view, _, _, _, _, _ | {
-      let mut keys: Vec<_> = intfs_view.keys().cloned().collect();
+    handler: | switch, _, _, _, _ | {
+      let mut keys: Vec<_> = switch.intfs_view.keys().cloned().collect();
       keys.sort();
       println!("Interfaces:\n==========\n");
-      for intf in keys {
-        println!("{}\n", intfs_view[intf]);
+      // Port-channels without any member configured are hidden
+      for intf in keys.into_iter().filter(|name| !switch.intfs_view[name].is_aggregate() || switch.lag.is_configured(name)) {
+        println!("{}\n", switch.intfs_view[intf]);
       }
     }
   },
   Command {
     pattern: &["show", "fib"],
     description: "Display FIB entries",
-    handler: | _, fib, _, _, _, _ | {
-      println!("FIB:\n====\n{}", fib)
+    handler: | switch, _, _, _, _ | {
+      println!("FIB:\n====\n{}", switch.fib)
+    }
+  },
+  Command {
+    pattern: &["show", "fib", "detail"],
+    description: "Display FIB entries with forwarding hits and last use",
+    handler: | switch, _, _, _, _ | {
+      println!("FIB:\n====\n{}", switch.fib.format_entries(&FibFilter::All, true))
+    }
+  },
+  Command {
+    pattern: &["show", "fib", "vlan", "<vlan>"],
+    description: "Display FIB entries for given vlan",
+    handler: | switch, _, _, _, args | {
+      if let Ok(vlan) = arg_to_vlan(&args["vlan"]) {
+        println!("FIB:\n====\n{}", switch.fib.format_entries(&FibFilter::Vlan(vlan), false))
+      }
+    }
+  },
+  Command {
+    pattern: &["show", "fib", "interface", "<intf>"],
+    description: "Display FIB entries for given interface",
+    handler: | switch, _, _, _, args | {
+      println!("FIB:\n====\n{}", switch.fib.format_entries(&FibFilter::Interface(args["intf"].clone()), false))
+    }
+  },
+  Command {
+    pattern: &["show", "fib", "address", "<mac>"],
+    description: "Display FIB entries for given MAC address",
+    handler: | switch, _, _, _, args | {
+      if let Ok(mac) = arg_to_mac(&args["mac"]) {
+        println!("FIB:\n====\n{}", switch.fib.format_entries(&FibFilter::Address(mac), false))
+      }
+    }
+  },
+  Command {
+    pattern: &["show", "fib", "count"],
+    description: "Display FIB entries count per vlan and interface",
+    handler: | switch, _, _, _, _ | {
+      println!("FIB:\n====\n{}", switch.fib.format_counts())
+    }
+  },
+  Command {
+    pattern: &["clear", "fib"],
+    description: "Flush FIB dynamic entries",
+    handler: | switch, _, _, _, _ | {
+      switch.fib.clear(&FibFilter::All)
+    }
+  },
+  Command {
+    pattern: &["clear", "fib", "vlan", "<vlan>"],
+    description: "Flush FIB dynamic entries for given vlan",
+    handler: | switch, _, _, _, args | {
+      if let Ok(vlan) = arg_to_vlan(&args["vlan"]) {
+        switch.fib.clear(&FibFilter::Vlan(vlan))
+      }
+    }
+  },
+  Command {
+    pattern: &["clear", "fib", "interface", "<intf>"],
+    description: "Flush FIB dynamic entries for given interface",
+    handler: | switch, _, _, _, args | {
+      if switch.intfs_view.contains_key(&args["intf"][..]) {
+        switch.fib.clear(&FibFilter::Interface(args["intf"].clone()))
+      } else {
+        println!("Interface {} not found", args["intf"]);
+      }
+    }
+  },
+  Command {
+    pattern: &["clear", "fib", "address", "<mac>"],
+    description: "Flush FIB dynamic entries for given MAC address",
+    handler: | switch, _, _, _, args | {
+      if let Ok(mac) = arg_to_mac(&args["mac"]) {
+        switch.fib.clear(&FibFilter::Address(mac))
+      }
+    }
+  },
+  Command {
+    pattern: &["terminal", "monitor", "fib"],
+    description: "Stream FIB learn, move, age and flush events",
+    handler: | switch, _, _, _, _ | {
+      let mut monitor = FIB_MONITOR.lock().unwrap();
+      if monitor.is_some() {
+        return
+      }
+      let (id, events) = switch.fib.events().subscribe();
+      *monitor = Some(id);
+      thread::spawn(move || {
+        for event in events {
+          println!("FIB: {}", event);
+        }
+      });
+    }
+  },
+  Command {
+    pattern: &["no", "terminal", "monitor", "fib"],
+    description: "Stop streaming FIB events",
+    handler: | switch, _, _, _, _ | {
+      if let Some(id) = FIB_MONITOR.lock().unwrap().take() {
+        switch.fib.events().unsubscribe(id);
+      }
+    }
+  },
+  Command {
+    pattern: &["show", "mac", "address-table", "aging-time"],
+    description: "Display FIB entries aging time",
+    handler: | switch, _, _, _, _ | {
+      println!("Aging time: {}s", switch.fib.get_aging_time())
+    }
+  },
+  Command {
+    pattern: &["mac", "address-table", "aging-time", "<seconds>"],
+    description: "Set FIB entries aging time in seconds (0 disables aging)",
+    handler: | switch, _, _, _, args | {
+      if let Ok(seconds) = arg_to_seconds(&args["seconds"]) {
+        switch.fib.set_aging_time(seconds);
+      }
+    }
+  },
+  Command {
+    pattern: &["no", "mac", "address-table", "aging-time"],
+    description: "Reset FIB entries aging time to default",
+    handler: | switch, _, _, _, _ | {
+      switch.fib.set_aging_time(DEFAULT_AGING_TIME)
+    }
+  },
+  Command {
+    pattern: &["mac", "address-table", "limit", "<count>"],
+    description: "Limit number of entries in FIB",
+    handler: | switch, _, _, _, args | {
+      if let Ok(max_entries) = arg_to_mac_count(&args["count"]) {
+        switch.fib.set_max_entries(max_entries);
+      }
+    }
+  },
+  Command {
+    pattern: &["mac", "address-table", "limit", "action", "<action>"],
+    description: "Set FIB overflow policy: refuse or evict-lru",
+    handler: | switch, _, _, _, args | {
+      match &args["action"][..] {
+        "refuse" => switch.fib.set_overflow_policy(OverflowPolicy::Refuse),
+        "evict-lru" => switch.fib.set_overflow_policy(OverflowPolicy::EvictLru),
+        other => eprintln!("Error: invalid overflow policy \"{}\". Must be refuse or evict-lru", other),
+      }
+    }
+  },
+  Command {
+    pattern: &["no", "mac", "address-table", "limit"],
+    description: "Remove FIB entries limit",
+    handler: | switch, _, _, _, _ | {
+      switch.fib.set_max_entries(0);
+    }
+  },
+  Command {
+    pattern: &["show", "mac", "address-table", "moves"],
+    description: "Display recent MAC moves between interfaces",
+    handler: | switch, _, _, _, _ | {
+      println!("MAC moves:\n==========\n{}", switch.fib.moves())
+    }
+  },
+  Command {
+    pattern: &["mac", "address-table", "move-detection", "threshold", "<moves>", "window", "<seconds>"],
+    description: "Dampen MACs moving more than <moves> times in <seconds>",
+    handler: | switch, _, _, _, args | {
+      let Ok(threshold) = args["moves"].parse::<usize>() else {
+        eprintln!("Error: invalid moves threshold \"{}\". Must be a number", args["moves"]);
+        return
+      };
+      if let Ok(window) = arg_to_seconds(&args["seconds"]) {
+        switch.fib.moves().update_dampening(|dampening| {
+          dampening.threshold = threshold;
+          dampening.window = window;
+        });
+      }
+    }
+  },
+  Command {
+    pattern: &["mac", "address-table", "move-detection", "action", "suppress", "<seconds>"],
+    description: "Suppress learning of flapping MACs for <seconds>",
+    handler: | switch, _, _, _, args | {
+      if let Ok(hold) = arg_to_seconds(&args["seconds"]) {
+        switch.fib.moves().update_dampening(|dampening| dampening.action = FlapAction::Suppress(hold));
+      }
+    }
+  },
+  Command {
+    pattern: &["mac", "address-table", "move-detection", "action", "shutdown"],
+    description: "Shut down interfaces flapping MACs move to",
+    handler: | switch, _, _, _, _ | {
+      switch.fib.moves().update_dampening(|dampening| dampening.action = FlapAction::Shutdown);
+    }
+  },
+  Command {
+    pattern: &["no", "mac", "address-table", "move-detection"],
+    description: "Disable MAC flap dampening",
+    handler: | switch, _, _, _, _ | {
+      switch.fib.moves().disable_dampening();
+    }
+  },
+  Command {
+    pattern: &["mac", "address-table", "static", "<mac>", "vlan", "<vlan>", "interface", "<intf>"],
+    description: "Pin MAC address to interface for given vlan",
+    handler: | switch, _, _, _, args | {
+      let Some(intf) = switch.intfs_view.get(&args["intf"][..]) else {
+        eprintln!("Error: interface {} not found", args["intf"]);
+        return
+      };
+      if let Ok(mac) = arg_to_mac(&args["mac"]) && let Ok(vlan) = arg_to_vlan(&args["vlan"]) {
+        switch.fib.add_static_entry(vlan, &mac, Some(Arc::clone(intf)));
+      }
+    }
+  },
+  Command {
+    pattern: &["mac", "address-table", "static", "<mac>", "vlan", "<vlan>", "drop"],
+    description: "Drop traffic from/to MAC address for given vlan",
+    handler: | switch, _, _, _, args | {
+      if let Ok(mac) = arg_to_mac(&args["mac"]) && let Ok(vlan) = arg_to_vlan(&args["vlan"]) {
+        switch.fib.add_static_entry(vlan, &mac, None);
+      }
+    }
+  },
+  Command {
+    pattern: &["no", "mac", "address-table", "static", "<mac>", "vlan", "<vlan>"],
+    description: "Remove static or drop MAC address entry",
+    handler: | switch, _, _, _, args | {
+      if let Ok(mac) = arg_to_mac(&args["mac"]) && let Ok(vlan) = arg_to_vlan(&args["vlan"]) {
+        switch.fib.remove_entry(vlan, &mac);
+      }
+    }
+  },
+  Command {
+    pattern: &["fib", "save", "<file>"],
+    description: "Save FIB entries to file, to be restored with --fib-restore",
+    handler: | switch, _, _, _, args | {
+      match switch.fib.save(Path::new(&args["file"])) {
+        Ok(count) => println!("Saved {} FIB entries to {}", count, args["file"]),
+        Err(err) => eprintln!("Error: unable to save FIB to {}: {}", args["file"], err),
+      }
+    }
+  },
+  Command {
+    pattern: &["show", "mac", "address-table", "learning-group"],
+    description: "Display shared vlan learning groups",
+    handler: | switch, _, _, _, _ | {
+      print!("{}", switch.fib.format_learning_groups())
+    }
+  },
+  Command {
+    pattern: &["mac", "address-table", "learning-group", "<name>", "vlans", "<vlans>"],
+    description: "Share FIB learning between vlans of the group (e.g. 10,20-22)",
+    handler: | switch, _, _, _, args | {
+      if let Ok(vlans) = arg_to_vlans(&args["vlans"]) &&
+        let Err(err) = switch.fib.set_learning_group(&args["name"], &vlans) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["no", "mac", "address-table", "learning-group", "<name>"],
+    description: "Remove shared vlan learning group",
+    handler: | switch, _, _, _, args | {
+      if let Err(err) = switch.fib.remove_learning_group(&args["name"]) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["show", "vlan"],
+    description: "Display vlans with their name, state and member interfaces",
+    handler: | switch, _, _, _, _ | {
+      print!("{}", switch.vlans.format(&switch.intfs_view))
+    }
+  },
+  Command {
+    pattern: &["show", "vlan", "counters"],
+    description: "Display per vlan traffic counters",
+    handler: | switch, _, _, _, _ | {
+      if let Ok(output) = switch.vlans.format_counters(None) {
+        print!("{}", output)
+      }
+    }
+  },
+  Command {
+    pattern: &["show", "vlan", "counters", "<vlan>"],
+    description: "Display traffic counters of given vlan",
+    handler: | switch, _, _, _, args | {
+      if let Ok(vlan) = arg_to_vlan(&args["vlan"]) {
+        match switch.vlans.format_counters(Some(vlan)) {
+          Ok(output) => print!("{}", output),
+          Err(err) => eprintln!("Error: {}", err),
+        }
+      }
+    }
+  },
+  Command {
+    pattern: &["clear", "vlan", "counters"],
+    description: "Reset per vlan traffic counters",
+    handler: | switch, _, _, _, _ | {
+      switch.vlans.reset_counters();
+    }
+  },
+  Command {
+    pattern: &["show", "vlan", "private-vlan"],
+    description: "Display private vlan associations with their host and promiscuous interfaces",
+    handler: | switch, _, _, _, _ | {
+      print!("{}", switch.vlans.format_private_vlans(&switch.intfs_view))
+    }
+  },
+  Command {
+    pattern: &["vlan", "<vlan>"],
+    description: "Create vlan if needed and enter in vlan configuration mode",
+    handler: | switch, mode, _, _, args | {
+      if let Ok(vlan) = arg_to_vlan(&args["vlan"]) {
+        switch.vlans.create(vlan);
+        mode.store(Arc::new(CliMode::Vlan(vlan)));
+      }
+    }
+  },
+  Command {
+    pattern: &["no", "vlan", "<vlan>"],
+    description: "Delete vlan, its frames are dropped",
+    handler: | switch, _, _, _, args | {
+      if let Ok(vlan) = arg_to_vlan(&args["vlan"]) {
+        match switch.vlans.remove(vlan) {
+          Ok(()) => switch.fib.clear(&FibFilter::Vlan(vlan)),
+          Err(err) => eprintln!("Error: {}", err),
+        }
+      }
     }
   },
   Command {
     pattern: &["interface", "<intf>"],
     description: "Enter in interfate configuration mode for given target",
-    handler: | intfs_view, _, mode, _, _, args | {
+    handler: | switch, mode, _, _, args | {
       let intf_name = &args["intf"];
-      if intfs_view.contains_key(&intf_name[..]) {
+      if switch.intfs_view.contains_key(&intf_name[..]) {
         mode.store(Arc::new(CliMode::Interface(intf_name.to_string())));
       } else {
         println!("Interface {} not found", intf_name);
       }
     },
   },
+  Command {
+    pattern: &["vlan", "dot1q", "tag", "native"],
+    description: "Tag native vlan frames on all trunk interfaces",
+    handler: | switch, _, _, _, _ | {
+      for view in switch.intfs_view.values() {
+        view.set_tag_native(true);
+      }
+    }
+  },
+  Command {
+    pattern: &["no", "vlan", "dot1q", "tag", "native"],
+    description: "Send native vlan frames untagged on trunk interfaces",
+    handler: | switch, _, _, _, _ | {
+      for view in switch.intfs_view.values() {
+        view.set_tag_native(false);
+      }
+    }
+  },
+  Command {
+    pattern: &["show", "spanning-tree"],
+    description: "Display spanning tree root, bridge and port roles and states",
+    handler: | switch, _, _, _, _ | {
+      print!("{}", switch.stp.format())
+    }
+  },
+  Command {
+    pattern: &["spanning-tree"],
+    description: "Enable spanning tree, ports are discarding until their role is settled",
+    handler: | switch, _, _, _, _ | {
+      switch.stp.set_enabled(true);
+    }
+  },
+  Command {
+    pattern: &["no", "spanning-tree"],
+    description: "Disable spanning tree, all ports are forwarding",
+    handler: | switch, _, _, _, _ | {
+      switch.stp.set_enabled(false);
+    }
+  },
+  Command {
+    pattern: &["spanning-tree", "mode", "<mode>"],
+    description: "Set spanning tree protocol: rstp (default), stp or mst",
+    handler: | switch, _, _, _, args | {
+      match &args["mode"][..] {
+        "rstp" => switch.stp.set_version(StpVersion::Rstp),
+        "stp" => switch.stp.set_version(StpVersion::Stp),
+        "mst" => switch.stp.set_version(StpVersion::Mstp),
+        other => eprintln!("Error: invalid spanning tree mode \"{}\". Must be rstp, stp or mst", other),
+      }
+    }
+  },
+  Command {
+    pattern: &["spanning-tree", "priority", "<priority>"],
+    description: "Set bridge priority, a multiple of 4096 (default 32768)",
+    handler: | switch, _, _, _, args | {
+      if let Ok(priority) = arg_to_number_in_range(&args["priority"], "bridge priority", 0, 61440)
+        && let Err(err) = switch.stp.set_priority(0, priority as u16) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["spanning-tree", "hello-time", "<seconds>"],
+    description: "Set interval between BPDUs sent by designated ports (default 2s)",
+    handler: | switch, _, _, _, args | {
+      if let Ok(seconds) = arg_to_number_in_range(&args["seconds"], "hello time", 1, 10) {
+        switch.stp.set_timers(Some(seconds as u16), None, None);
+      }
+    }
+  },
+  Command {
+    pattern: &["spanning-tree", "max-age", "<seconds>"],
+    description: "Set age after which root information is discarded (default 20s)",
+    handler: | switch, _, _, _, args | {
+      if let Ok(seconds) = arg_to_number_in_range(&args["seconds"], "max age", 6, 40) {
+        switch.stp.set_timers(None, Some(seconds as u16), None);
+      }
+    }
+  },
+  Command {
+    pattern: &["spanning-tree", "forward-time", "<seconds>"],
+    description: "Set time spent in discarding and learning states without agreement (default 15s)",
+    handler: | switch, _, _, _, args | {
+      if let Ok(seconds) = arg_to_number_in_range(&args["seconds"], "forward delay", 4, 30) {
+        switch.stp.set_timers(None, None, Some(seconds as u16));
+      }
+    }
+  },
+  Command {
+    pattern: &["show", "spanning-tree", "mst", "configuration"],
+    description: "Display MST region name, revision, digest and vlan to instance mapping",
+    handler: | switch, _, _, _, _ | {
+      print!("{}", switch.stp.format_mst_configuration())
+    }
+  },
+  Command {
+    pattern: &["show", "spanning-tree", "mst", "<instance>"],
+    description: "Display regional root, bridge and port roles and states of MST instance",
+    handler: | switch, _, _, _, args | {
+      let Ok(instance) = arg_to_number_in_range(&args["instance"], "MST instance", 0, MAX_MST_INSTANCE_ID as u32) else {
+        return
+      };
+      match switch.stp.format_mst(instance as u16) {
+        Ok(output) => print!("{}", output),
+        Err(err) => eprintln!("Error: {}", err),
+      }
+    }
+  },
+  Command {
+    pattern: &["spanning-tree", "mst", "name", "<name>"],
+    description: "Set MST region name",
+    handler: | switch, _, _, _, args | {
+      if args["name"].len() > REGION_NAME_LEN {
+        eprintln!("Error: MST region name must be at most {} characters", REGION_NAME_LEN);
+        return
+      }
+      switch.stp.set_mst_name(&args["name"]);
+    }
+  },
+  Command {
+    pattern: &["spanning-tree", "mst", "revision", "<revision>"],
+    description: "Set MST region revision number",
+    handler: | switch, _, _, _, args | {
+      if let Ok(revision) = arg_to_number_in_range(&args["revision"], "MST revision", 0, 65535) {
+        switch.stp.set_mst_revision(revision as u16);
+      }
+    }
+  },
+  Command {
+    pattern: &["spanning-tree", "mst", "max-hops", "<hops>"],
+    description: "Set hop count after which MST instance information is discarded (default 20)",
+    handler: | switch, _, _, _, args | {
+      if let Ok(hops) = arg_to_number_in_range(&args["hops"], "max hops", 1, 255) {
+        switch.stp.set_max_hops(hops as u8);
+      }
+    }
+  },
+  Command {
+    pattern: &["spanning-tree", "mst", "instance", "<instance>", "vlan", "<vlans>"],
+    description: "Map vlans to MST instance, the instance is created if missing",
+    handler: | switch, _, _, _, args | {
+      if let Ok(instance) = arg_to_number_in_range(&args["instance"], "MST instance", 1, MAX_MST_INSTANCE_ID as u32)
+        && let Ok(vlans) = arg_to_vlans(&args["vlans"])
+        && let Err(err) = switch.stp.map_mst_vlans(instance as u16, &vlans) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["no", "spanning-tree", "mst", "instance", "<instance>", "vlan", "<vlans>"],
+    description: "Map vlans of MST instance back to the CIST",
+    handler: | switch, _, _, _, args | {
+      if let Ok(instance) = arg_to_number_in_range(&args["instance"], "MST instance", 1, MAX_MST_INSTANCE_ID as u32)
+        && let Ok(vlans) = arg_to_vlans(&args["vlans"])
+        && let Err(err) = switch.stp.unmap_mst_vlans(instance as u16, Some(&vlans)) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["no", "spanning-tree", "mst", "instance", "<instance>"],
+    description: "Delete MST instance, its vlans are mapped back to the CIST",
+    handler: | switch, _, _, _, args | {
+      if let Ok(instance) = arg_to_number_in_range(&args["instance"], "MST instance", 1, MAX_MST_INSTANCE_ID as u32)
+        && let Err(err) = switch.stp.remove_mst_instance(instance as u16) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["spanning-tree", "mst", "<instance>", "priority", "<priority>"],
+    description: "Set bridge priority in MST instance, a multiple of 4096 (default 32768)",
+    handler: | switch, _, _, _, args | {
+      if let Ok(instance) = arg_to_number_in_range(&args["instance"], "MST instance", 0, MAX_MST_INSTANCE_ID as u32)
+        && let Ok(priority) = arg_to_number_in_range(&args["priority"], "bridge priority", 0, 61440)
+        && let Err(err) = switch.stp.set_priority(instance as u16, priority as u16) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["show", "lldp", "neighbors"],
+    description: "Display neighbours advertised by LLDP on each interface",
+    handler: | switch, _, _, _, _ | {
+      print!("{}", switch.lldp.format_neighbors())
+    }
+  },
+  Command {
+    pattern: &["show", "lldp", "neighbors", "detail"],
+    description: "Display all information advertised by LLDP neighbours",
+    handler: | switch, _, _, _, _ | {
+      print!("{}", switch.lldp.format_neighbors_detail())
+    }
+  },
+  Command {
+    pattern: &["lldp", "run"],
+    description: "Enable LLDP, interfaces are advertised every 30 seconds",
+    handler: | switch, _, _, _, _ | {
+      switch.lldp.set_enabled(true);
+    }
+  },
+  Command {
+    pattern: &["no", "lldp", "run"],
+    description: "Disable LLDP, neighbours are flushed and LLDPDUs flooded",
+    handler: | switch, _, _, _, _ | {
+      switch.lldp.set_enabled(false);
+    }
+  },
+  Command {
+    pattern: &["show", "etherchannel", "summary"],
+    description: "Display port-channels and the state of their members",
+    handler: | switch, _, _, _, _ | {
+      print!("{}", switch.lag.format_summary())
+    }
+  },
+  Command {
+    pattern: &["show", "lacp", "neighbor"],
+    description: "Display LACP partner of each channel group member",
+    handler: | switch, _, _, _, _ | {
+      print!("{}", switch.lag.format_neighbors())
+    }
+  },
+  Command {
+    pattern: &["lacp", "system-priority", "<priority>"],
+    description: "Set LACP system priority, lowest system selects the aggregated ports (default 32768)",
+    handler: | switch, _, _, _, args | {
+      if let Ok(priority) = arg_to_number_in_range(&args["priority"], "system priority", 1, 65535) {
+        switch.lag.set_system_priority(priority as u16);
+      }
+    }
+  },
+  Command {
+    pattern: &["port-channel", "load-balance", "<method>"],
+    description: "Set fields hashed to select port-channel member: src-mac, dst-mac, src-dst-mac (default), src-dst-mac-vlan, src-dst-ip or src-dst-ip-l4",
+    handler: | switch, _, _, _, args | {
+      let load_balance = match &args["method"][..] {
+        "src-mac" => LoadBalance::SrcMac,
+        "dst-mac" => LoadBalance::DstMac,
+        "src-dst-mac" => LoadBalance::SrcDstMac,
+        "src-dst-mac-vlan" => LoadBalance::SrcDstMacVlan,
+        "src-dst-ip" => LoadBalance::SrcDstIp,
+        "src-dst-ip-l4" => LoadBalance::SrcDstIpL4,
+        other => {
+          eprintln!("Error: invalid load balance method \"{}\". Must be src-mac, dst-mac, src-dst-mac, src-dst-mac-vlan, src-dst-ip or src-dst-ip-l4", other);
+          return
+        }
+      };
+      switch.lag.set_load_balance(load_balance);
+    }
+  },
   Command {
     pattern: &["debug"],
     description: "Set entire device and all interfaces in debug mode",
-    handler: | intfs_view, _, _, _, _, _ | {
-      for (_, view) in intfs_view {
+    handler: | switch, _, _, _, _ | {
+      for (_, view) in &switch.intfs_view {
         view.set_debug_mode(true);
       }
     }
@@ -59,8 +655,8 @@ pub const GENERAL_COMMANDS: &[Command] = &[
   Command {
     pattern: &["no", "debug"],
     description: "Disable debug mode for entire device and interfaces",
-    handler: | intfs_view, _, _, _, _, _ | {
-      for (_, view) in intfs_view {
+    handler: | switch, _, _, _, _ | {
+      for (_, view) in &switch.intfs_view {
         view.set_debug_mode(false);
       }
     }
@@ -68,16 +664,17 @@ pub const GENERAL_COMMANDS: &[Command] = &[
   Command {
     pattern: &["counters", "reset"],
     description: "Reset all counters on the device",
-    handler: | intfs_view, _, _, _, _, _ | {
-      for (_, view) in intfs_view {
+    handler: | switch, _, _, _, _ | {
+      for (_, view) in &switch.intfs_view {
         view.reset_counters();
       }
+      switch.fib.reset_counters();
     }
   },
   Command {
     pattern: &["show", "config"],
     description: "Display current running configuration",
-    handler: | _, _, _, _, config, _ | {
+    handler: | _, _, _, config, _ | {
       //TODO
       todo!()
       //print!("{}", config_to_str(config))
@@ -86,7 +683,7 @@ pub const GENERAL_COMMANDS: &[Command] = &[
   Command {
     pattern: &["config", "save", "<filename>"],
     description: "Save current running configuration at <filename>",
-    handler: | _, _, _, _, config, _args | {
+    handler: | _, _, _, config, _args | {
       todo!()
       //TODO
       /*let filepath = &args["filename"]
@@ -100,21 +697,22 @@ pub const GENERAL_COMMANDS: &[Command] = &[
   Command {
     pattern: &["config", "load", "<filename>"],
     description: "Replace running configuration with config stored at <filename>",
-    handler: | _, _, _, _, _, _ | {
+    handler: | _, _, _, _, _ | {
       //TODO
     }
   },
   Command {
     pattern: &["help"],
     description: "Display this help menu with available commandes",
-    handler: | _, _, mode, _, _, _ | {
+    handler: | _, mode, _, _, _ | {
       display_candidates_help_menu(mode.load().as_ref(), &String::new());
     },
   },
   Command {
     pattern: &["exit"],
     description: "Exit and shutdown program",
-    handler: | _, _, _, _, _, _ | {
+    handler: | switch, _, _, _, _ | {
+      switch.fib.save_on_exit();
       process::exit(0)
     }
   },
@@ -124,144 +722,692 @@ pub const INTF_COMMANDS: &[Command] = &[
   Command {
     pattern: &["show"],
     description: "Show interface information",
-    handler: | _, _, _, intf, _, _ | {
+    handler: | _, _, intf, _, _ | {
       println!("{}", intf)
     },
   },
   Command {
-    pattern: &["debug"],
-    description: "Enable debug mode on interface",
-    handler: | _, _, _, curr_intf, config, _ | {
-      curr_intf.set_debug_mode(true);
-      //config.entry(mode.clone()).or_insert(HashSet::new()).insert(cmd.clone());
-    },
+    pattern: &["debug"],
+    description: "Enable debug mode on interface",
+    handler: | _, _, curr_intf, config, _ | {
+      curr_intf.set_debug_mode(true);
+      //config.entry(mode.clone()).or_insert(HashSet::new()).insert(cmd.clone());
+    },
+  },
+  Command {
+    pattern: &["no", "debug"],
+    description: "Disable debug mode on interface",
+    handler: | _, _, intf, _, _ | {
+      intf.set_debug_mode(false)
+    },
+  },
+  Command {
+    pattern: &["shutdown"],
+    description: "Shut the interface off stopping ingress/egress traffic",
+    handler: | _, _, curr_intf, _, _ | {
+      curr_intf.send_cmd(IntfCmd::Shutdown);
+      //config.entry(mode.clone()).or_insert(HashSet::new()).insert(cmd.clone());
+    }
+  },
+  Command {
+    pattern: &["no", "shutdown"],
+    description: "Bring interface up re-establshing ingress/egress traffic",
+    handler: | _, _, intf, _, _ | {
+      intf.send_cmd(IntfCmd::NoShutdown)
+    },
+  },
+  Command {
+    pattern: &["counters", "reset"],
+    description: "Reset all interface counters to 0",
+    handler: | _, _, intf, _, _ | {
+      intf.reset_counters()
+    },
+  },
+  Command {
+    pattern: &["switchport", "mode", "access"],
+    description: "Set interface in vlan access mode",
+    handler: | _, _, intf, _, _ | {
+      intf.send_cmd(IntfCmd::PortModeAccess);
+    }
+  },
+  Command {
+    pattern: &["switchport", "mode", "dot1q-tunnel"],
+    description: "Set interface in vlan tunneling mode",
+    handler: | _, _, intf, _, _ | {
+      intf.send_cmd(IntfCmd::PortModeVlanTunnel);
+    }
+  },
+  Command {
+    pattern: &["switchport", "dot1q-tunnel", "map", "<cvlans>", "svlan", "<svlan>"],
+    description: "Tunnel given c-vlans (e.g. 10-20,30) in <svlan> instead of interface vlan",
+    handler: | switch, _, intf, _, args | {
+      let mode = intf.get_port_mode();
+      let PortMode::VlanTunnel{..} = mode else {
+        eprintln!("Error: invalid switchport mode \"{}\". Interface must be in dot1q-tunnel mode", mode);
+        return
+      };
+      if let Ok(cvlans) = arg_to_vlans(&args["cvlans"]) && let Ok(svlan) = arg_to_vlan(&args["svlan"]) {
+        if switch.vlans.create(svlan) {
+          println!("Vlan {} does not exist, creating it", svlan);
+        }
+        intf.send_cmd(IntfCmd::PortVlanTunnelMap(cvlans, svlan));
+      }
+    }
+  },
+  Command {
+    pattern: &["no", "switchport", "dot1q-tunnel", "map", "<cvlans>"],
+    description: "Remove c-vlans mapping, they are tunneled according to default action",
+    handler: | _, _, intf, _, args | {
+      let mode = intf.get_port_mode();
+      let PortMode::VlanTunnel{..} = mode else {
+        eprintln!("Error: invalid switchport mode \"{}\". Interface must be in dot1q-tunnel mode", mode);
+        return
+      };
+      if let Ok(cvlans) = arg_to_vlans(&args["cvlans"]) {
+        intf.send_cmd(IntfCmd::PortVlanTunnelUnmap(cvlans));
+      }
+    }
+  },
+  Command {
+    pattern: &["switchport", "dot1q-tunnel", "default-action", "<action>"],
+    description: "Action for unmapped c-vlans: drop or svlan (tunnel in interface vlan)",
+    handler: | _, _, intf, _, args | {
+      let mode = intf.get_port_mode();
+      let PortMode::VlanTunnel{..} = mode else {
+        eprintln!("Error: invalid switchport mode \"{}\". Interface must be in dot1q-tunnel mode", mode);
+        return
+      };
+      match &args["action"][..] {
+        "drop" => intf.send_cmd(IntfCmd::PortVlanTunnelDropUnmatched(true)),
+        "svlan" => intf.send_cmd(IntfCmd::PortVlanTunnelDropUnmatched(false)),
+        other => eprintln!("Error: invalid default action \"{}\". Must be drop or svlan", other),
+      }
+    }
+  },
+  Command {
+    pattern: &["spanning-tree", "cost", "<cost>"],
+    description: "Set spanning tree path cost of interface (default 20000)",
+    handler: | switch, _, intf, _, args | {
+      if let Ok(cost) = arg_to_number_in_range(&args["cost"], "path cost", 1, 200000000)
+        && let Err(err) = switch.stp.set_port_cost(&intf.name, 0, cost) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["no", "spanning-tree", "cost"],
+    description: "Reset spanning tree path cost of interface to 20000",
+    handler: | switch, _, intf, _, _ | {
+      if let Err(err) = switch.stp.set_port_cost(&intf.name, 0, DEFAULT_PORT_COST) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["spanning-tree", "port-priority", "<priority>"],
+    description: "Set spanning tree priority of interface, a multiple of 16 (default 128)",
+    handler: | switch, _, intf, _, args | {
+      if let Ok(priority) = arg_to_number_in_range(&args["priority"], "port priority", 0, 240)
+        && let Err(err) = switch.stp.set_port_priority(&intf.name, 0, priority as u8) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["no", "spanning-tree", "port-priority"],
+    description: "Reset spanning tree priority of interface to 128",
+    handler: | switch, _, intf, _, _ | {
+      if let Err(err) = switch.stp.set_port_priority(&intf.name, 0, DEFAULT_PORT_PRIORITY) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["spanning-tree", "portfast"],
+    description: "Set interface as edge port, forwarding right away until a BPDU is received",
+    handler: | switch, _, intf, _, _ | {
+      if let Err(err) = switch.stp.set_port_edge(&intf.name, true) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["no", "spanning-tree", "portfast"],
+    description: "Remove edge port setting of interface",
+    handler: | switch, _, intf, _, _ | {
+      if let Err(err) = switch.stp.set_port_edge(&intf.name, false) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["spanning-tree", "bpduguard"],
+    description: "Err-disable interface when it receives a BPDU",
+    handler: | switch, _, intf, _, _ | {
+      if let Err(err) = switch.stp.set_port_bpdu_guard(&intf.name, true) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["no", "spanning-tree", "bpduguard"],
+    description: "Disable BPDU guard on interface",
+    handler: | switch, _, intf, _, _ | {
+      if let Err(err) = switch.stp.set_port_bpdu_guard(&intf.name, false) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["spanning-tree", "bpdufilter"],
+    description: "Neither send nor process BPDUs on interface",
+    handler: | switch, _, intf, _, _ | {
+      if let Err(err) = switch.stp.set_port_bpdu_filter(&intf.name, true) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["no", "spanning-tree", "bpdufilter"],
+    description: "Disable BPDU filter on interface",
+    handler: | switch, _, intf, _, _ | {
+      if let Err(err) = switch.stp.set_port_bpdu_filter(&intf.name, false) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["spanning-tree", "guard", "root"],
+    description: "Block interface instead of making it root port when it receives superior BPDUs",
+    handler: | switch, _, intf, _, _ | {
+      if let Err(err) = switch.stp.set_port_guard(&intf.name, PortGuard::Root) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["spanning-tree", "guard", "loop"],
+    description: "Keep interface blocked instead of making it designated when BPDUs stop",
+    handler: | switch, _, intf, _, _ | {
+      if let Err(err) = switch.stp.set_port_guard(&intf.name, PortGuard::Loop) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["no", "spanning-tree", "guard"],
+    description: "Disable root and loop guard on interface",
+    handler: | switch, _, intf, _, _ | {
+      if let Err(err) = switch.stp.set_port_guard(&intf.name, PortGuard::None) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["lldp", "transmit"],
+    description: "Send LLDPDUs on interface (default)",
+    handler: | switch, _, intf, _, _ | {
+      if let Err(err) = switch.lldp.set_port_transmit(&intf.name, true) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["no", "lldp", "transmit"],
+    description: "Stop sending LLDPDUs on interface",
+    handler: | switch, _, intf, _, _ | {
+      if let Err(err) = switch.lldp.set_port_transmit(&intf.name, false) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["lldp", "receive"],
+    description: "Learn LLDP neighbours on interface (default)",
+    handler: | switch, _, intf, _, _ | {
+      if let Err(err) = switch.lldp.set_port_receive(&intf.name, true) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["no", "lldp", "receive"],
+    description: "Drop LLDPDUs received on interface and flush its neighbours",
+    handler: | switch, _, intf, _, _ | {
+      if let Err(err) = switch.lldp.set_port_receive(&intf.name, false) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["channel-group", "<group>", "mode", "<mode>"],
+    description: "Add interface to port-channel, LACP mode is active or passive",
+    handler: | switch, _, intf, _, args | {
+      let mode = match &args["mode"][..] {
+        "active" => LacpMode::Active,
+        "passive" => LacpMode::Passive,
+        other => {
+          eprintln!("Error: invalid LACP mode \"{}\". Must be active or passive", other);
+          return
+        }
+      };
+      if let Ok(group) = arg_to_number_in_range(&args["group"], "channel group", 1, MAX_PORT_CHANNELS as u32)
+        && let Err(err) = switch.lag.set_channel_group(&intf.name, Some((group as usize - 1, mode))) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["no", "channel-group"],
+    description: "Remove interface from its port-channel",
+    handler: | switch, _, intf, _, _ | {
+      if let Err(err) = switch.lag.set_channel_group(&intf.name, None) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["lacp", "rate", "fast"],
+    description: "Ask partner to send LACPDUs every second, timeout is 3 seconds",
+    handler: | switch, _, intf, _, _ | {
+      if let Err(err) = switch.lag.set_rate(&intf.name, true) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["lacp", "rate", "normal"],
+    description: "Ask partner to send LACPDUs every 30 seconds, timeout is 90 seconds (default)",
+    handler: | switch, _, intf, _, _ | {
+      if let Err(err) = switch.lag.set_rate(&intf.name, false) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["spanning-tree", "mst", "<instance>", "cost", "<cost>"],
+    description: "Set spanning tree path cost of interface in MST instance (default 20000)",
+    handler: | switch, _, intf, _, args | {
+      if let Ok(instance) = arg_to_number_in_range(&args["instance"], "MST instance", 0, MAX_MST_INSTANCE_ID as u32)
+        && let Ok(cost) = arg_to_number_in_range(&args["cost"], "path cost", 1, 200000000)
+        && let Err(err) = switch.stp.set_port_cost(&intf.name, instance as u16, cost) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["spanning-tree", "mst", "<instance>", "port-priority", "<priority>"],
+    description: "Set spanning tree priority of interface in MST instance, a multiple of 16 (default 128)",
+    handler: | switch, _, intf, _, args | {
+      if let Ok(instance) = arg_to_number_in_range(&args["instance"], "MST instance", 0, MAX_MST_INSTANCE_ID as u32)
+        && let Ok(priority) = arg_to_number_in_range(&args["priority"], "port priority", 0, 240)
+        && let Err(err) = switch.stp.set_port_priority(&intf.name, instance as u16, priority as u8) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["switchport", "private-vlan", "host-association", "<primary>", "<secondary>"],
+    description: "Set interface as private vlan host port of secondary vlan",
+    handler: | switch, _, intf, _, args | {
+      let (Ok(primary), Ok(secondary)) = (arg_to_vlan(&args["primary"]), arg_to_vlan(&args["secondary"])) else {
+        return
+      };
+      if secondary == primary || switch.vlans.primary_vlan(secondary) != primary {
+        eprintln!("Error: vlan {} is not a secondary vlan associated to primary vlan {}", secondary, primary);
+        return
+      }
+      intf.send_cmd(IntfCmd::PortPrivateVlanHost(primary, secondary));
+    }
+  },
+  Command {
+    pattern: &["switchport", "private-vlan", "mapping", "<primary>", "<secondaries>"],
+    description: "Set interface as private vlan promiscuous port of primary and secondary vlans",
+    handler: | switch, _, intf, _, args | {
+      let (Ok(primary), Ok(secondaries)) = (arg_to_vlan(&args["primary"]), arg_to_vlans(&args["secondaries"])) else {
+        return
+      };
+      if !matches!(switch.vlans.get_private_vlan(primary), Some(PrivateVlan::Primary(_))) {
+        eprintln!("Error: vlan {} is not a primary private vlan", primary);
+        return
+      }
+      let not_associated: Vec<&u16> = secondaries.iter()
+        .filter(|secondary| **secondary == primary || switch.vlans.primary_vlan(**secondary) != primary)
+        .collect();
+      if !not_associated.is_empty() {
+        eprintln!("Error: vlans \"{}\" are not secondary vlans associated to primary vlan {}",
+          format_vlan_list(not_associated), primary);
+        return
+      }
+      intf.send_cmd(IntfCmd::PortPrivateVlanPromiscuous(primary, secondaries));
+    }
+  },
+  Command {
+    pattern: &["switchport", "dot1q", "ethertype", "<ethertype>"],
+    description: "Set ether type of outer vlan tag on interface (e.g. 0x88a8)",
+    handler: | _, _, intf, _, args | {
+      if let Ok(tpid) = arg_to_ethertype(&args["ethertype"]) {
+        intf.send_cmd(IntfCmd::PortDot1qEthertype(tpid));
+      }
+    }
+  },
+  Command {
+    pattern: &["no", "switchport", "dot1q", "ethertype"],
+    description: "Reset ether type of outer vlan tag on interface to 0x8100",
+    handler: | _, _, intf, _, _ | {
+      intf.send_cmd(IntfCmd::PortDot1qEthertype(DOT1Q_TPID));
+    }
+  },
+  Command {
+    pattern: &["switchport", "mode", "trunk"],
+    description: "Set interface in Vlan trunk mode",
+    handler: | _, _, intf, _, _ | {
+      intf.send_cmd(IntfCmd::PortModeTrunk);
+    }
+  },
+  Command {
+    pattern: &["switchport", "mode", "hybrid"],
+    description: "Set interface in hybrid mode with both untagged and tagged vlans",
+    handler: | _, _, intf, _, _ | {
+      intf.send_cmd(IntfCmd::PortModeHybrid);
+    }
+  },
+  Command {
+    pattern: &["switchport", "hybrid", "pvid", "<vlan>"],
+    description: "Set vlan of untagged frames on hybrid interface",
+    handler: | switch, _, intf, _, args | {
+      let mode = intf.get_port_mode();
+      let PortMode::Hybrid{..} = mode else {
+        eprintln!("Error: invalid switchport mode \"{}\". Interface must be in hybrid mode", mode);
+        return
+      };
+      if let Ok(vlan) = arg_to_vlan(&args["vlan"]) {
+        if switch.vlans.create(vlan) {
+          println!("Vlan {} does not exist, creating it", vlan);
+        }
+        intf.send_cmd(IntfCmd::PortHybridPvid(vlan));
+      }
+    }
+  },
+  Command {
+    pattern: &["no", "switchport", "hybrid", "pvid"],
+    description: "Reset vlan of untagged frames on hybrid interface to default vlan 1",
+    handler: | _, _, intf, _, _ | {
+      let mode = intf.get_port_mode();
+      let PortMode::Hybrid{..} = mode else {
+        eprintln!("Error: invalid switchport mode \"{}\". Interface must be in hybrid mode", mode);
+        return
+      };
+      intf.send_cmd(IntfCmd::PortHybridPvid(DEFAULT_VLAN));
+    }
+  },
+  Command {
+    pattern: &["switchport", "hybrid", "tagged", "vlans", "<vlans>"],
+    description: "Add vlans sent tagged on hybrid interface (e.g. 10-20,30)",
+    handler: | switch, _, intf, _, args | {
+      let mode = intf.get_port_mode();
+      let PortMode::Hybrid{..} = mode else {
+        eprintln!("Error: invalid switchport mode \"{}\". Interface must be in hybrid mode", mode);
+        return
+      };
+      if let Ok(vlans) = arg_to_vlans(&args["vlans"]) {
+        create_vlans(switch, &vlans);
+        intf.send_cmd(IntfCmd::PortHybridAddVlans(vlans, false));
+      }
+    }
+  },
+  Command {
+    pattern: &["switchport", "hybrid", "untagged", "vlans", "<vlans>"],
+    description: "Add vlans sent untagged on hybrid interface (e.g. 10-20,30)",
+    handler: | switch, _, intf, _, args | {
+      let mode = intf.get_port_mode();
+      let PortMode::Hybrid{..} = mode else {
+        eprintln!("Error: invalid switchport mode \"{}\". Interface must be in hybrid mode", mode);
+        return
+      };
+      if let Ok(vlans) = arg_to_vlans(&args["vlans"]) {
+        create_vlans(switch, &vlans);
+        intf.send_cmd(IntfCmd::PortHybridAddVlans(vlans, true));
+      }
+    }
+  },
+  Command {
+    pattern: &["no", "switchport", "hybrid", "vlans", "<vlans>"],
+    description: "Remove tagged or untagged vlans from hybrid interface",
+    handler: | _, _, intf, _, args | {
+      let mode = intf.get_port_mode();
+      let PortMode::Hybrid{..} = mode else {
+        eprintln!("Error: invalid switchport mode \"{}\". Interface must be in hybrid mode", mode);
+        return
+      };
+      if let Ok(vlans) = arg_to_vlans(&args["vlans"]) {
+        intf.send_cmd(IntfCmd::PortHybridRemoveVlans(vlans));
+      }
+    }
   },
   Command {
-    pattern: &["no", "debug"],
-    description: "Disable debug mode on interface",
-    handler: | _, _, _, intf, _, _ | {
-      intf.set_debug_mode(false)
-    },
+    pattern: &["switchport", "trunk", "vlans", "add", "<vlans>"],
+    description: "Add allowed vlans for interface (e.g. 10-20,30)",
+    handler: | switch, _, intf, _, args | {
+      let mode = intf.get_port_mode();
+      let PortMode::Trunk{..} = mode else {
+        eprintln!("Error: invalid switchport mode \"{}\". Interface must be in trunk mode", mode);
+        return
+      };
+      if let Ok(vlans) = arg_to_vlans(&args["vlans"]) {
+        create_vlans(switch, &vlans);
+        intf.send_cmd(IntfCmd::PortTrunkAddVlans(vlans));
+      }
+    }
   },
   Command {
-    pattern: &["shutdown"],
-    description: "Shut the interface off stopping ingress/egress traffic",
-    handler: | _, _fib, _, curr_intf, _, _ | {
-      curr_intf.send_cmd(IntfCmd::Shutdown);
-      //config.entry(mode.clone()).or_insert(HashSet::new()).insert(cmd.clone());
+    pattern: &["switchport", "trunk", "vlans", "all"],
+    description: "Allow all vlans on interface",
+    handler: | _, _, intf, _, _ | {
+      let mode = intf.get_port_mode();
+      let PortMode::Trunk{..} = mode else {
+        eprintln!("Error: invalid switchport mode \"{}\". Interface must be in trunk mode", mode);
+        return
+      };
+      println!("{}", MISSING_VLANS_NOTE);
+      intf.send_cmd(IntfCmd::PortTrunkSetVlans((1..4096).collect()));
     }
   },
   Command {
-    pattern: &["no", "shutdown"],
-    description: "Bring interface up re-establshing ingress/egress traffic",
-    handler: | _, _, _, intf, _, _ | {
-      intf.send_cmd(IntfCmd::NoShutdown)
-    },
+    pattern: &["switchport", "trunk", "vlans", "except", "<vlans>"],
+    description: "Allow all vlans on interface but given ones (e.g. 10-20,30)",
+    handler: | _, _, intf, _, args | {
+      let mode = intf.get_port_mode();
+      let PortMode::Trunk{..} = mode else {
+        eprintln!("Error: invalid switchport mode \"{}\". Interface must be in trunk mode", mode);
+        return
+      };
+      if let Ok(excluded) = arg_to_vlans(&args["vlans"]) {
+        println!("{}", MISSING_VLANS_NOTE);
+        intf.send_cmd(IntfCmd::PortTrunkSetVlans((1..4096).filter(|vlan| !excluded.contains(vlan)).collect()));
+      }
+    }
   },
   Command {
-    pattern: &["counters", "reset"],
-    description: "Reset all interface counters to 0",
-    handler: | _, _, _, intf, _, _ | {
-      intf.reset_counters()
-    },
+    pattern: &["switchport", "trunk", "vlans", "remove", "<vlans>"],
+    description: "Remove allowed vlans for interface (e.g. 10-20,30)",
+    handler: | _, _, intf, _, args | {
+      let mode = intf.get_port_mode();
+      let PortMode::Trunk{vlans: allowed_vlans, ..} = mode else {
+        eprintln!("Error: invalid switchport mode \"{}\". Interface must be in trunk mode", mode);
+        return
+      };
+      if let Ok(vlans) = arg_to_vlans(&args["vlans"]) {
+        let not_allowed: Vec<&u16> = vlans.iter().filter(|vlan| !allowed_vlans.contains(vlan)).collect();
+        if !not_allowed.is_empty() {
+          eprintln!("Error: trunk port does not allow vlans \"{}\". Allowed vlans {}",
+            format_vlan_list(not_allowed), format_vlan_list(&allowed_vlans));
+        } else {
+          intf.send_cmd(IntfCmd::PortTrunkRemoveVlans(vlans));
+        }
+      }
+    }
   },
   Command {
-    pattern: &["switchport", "mode", "access"],
-    description: "Set interface in vlan access mode",
-    handler: | _, _fib, _, intf, _, _ | {
-      intf.send_cmd(IntfCmd::PortModeAccess);
+    pattern: &["switchport", "trunk", "native", "vlan", "<vlan>"],
+    description: "Set vlan of untagged frames on trunk interface",
+    handler: | switch, _, intf, _, args | {
+      let mode = intf.get_port_mode();
+      let PortMode::Trunk{..} = mode else {
+        eprintln!("Error: invalid switchport mode \"{}\". Interface must be in trunk mode", mode);
+        return
+      };
+      if let Ok(vlan) = arg_to_vlan(&args["vlan"]) {
+        if switch.vlans.create(vlan) {
+          println!("Vlan {} does not exist, creating it", vlan);
+        }
+        intf.send_cmd(IntfCmd::PortTrunkNativeVlan(Some(vlan)));
+      }
     }
   },
   Command {
-    pattern: &["switchport", "mode", "dot1q-tunnel"],
-    description: "Set interface in vlan tunneling mode",
-    handler: | _, _fib, _, intf, _, _ | {
-      intf.send_cmd(IntfCmd::PortModeVlanTunnel);
+    pattern: &["no", "switchport", "trunk", "native", "vlan"],
+    description: "Remove native vlan, untagged frames are dropped on trunk interface",
+    handler: | _, _, intf, _, _ | {
+      let mode = intf.get_port_mode();
+      let PortMode::Trunk{..} = mode else {
+        eprintln!("Error: invalid switchport mode \"{}\". Interface must be in trunk mode", mode);
+        return
+      };
+      intf.send_cmd(IntfCmd::PortTrunkNativeVlan(None));
     }
   },
   Command {
-    pattern: &["switchport", "mode", "trunk"],
-    description: "Set interface in Vlan trunk mode",
-    handler: | _, _fib, _, intf, _, _ | {
+    pattern: &["no", "switchport", "trunk", "vlans"],
+    description: "Remove allowed vlans for interface",
+    handler: | _, _, intf, _, _ | {
+      let mode = intf.get_port_mode();
+      let PortMode::Trunk{..} = mode else {
+        eprintln!("Error: invalid switchport mode \"{}\". Interface must be in trunk mode", mode);
+        return
+      };
       intf.send_cmd(IntfCmd::PortModeTrunk);
     }
   },
   Command {
-    pattern: &["switchport", "trunk", "vlans", "add", "<vlan>"],
-    description: "Add allowed vlans for interface",
-    handler: | _, _, _, intf, _, args | {
+    pattern: &["switchport", "vlan", "translation", "<in_vlans>", "<new_vlans>"],
+    description: "Add vlan mappings for interface translating <in_vlans> to <new_vlans> in order",
+    handler: | switch, _, intf, _, args | {
       let mode = intf.get_port_mode();
       let PortMode::Trunk{..} = mode else {
         eprintln!("Error: invalid switchport mode \"{}\". Interface must be in trunk mode", mode);
         return
       };
-      if let Ok(vlan) = arg_to_vlan(&args["vlan"]) {
-        intf.send_cmd(IntfCmd::PortTrunkAddVlans(vec!(vlan)));
+      if let Ok(mappings) = arg_to_vlan_mappings(&args["in_vlans"], &args["new_vlans"]) {
+        create_vlans(switch, &mappings.iter().map(|(_, new_vlan)| *new_vlan).collect::<Vec<u16>>());
+        for (in_vlan, new_vlan) in mappings {
+          intf.send_cmd(IntfCmd::PortAddVlanTranslation(in_vlan, new_vlan));
+        }
       }
     }
   },
   Command {
-    pattern: &["switchport", "trunk", "vlans", "remove", "<vlans>"],
-    description: "Remove allowed vlans for interface",
-    handler: | _, _fib, _, intf, _, args | {
+    pattern: &["switchport", "vlan", "translation", "ingress", "<in_vlans>", "<new_vlans>"],
+    description: "Translate <in_vlans> to <new_vlans> on ingress only, <new_vlans> may be a single vlan",
+    handler: | switch, _, intf, _, args | {
       let mode = intf.get_port_mode();
-      let PortMode::Trunk{vlans: allowed_vlans, ..} = mode else {
+      let PortMode::Trunk{..} = mode else {
         eprintln!("Error: invalid switchport mode \"{}\". Interface must be in trunk mode", mode);
         return
       };
-      if let Ok(vlan) = arg_to_vlan(&args["vlans"]) {
-        if !allowed_vlans.contains(&vlan) {
-          eprintln!("Error: trunk port does not allow vlan \"{}\". Allowed vlans {:?}", vlan, allowed_vlans);
-        } else {
-          intf.send_cmd(IntfCmd::PortTrunkRemoveVlans(vec!(vlan)));
+      if let Ok(mappings) = arg_to_vlan_mappings_many_to_one(&args["in_vlans"], &args["new_vlans"]) {
+        create_vlans(switch, &mappings.iter().map(|(_, new_vlan)| *new_vlan).collect::<Vec<u16>>());
+        for (in_vlan, new_vlan) in mappings {
+          intf.send_cmd(IntfCmd::PortAddIngressVlanTranslation(in_vlan, new_vlan));
         }
       }
     }
   },
   Command {
-    pattern: &["no", "switchport", "trunk", "vlans"],
-    description: "Remove allowed vlans for interface",
-    handler: | _, _fib, _, intf, _, _ | {
+    pattern: &["no", "switchport", "vlan", "translation", "ingress", "<in_vlans>"],
+    description: "Remove ingress vlan translations of <in_vlans>",
+    handler: | _, _, intf, _, args | {
       let mode = intf.get_port_mode();
       let PortMode::Trunk{..} = mode else {
         eprintln!("Error: invalid switchport mode \"{}\". Interface must be in trunk mode", mode);
         return
       };
-      intf.send_cmd(IntfCmd::PortModeTrunk);
+      if let Ok(in_vlans) = arg_to_vlans(&args["in_vlans"]) {
+        for in_vlan in in_vlans {
+          intf.send_cmd(IntfCmd::PortRemoveIngressVlanTranslation(in_vlan));
+        }
+      }
+    }
+  },
+  Command {
+    pattern: &["switchport", "vlan", "translation", "egress", "<in_vlans>", "<new_vlans>"],
+    description: "Translate <in_vlans> to <new_vlans> on egress only",
+    handler: | switch, _, intf, _, args | {
+      let mode = intf.get_port_mode();
+      let PortMode::Trunk{vlan_translations, ..} = mode else {
+        eprintln!("Error: invalid switchport mode \"{}\". Interface must be in trunk mode", mode);
+        return
+      };
+      let Ok(mappings) = arg_to_vlan_mappings(&args["in_vlans"], &args["new_vlans"]) else {
+        return
+      };
+      // A vlan leaves with a single tag and a tag identifies a single vlan
+      let mut egress = vlan_translations.1;
+      for (in_vlan, new_vlan) in &mappings {
+        if let Some(current) = egress.get(in_vlan) && current != new_vlan {
+          eprintln!("Error: vlan {} is already translated to {} on egress", in_vlan, current);
+          return
+        }
+        if let Some((other, _)) = egress.iter().find(|(vlan, translated)| *translated == new_vlan && *vlan != in_vlan) {
+          eprintln!("Error: vlan {} is already translated to {} on egress", other, new_vlan);
+          return
+        }
+        egress.insert(*in_vlan, *new_vlan);
+      }
+      // Frames are switched in <in_vlans>, <new_vlans> only exist on the wire
+      create_vlans(switch, &mappings.iter().map(|(in_vlan, _)| *in_vlan).collect::<Vec<u16>>());
+      for (in_vlan, new_vlan) in mappings {
+        intf.send_cmd(IntfCmd::PortAddEgressVlanTranslation(in_vlan, new_vlan));
+      }
     }
   },
   Command {
-    pattern: &["switchport", "vlan", "translation", "<in_vlan>", "<new_vlan>"],
-    description: "Add vlan mapping for interface translating <in_vlan> to <new_vlan>",
-    handler: | _, _, _, intf, _, args | {
+    pattern: &["no", "switchport", "vlan", "translation", "egress", "<in_vlans>"],
+    description: "Remove egress vlan translations of <in_vlans>",
+    handler: | _, _, intf, _, args | {
       let mode = intf.get_port_mode();
       let PortMode::Trunk{..} = mode else {
         eprintln!("Error: invalid switchport mode \"{}\". Interface must be in trunk mode", mode);
         return
       };
-      if let Ok(in_vlan) = arg_to_vlan(&args["in_vlan"]) && let Ok(new_vlan) = arg_to_vlan(&args["new_vlan"]) {
-        intf.send_cmd(IntfCmd::PortAddVlanTranslation(in_vlan, new_vlan));
+      if let Ok(in_vlans) = arg_to_vlans(&args["in_vlans"]) {
+        for in_vlan in in_vlans {
+          intf.send_cmd(IntfCmd::PortRemoveEgressVlanTranslation(in_vlan));
+        }
       }
     }
   },
   Command {
-    pattern: &["no", "switchport", "vlan", "translation", "<in_vlan>", "<new_vlan>"],
-    description: "Remove vlan mapping from interface",
-    handler: | _, _fib, _, intf, _, args | {
+    // Listed after ingress and egress removals as the first matching pattern wins
+    pattern: &["no", "switchport", "vlan", "translation", "<in_vlans>", "<new_vlans>"],
+    description: "Remove vlan mappings from interface",
+    handler: | _, _, intf, _, args | {
       let mode = intf.get_port_mode();
       let PortMode::Trunk{..} = mode else {
         eprintln!("Error: invalid switchport mode \"{}\". Interface must be in trunk mode", mode);
         return
       };
-      if let Ok(in_vlan) = arg_to_vlan(&args["in_vlan"]) && let Ok(new_vlan) = arg_to_vlan(&args["new_vlan"]) {
-        intf.send_cmd(IntfCmd::PortRemoveVlanTranslation(in_vlan, new_vlan));
+      if let Ok(mappings) = arg_to_vlan_mappings(&args["in_vlans"], &args["new_vlans"]) {
+        for (in_vlan, new_vlan) in mappings {
+          intf.send_cmd(IntfCmd::PortRemoveVlanTranslation(in_vlan, new_vlan));
+        }
       }
     }
   },
   Command {
     pattern: &["no", "switchport", "vlan", "translation"],
     description: "Remove all vlan mappings from interface",
-    handler: | _, _fib, _, intf, _, _args | {
+    handler: | _, _, intf, _, _args | {
       let mode = intf.get_port_mode();
       let PortMode::Trunk{..} = mode else {
         eprintln!("Error: invalid switchport mode \"{}\". Interface must be in trunk mode", mode);
@@ -273,9 +1419,9 @@ pub const INTF_COMMANDS: &[Command] = &[
   Command {
     pattern: &["switchport", "mode", "monitor", "<intf>"],
     description: "Set interface in monitor mode to mirror traffic from target interfaces",
-    handler: | intfs_view, _, _, intf, _, args | {
+    handler: | switch, _, intf, _, args | {
       let target = &args["target_intf"];
-      if intfs_view.contains_key(&target[..]) {
+      if switch.intfs_view.contains_key(&target[..]) {
         intf.send_cmd(IntfCmd::PortModeMonitoring(target.to_string()));
       } else {
         println!("Interface {} not found", target);
@@ -285,35 +1431,156 @@ pub const INTF_COMMANDS: &[Command] = &[
   Command {
     pattern: &["switchport", "access", "vlan", "<vlan>"],
     description: "Set vlan group for interface",
-    handler: | _, _fib, _, intf, _, args | {
+    handler: | switch, _, intf, _, args | {
       if let Ok(vlan) = arg_to_vlan(&args["vlan"]) {
         let mode = intf.get_port_mode();
-        match mode {
-        PortMode::Access{..} => intf.send_cmd(IntfCmd::PortAccessVlan(vlan)),
-        PortMode::VlanTunnel{..} => intf.send_cmd(IntfCmd::PortModeVlanTunnelSetVlan(vlan)),
-        _ => eprintln!("Error: invalid switchport mode \"{}\". Interface must be in access or vlan tunnel mode", mode),
+        let cmd = match mode {
+        PortMode::Access{..} => IntfCmd::PortAccessVlan(vlan),
+        PortMode::VlanTunnel{..} => IntfCmd::PortModeVlanTunnelSetVlan(vlan),
+        _ => {
+          eprintln!("Error: invalid switchport mode \"{}\". Interface must be in access or vlan tunnel mode", mode);
+          return
+        }
+        };
+        if switch.vlans.create(vlan) {
+          println!("Vlan {} does not exist, creating it", vlan);
         }
+        intf.send_cmd(cmd);
       }
     },
   },
   Command {
     pattern: &["no", "switchport", "access", "vlan"],
     description: "Reset vlan group for interface to default group 1",
-    handler: | _, _fib, _, intf, _, _ | {
+    handler: | _, _, intf, _, _ | {
       intf.send_cmd(IntfCmd::PortModeAccess);
     },
   },
+  Command {
+    pattern: &["vlan", "classify", "mac", "<mac>", "vlan", "<vlan>"],
+    description: "Classify untagged frames from source MAC or OUI in vlan (e.g. aa:bb:cc:00:00:00/24)",
+    handler: | switch, _, intf, _, args | {
+      let mode = intf.get_port_mode();
+      let PortMode::Access{..} = mode else {
+        eprintln!("Error: invalid switchport mode \"{}\". Interface must be in access mode", mode);
+        return
+      };
+      if let Ok((mac, mask)) = arg_to_mac_mask(&args["mac"]) && let Ok(vlan) = arg_to_vlan(&args["vlan"]) {
+        if switch.vlans.create(vlan) {
+          println!("Vlan {} does not exist, creating it", vlan);
+        }
+        intf.send_cmd(IntfCmd::PortAddMacVlanClassifier(mac, mask, vlan));
+      }
+    }
+  },
+  Command {
+    pattern: &["no", "vlan", "classify", "mac", "<mac>"],
+    description: "Remove source MAC vlan classification rule",
+    handler: | _, _, intf, _, args | {
+      if let Ok((mac, mask)) = arg_to_mac_mask(&args["mac"]) {
+        intf.send_cmd(IntfCmd::PortRemoveMacVlanClassifier(mac, mask));
+      }
+    }
+  },
+  Command {
+    pattern: &["vlan", "classify", "protocol", "<ethertype>", "vlan", "<vlan>"],
+    description: "Classify untagged frames of ether type in vlan (e.g. 0x0806)",
+    handler: | switch, _, intf, _, args | {
+      let mode = intf.get_port_mode();
+      let PortMode::Access{..} = mode else {
+        eprintln!("Error: invalid switchport mode \"{}\". Interface must be in access mode", mode);
+        return
+      };
+      if let Ok(ether_type) = arg_to_ethertype(&args["ethertype"]) && let Ok(vlan) = arg_to_vlan(&args["vlan"]) {
+        if switch.vlans.create(vlan) {
+          println!("Vlan {} does not exist, creating it", vlan);
+        }
+        intf.send_cmd(IntfCmd::PortAddProtocolVlanClassifier(ether_type, vlan));
+      }
+    }
+  },
+  Command {
+    pattern: &["no", "vlan", "classify", "protocol", "<ethertype>"],
+    description: "Remove ether type vlan classification rule",
+    handler: | _, _, intf, _, args | {
+      if let Ok(ether_type) = arg_to_ethertype(&args["ethertype"]) {
+        intf.send_cmd(IntfCmd::PortRemoveProtocolVlanClassifier(ether_type));
+      }
+    }
+  },
+  Command {
+    pattern: &["switchport", "port-security"],
+    description: "Enable port security on interface",
+    handler: | _, _, intf, _, _ | {
+      intf.send_cmd(IntfCmd::PortSecurity);
+    },
+  },
+  Command {
+    pattern: &["switchport", "port-security", "maximum", "<count>"],
+    description: "Set maximum number of MAC addresses learned on interface",
+    handler: | _, _, intf, _, args | {
+      if let Ok(maximum) = arg_to_mac_count(&args["count"]) {
+        intf.send_cmd(IntfCmd::PortSecurityMaximum(maximum));
+      }
+    },
+  },
+  Command {
+    pattern: &["switchport", "port-security", "maximum", "<count>", "vlan", "<vlan>"],
+    description: "Set maximum number of MAC addresses learned on interface for given vlan",
+    handler: | _, _, intf, _, args | {
+      if let Ok(maximum) = arg_to_mac_count(&args["count"]) && let Ok(vlan) = arg_to_vlan(&args["vlan"]) {
+        intf.send_cmd(IntfCmd::PortSecurityVlanMaximum(vlan, maximum));
+      }
+    },
+  },
+  Command {
+    pattern: &["switchport", "port-security", "violation", "<action>"],
+    description: "Set port security violation action: protect, restrict or shutdown",
+    handler: | _, _, intf, _, args | {
+      let action = match &args["action"][..] {
+        "protect" => ViolationAction::Protect,
+        "restrict" => ViolationAction::Restrict,
+        "shutdown" => ViolationAction::Shutdown,
+        other => {
+          eprintln!("Error: invalid violation action \"{}\". Must be protect, restrict or shutdown", other);
+          return
+        }
+      };
+      intf.send_cmd(IntfCmd::PortSecurityViolation(action));
+    },
+  },
+  Command {
+    pattern: &["switchport", "port-security", "mac-address", "sticky"],
+    description: "Turn MAC addresses learned on interface into static entries",
+    handler: | _, _, intf, _, _ | {
+      intf.send_cmd(IntfCmd::PortSecuritySticky(true));
+    },
+  },
+  Command {
+    pattern: &["no", "switchport", "port-security", "mac-address", "sticky"],
+    description: "Stop turning MAC addresses learned on interface into static entries",
+    handler: | _, _, intf, _, _ | {
+      intf.send_cmd(IntfCmd::PortSecuritySticky(false));
+    },
+  },
+  Command {
+    pattern: &["no", "switchport", "port-security"],
+    description: "Disable port security on interface",
+    handler: | _, _, intf, _, _ | {
+      intf.send_cmd(IntfCmd::NoPortSecurity);
+    },
+  },
   Command {
     pattern: &["help"],
     description: "Display this help menu with available commandes",
-    handler: | _, _, mode, _, _, _ | {
+    handler: | _, mode, _, _, _ | {
       display_candidates_help_menu(mode.load().as_ref(), &String::new());
     }
   },
   Command {
     pattern: &["exit"],
     description: "Exit interface configuration mode",
-    handler: | _, _, mode, _, _, _ | {
+    handler: | _, mode, _, _, _ | {
       mode.store(Arc::new(CliMode::General))
     }
   },
@@ -344,16 +1611,135 @@ impl Command<'_> {
     result
   }
 
-  pub fn run(&self, intfs_view: &IntfsViewMap, fib: &Arc<Fib>, mode: &ArcSwap<CliMode>, intf: Arc<InterfaceView>, conf: &mut HashMap<CliMode, HashSet<String>>, cmd: &String ) {
+  pub fn run<'b>(&self, switch: &Switch<'b>, mode: &ArcSwap<CliMode>, intf: Arc<InterfaceView<'b>>, conf: &mut HashMap<CliMode, HashSet<String>>, cmd: &String ) {
     let args = self.extract_args(cmd);
-    (self.handler)(intfs_view, fib, mode, intf, conf, args)
+    (self.handler)(switch, mode, intf, conf, args)
   }
 }
 
+pub const VLAN_COMMANDS: &[Command] = &[
+  Command {
+    pattern: &["name", "<name>"],
+    description: "Set vlan name",
+    handler: | switch, mode, _, _, args | {
+      if let CliMode::Vlan(vlan) = mode.load().as_ref() && let Err(err) = switch.vlans.set_name(*vlan, &args["name"]) {
+        eprintln!("Error: {}", err);
+      }
+    }
+  },
+  Command {
+    pattern: &["state", "<state>"],
+    description: "Set vlan state: active or suspend",
+    handler: | switch, mode, _, _, args | {
+      let CliMode::Vlan(vlan) = *mode.load().as_ref() else {
+        return
+      };
+      let state = match &args["state"][..] {
+        "active" => VlanState::Active,
+        "suspend" => VlanState::Suspend,
+        other => {
+          eprintln!("Error: invalid vlan state \"{}\". Must be active or suspend", other);
+          return
+        }
+      };
+      if let Err(err) = switch.vlans.set_state(vlan, state) {
+        eprintln!("Error: {}", err);
+      } else if state == VlanState::Suspend {
+        switch.fib.clear(&FibFilter::Vlan(vlan));
+      }
+    }
+  },
+  Command {
+    pattern: &["private-vlan", "<type>"],
+    description: "Set private vlan type: primary, isolated or community",
+    handler: | switch, mode, _, _, args | {
+      let CliMode::Vlan(vlan) = *mode.load().as_ref() else {
+        return
+      };
+      let private_vlan = match &args["type"][..] {
+        "primary" => PrivateVlan::Primary(Default::default()),
+        "isolated" => PrivateVlan::Isolated,
+        "community" => PrivateVlan::Community,
+        other => {
+          eprintln!("Error: invalid private vlan type \"{}\". Must be primary, isolated or community", other);
+          return
+        }
+      };
+      let primary = switch.vlans.primary_vlan(vlan);
+      if let Err(err) = switch.vlans.set_private_vlan(vlan, Some(private_vlan)) {
+        eprintln!("Error: {}", err);
+      } else {
+        switch.fib.clear(&FibFilter::Vlan(primary));
+      }
+    }
+  },
+  Command {
+    pattern: &["no", "private-vlan"],
+    description: "Make vlan a regular vlan, removing its private vlan associations",
+    handler: | switch, mode, _, _, _ | {
+      let CliMode::Vlan(vlan) = *mode.load().as_ref() else {
+        return
+      };
+      let primary = switch.vlans.primary_vlan(vlan);
+      if let Err(err) = switch.vlans.set_private_vlan(vlan, None) {
+        eprintln!("Error: {}", err);
+      } else {
+        switch.fib.clear(&FibFilter::Vlan(primary));
+      }
+    }
+  },
+  Command {
+    pattern: &["private-vlan", "association", "<vlans>"],
+    description: "Associate isolated and community vlans to primary vlan (e.g. 101,102-104)",
+    handler: | switch, mode, _, _, args | {
+      let CliMode::Vlan(vlan) = *mode.load().as_ref() else {
+        return
+      };
+      if let Ok(secondaries) = arg_to_vlans(&args["vlans"]) {
+        match switch.vlans.associate_private_vlans(vlan, &secondaries) {
+          Ok(()) => for secondary in secondaries {
+            switch.fib.clear(&FibFilter::Vlan(secondary));
+          },
+          Err(err) => eprintln!("Error: {}", err),
+        }
+      }
+    }
+  },
+  Command {
+    pattern: &["no", "private-vlan", "association"],
+    description: "Remove secondary vlans associated to primary vlan",
+    handler: | switch, mode, _, _, _ | {
+      let CliMode::Vlan(vlan) = *mode.load().as_ref() else {
+        return
+      };
+      if let Err(err) = switch.vlans.dissociate_private_vlans(vlan) {
+        eprintln!("Error: {}", err);
+      } else {
+        switch.fib.clear(&FibFilter::Vlan(vlan));
+      }
+    }
+  },
+  Command {
+    pattern: &["help"],
+    description: "Display this help menu with available commandes",
+    handler: | _, mode, _, _, _ | {
+      display_candidates_help_menu(mode.load().as_ref(), &String::new());
+    },
+  },
+  Command {
+    pattern: &["exit"],
+    description: "Exit vlan configuration mode",
+    handler: | _, mode, _, _, _ | {
+      mode.store(Arc::new(CliMode::General))
+    }
+  },
+];
+
 pub fn display_candidates_help_menu(mode: &CliMode, current_cmd: &String) {
   let cmds =  match mode {
     CliMode::General => GENERAL_COMMANDS,
     CliMode::Interface(_) => INTF_COMMANDS,
+    CliMode::Vlan(_) => VLAN_COMMANDS,
   };
   for cmd in cmds {
     if cmd.pattern.join(" ").starts_with(current_cmd) {
@@ -363,6 +1749,57 @@ pub fn display_candidates_help_menu(mode: &CliMode, current_cmd: &String) {
   println!("");
 }
 
+fn arg_to_mac(mac_str: &str) -> io::Result<MacAddr6> {
+  mac_str.parse::<MacAddr6>().map_err(|_| {
+    eprintln!("Error: invalid MAC address \"{}\". Expected format aa:bb:cc:dd:ee:ff", mac_str);
+    Error::new(ErrorKind::InvalidInput, "Invalid MAC address")
+  })
+}
+
+// MAC with optional mask given as prefix length or MAC, e.g. aa:bb:cc:00:00:00/24
+fn arg_to_mac_mask(mac_str: &str) -> io::Result<(MacAddr6, MacAddr6)> {
+  let Some((mac, mask)) = mac_str.split_once('/') else {
+    return Ok((arg_to_mac(mac_str)?, MacAddr6::broadcast()));
+  };
+  let mac = arg_to_mac(mac)?;
+  if let Ok(len) = mask.parse::<u32>() {
+    if len > 48 {
+      eprintln!("Error: invalid MAC mask length \"{}\". Must be between 0 and 48", len);
+      return Err(Error::new(ErrorKind::InvalidInput, "Invalid MAC mask length"));
+    }
+    let bytes = (u64::MAX << (48 - len)).to_be_bytes();
+    return Ok((mac, MacAddr6::new(bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7])));
+  }
+  Ok((mac, arg_to_mac(mask)?))
+}
+
+fn arg_to_mac_count(count_str: &str) -> io::Result<usize> {
+  match count_str.parse::<usize>() {
+    Ok(count) if count > 0 => Ok(count),
+    _ => {
+      eprintln!("Error: invalid MAC address count \"{}\". Must be a number greater than 0", count_str);
+      Err(Error::new(ErrorKind::InvalidInput, "Must be greater than 0"))
+    }
+  }
+}
+
+fn arg_to_seconds(seconds_str: &str) -> io::Result<u64> {
+  seconds_str.parse::<u64>().map_err(|_| {
+    eprintln!("Error: invalid duration \"{}\". Must be a number of seconds", seconds_str);
+    Error::new(ErrorKind::InvalidInput, "Must be a u64")
+  })
+}
+
+fn arg_to_number_in_range(number_str: &str, name: &str, min: u32, max: u32) -> io::Result<u32> {
+  match number_str.parse::<u32>() {
+    Ok(number) if number >= min && number <= max => Ok(number),
+    _ => {
+      eprintln!("Error: invalid {} \"{}\". Must be a number between {} and {}", name, number_str, min, max);
+      Err(Error::new(ErrorKind::InvalidInput, format!("Invalid {}", name)))
+    }
+  }
+}
+
 fn arg_to_vlan(vlan_str: &String) -> io::Result<u16> {
   match vlan_str.parse::<u16>() {
     Ok(vlan) => {
@@ -379,3 +1816,72 @@ fn arg_to_vlan(vlan_str: &String) -> io::Result<u16> {
     }
   }
 }
+
+// Hexadecimal ether type, e.g. 0x9100
+fn arg_to_ethertype(ethertype_str: &str) -> io::Result<u16> {
+  let digits = ethertype_str.strip_prefix("0x").unwrap_or(ethertype_str);
+  match u16::from_str_radix(digits, 16) {
+    Ok(ethertype) if ethertype >= 0x0600 => Ok(ethertype),
+    _ => {
+      eprintln!("Error: invalid ether type \"{}\". Must be hexadecimal between 0x0600 and 0xffff", ethertype_str);
+      Err(Error::new(ErrorKind::InvalidInput, "Invalid ether type"))
+    }
+  }
+}
+
+// Create vlans missing from the vlan database
+fn create_vlans(switch: &Switch, vlans: &[u16]) {
+  let created: Vec<&u16> = vlans.iter().filter(|vlan| switch.vlans.create(**vlan)).collect();
+  match created[..] {
+    [] => (),
+    [vlan] => println!("Vlan {} does not exist, creating it", vlan),
+    _ => println!("Vlans {} do not exist, creating them", format_vlan_list(created)),
+  }
+}
+
+// Vlan list made of comma separated vlans and ranges, e.g. 10-20,30
+fn arg_to_vlans(vlans_str: &str) -> io::Result<Vec<u16>> {
+  let mut vlans = arg_to_vlans_ordered(vlans_str)?;
+  vlans.sort_unstable();
+  vlans.dedup();
+  Ok(vlans)
+}
+
+// Vlan list in the order given
+fn arg_to_vlans_ordered(vlans_str: &str) -> io::Result<Vec<u16>> {
+  let mut vlans = Vec::new();
+  for item in vlans_str.split(',') {
+    match item.split_once('-') {
+      Some((first, last)) => {
+        let (first, last) = (arg_to_vlan(&first.to_string())?, arg_to_vlan(&last.to_string())?);
+        if first > last {
+          eprintln!("Error: invalid vlan range \"{}\". First vlan must not be greater than last", item);
+          return Err(Error::new(ErrorKind::InvalidInput, "Invalid vlan range"));
+        }
+        vlans.extend(first..=last);
+      }
+      None => vlans.push(arg_to_vlan(&item.to_string())?),
+    }
+  }
+  Ok(vlans)
+}
+
+// Pairs vlans of two lists of the same size, e.g. 10-12 and 20,22,24
+fn arg_to_vlan_mappings(in_vlans_str: &str, new_vlans_str: &str) -> io::Result<Vec<(u16, u16)>> {
+  let in_vlans = arg_to_vlans_ordered(in_vlans_str)?;
+  let new_vlans = arg_to_vlans_ordered(new_vlans_str)?;
+  if in_vlans.len() != new_vlans.len() {
+    eprintln!("Error: vlan lists \"{}\" and \"{}\" must have the same number of vlans", in_vlans_str, new_vlans_str);
+    return Err(Error::new(ErrorKind::InvalidInput, "Vlan lists size mismatch"));
+  }
+  Ok(in_vlans.into_iter().zip(new_vlans).collect())
+}
+
+// Same as arg_to_vlan_mappings, a single new vlan is used for all vlans
+fn arg_to_vlan_mappings_many_to_one(in_vlans_str: &str, new_vlans_str: &str) -> io::Result<Vec<(u16, u16)>> {
+  if !new_vlans_str.contains([',', '-']) {
+    let new_vlan = arg_to_vlan(&new_vlans_str.to_string())?;
+    return Ok(arg_to_vlans_ordered(in_vlans_str)?.into_iter().map(|in_vlan| (in_vlan, new_vlan)).collect());
+  }
+  arg_to_vlan_mappings(in_vlans_str, new_vlans_str)
+}
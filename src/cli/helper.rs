@@ -29,6 +29,7 @@ impl Completer for CommandHelper<'_> {
     let cmds = match self.mode.load().as_ref() {
       CliMode::General => commands::GENERAL_COMMANDS,
       CliMode::Interface(_) => commands::INTF_COMMANDS,
+      CliMode::Vlan(_) => commands::VLAN_COMMANDS,
     };
 
     'main: for cmd in cmds {
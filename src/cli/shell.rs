@@ -7,15 +7,13 @@ use rustyline::error::ReadlineError;
 
 use super::helper;
 use super::commands;
-use crate::network::interface::InterfaceView;
-use crate::fib::Fib;
-
-pub type IntfsViewMap<'a> = HashMap<&'a str, Arc<InterfaceView<'a>>>;
+use crate::Switch;
 
 #[derive(PartialOrd, Ord, Clone, Eq, PartialEq, Hash)]
 pub enum CliMode {
   General,
   Interface(String),
+  Vlan(u16),
 }
 
 fn generate_prompt(mode: &CliMode) -> String {
@@ -25,20 +23,23 @@ fn generate_prompt(mode: &CliMode) -> String {
     CliMode::Interface(ref if_name) => {
       prompt = format!("{}({})", prompt, if_name);
     },
+    CliMode::Vlan(vlan) => {
+      prompt = format!("{}(vlan {})", prompt, vlan);
+    },
     _ => (),
   };
   prompt += "#";
   prompt
 }
 
-pub fn cli_run(intfs_view: &IntfsViewMap, fib: &Arc<Fib>) {
+pub fn cli_run(switch: &Switch) {
 
   let mut rl = rustyline::Editor::new().unwrap();
   let mut mode = &ArcSwap::new(Arc::new(CliMode::General));
   let mut config = HashMap::new();
   let helper= helper::CommandHelper{
     mode: mode,
-    intfs: intfs_view.keys().cloned().collect() };
+    intfs: switch.intfs_view.keys().cloned().collect() };
   rl.set_helper(Some(helper));
 
   'main: loop {
@@ -51,8 +52,8 @@ pub fn cli_run(intfs_view: &IntfsViewMap, fib: &Arc<Fib>) {
           Ok(ref cmd) => {
             for available_cmd in commands::GENERAL_COMMANDS {
               if available_cmd.matches_pattern(&cmd) {
-                available_cmd.run(intfs_view, fib, &mut mode,
-                  intfs_view.values().next().unwrap().clone(), &mut config, cmd);
+                available_cmd.run(switch, &mut mode,
+                  switch.intfs_view.values().next().unwrap().clone(), &mut config, cmd);
                 if let Ok(cmd) = input && let Err(err) = rl.add_history_entry(&cmd) {
                   eprintln!("Err: {}", err);
                 }
@@ -62,7 +63,10 @@ pub fn cli_run(intfs_view: &IntfsViewMap, fib: &Arc<Fib>) {
             println!("Unknown command")
           },
           Err(ReadlineError::Interrupted) => println!("^C"),
-          Err(ReadlineError::Eof) => process::exit(0),
+          Err(ReadlineError::Eof) => {
+            switch.fib.save_on_exit();
+            process::exit(0)
+          },
           Err(_) => println!("No input"),
         }
       },
@@ -71,8 +75,28 @@ pub fn cli_run(intfs_view: &IntfsViewMap, fib: &Arc<Fib>) {
           Ok(ref cmd) => {
             for available_cmd in commands::INTF_COMMANDS {
               if available_cmd.matches_pattern(&cmd) {
-                available_cmd.run(intfs_view, fib, &mut mode,
-                  intfs_view[&if_name[..]].clone(), &mut config, cmd);
+                available_cmd.run(switch, &mut mode,
+                  switch.intfs_view[&if_name[..]].clone(), &mut config, cmd);
+                if let Ok(cmd) = input && let Err(err) = rl.add_history_entry(&cmd) {
+                  eprintln!("Err: {}", err);
+                }
+                continue 'main;
+              }
+            }
+            println!("Unknown command")
+          },
+          Err(ReadlineError::Interrupted) => println!("^C"),
+          Err(ReadlineError::Eof) => mode.store(Arc::new(CliMode::General)),
+          Err(_) => println!("No input"),
+        }
+      },
+      CliMode::Vlan(_) => {
+        match input {
+          Ok(ref cmd) => {
+            for available_cmd in commands::VLAN_COMMANDS {
+              if available_cmd.matches_pattern(cmd) {
+                available_cmd.run(switch, mode,
+                  switch.intfs_view.values().next().unwrap().clone(), &mut config, cmd);
                 if let Ok(cmd) = input && let Err(err) = rl.add_history_entry(&cmd) {
                   eprintln!("Err: {}", err);
                 }
@@ -101,6 +125,9 @@ fn config_to_str(config: &HashMap<CliMode, HashSet<String>>) -> String {
       CliMode::Interface(ref if_name) => {
         config_str += &format!("interface {}\n", if_name)[..];
       },
+      CliMode::Vlan(vlan) => {
+        config_str += &format!("vlan {}\n", vlan)[..];
+      },
     }
     for cmd in &config[&mode] {
       config_str += &format!("  {}\n", cmd)[..];
@@ -2,8 +2,8 @@ use std::sync::Arc;
 use crossbeam_channel::Receiver;
 use dashmap::DashMap;
 
-use crate::network::interface::{Interface, InterfaceView, IntfCmd, DEFAULT_VLAN};
-use crate::fib::Fib;
+use crate::network::interface::{ErrDisableReason, Interface, InterfaceView, IntfCmd, PortMode, ViolationAction, DEFAULT_VLAN};
+use crate::fib::{Fib, LearnError};
 
 pub fn handle_control_plane<'a>(ing_intf: &mut Interface<'a>, rx: &Receiver<IntfCmd>,
   fib: &Arc<Fib<'a>>, mirrors: &DashMap<String, Vec<Arc<InterfaceView<'a>>>>,) {
@@ -13,6 +13,10 @@ pub fn handle_control_plane<'a>(ing_intf: &mut Interface<'a>, rx: &Receiver<Intf
       ing_intf.close();
       fib.remove_intf_entries(ing_intf.name.clone());
     },
+    Ok(IntfCmd::ErrDisable(reason)) => {
+      ing_intf.err_disable(reason);
+      fib.remove_intf_entries(ing_intf.name.clone());
+    },
     Ok(IntfCmd::NoShutdown) => {
       if let Err(err) = ing_intf.open() {
         eprintln!("Error: {}", err)
@@ -32,16 +36,64 @@ pub fn handle_control_plane<'a>(ing_intf: &mut Interface<'a>, rx: &Receiver<Intf
       fib.remove_intf_entries(ing_intf.name.clone());
     },
     Ok(IntfCmd::PortModeVlanTunnelSetVlan(vlan)) => {
-      ing_intf.set_port_mode_vlan_tunnel(vlan);
+      ing_intf.set_vlan_tunnel_service_vlan(vlan);
+      fib.remove_intf_entries(ing_intf.name.clone());
+    },
+    Ok(IntfCmd::PortVlanTunnelMap(cvlans, svlan)) => {
+      ing_intf.map_vlan_tunnel_cvlans(&cvlans, svlan);
+      fib.remove_intf_entries(ing_intf.name.clone());
+    },
+    Ok(IntfCmd::PortVlanTunnelUnmap(cvlans)) => {
+      ing_intf.unmap_vlan_tunnel_cvlans(&cvlans);
+      fib.remove_intf_entries(ing_intf.name.clone());
+    },
+    Ok(IntfCmd::PortVlanTunnelDropUnmatched(drop)) => {
+      ing_intf.set_vlan_tunnel_drop_unmatched(drop);
       fib.remove_intf_entries(ing_intf.name.clone());
     },
     Ok(IntfCmd::PortModeTrunk) => {
       ing_intf.set_port_mode_trunk_vlan();
       fib.remove_intf_entries(ing_intf.name.clone());
     },
+    Ok(IntfCmd::PortModeHybrid) => {
+      ing_intf.set_port_mode_hybrid();
+      fib.remove_intf_entries(ing_intf.name.clone());
+    },
+    Ok(IntfCmd::PortHybridPvid(vlan)) => {
+      let mode = ing_intf.view.get_port_mode();
+      ing_intf.set_hybrid_pvid(vlan);
+      if let PortMode::Hybrid{pvid, ..} = mode && pvid != vlan {
+        fib.remove_intf_vlan_entries(ing_intf.name.clone(), pvid);
+      }
+    },
+    Ok(IntfCmd::PortHybridAddVlans(vlans, untagged)) => {
+      ing_intf.add_hybrid_vlans(&vlans, untagged);
+    },
+    Ok(IntfCmd::PortHybridRemoveVlans(vlans)) => {
+      ing_intf.remove_hybrid_vlans(&vlans);
+      for vlan in vlans {
+        fib.remove_intf_vlan_entries(ing_intf.name.clone(), vlan);
+      }
+    },
     Ok(IntfCmd::PortTrunkAddVlans(vlans)) => {
       ing_intf.add_trunk_allowed_vlan(&vlans);
     },
+    Ok(IntfCmd::PortTrunkSetVlans(vlans)) => {
+      let mode = ing_intf.view.get_port_mode();
+      ing_intf.set_trunk_allowed_vlan(&vlans);
+      if let PortMode::Trunk{vlans: previous, ..} = mode {
+        for vlan in previous.into_iter().filter(|vlan| !vlans.contains(vlan)) {
+          fib.remove_intf_vlan_entries(ing_intf.name.clone(), vlan);
+        }
+      }
+    },
+    Ok(IntfCmd::PortTrunkNativeVlan(vlan)) => {
+      let mode = ing_intf.view.get_port_mode();
+      ing_intf.set_trunk_native_vlan(vlan);
+      if let PortMode::Trunk{native_vlan: Some(previous), ..} = mode && vlan != Some(previous) {
+        fib.remove_intf_vlan_entries(ing_intf.name.clone(), previous);
+      }
+    },
     Ok(IntfCmd::PortTrunkRemoveVlans(vlans)) => {
       ing_intf.remove_trunk_allowed_vlan(&vlans);
       for vlan in vlans {
@@ -65,6 +117,69 @@ pub fn handle_control_plane<'a>(ing_intf: &mut Interface<'a>, rx: &Receiver<Intf
       ing_intf.remove_all_vlan_translations();
       fib.remove_intf_entries(ing_intf.name.clone());
     },
+    Ok(IntfCmd::PortAddIngressVlanTranslation(vlan, new_vlan)) => {
+      ing_intf.add_ingress_vlan_translation(vlan, new_vlan);
+    },
+    Ok(IntfCmd::PortRemoveIngressVlanTranslation(vlan)) => {
+      let mode = ing_intf.view.get_port_mode();
+      ing_intf.remove_ingress_vlan_translation(vlan);
+      if let PortMode::Trunk{vlan_translations, ..} = mode && let Some(new_vlan) = vlan_translations.0.get(&vlan) {
+        fib.remove_intf_vlan_entries(ing_intf.name.clone(), *new_vlan);
+      }
+    },
+    Ok(IntfCmd::PortAddEgressVlanTranslation(vlan, new_vlan)) => {
+      ing_intf.add_egress_vlan_translation(vlan, new_vlan);
+    },
+    Ok(IntfCmd::PortRemoveEgressVlanTranslation(vlan)) => {
+      ing_intf.remove_egress_vlan_translation(vlan);
+    },
+    Ok(IntfCmd::PortPrivateVlanHost(primary, secondary)) => {
+      ing_intf.set_port_mode_private_vlan_host(primary, secondary);
+      fib.remove_intf_entries(ing_intf.name.clone());
+    },
+    Ok(IntfCmd::PortPrivateVlanPromiscuous(primary, secondaries)) => {
+      ing_intf.set_port_mode_private_vlan_promiscuous(primary, &secondaries);
+      fib.remove_intf_entries(ing_intf.name.clone());
+    },
+    Ok(IntfCmd::PortAddMacVlanClassifier(mac, mask, vlan)) => {
+      ing_intf.update_vlan_classifiers(|classifiers| classifiers.add_mac(&mac, &mask, vlan));
+      fib.remove_intf_entries(ing_intf.name.clone());
+    },
+    Ok(IntfCmd::PortRemoveMacVlanClassifier(mac, mask)) => {
+      ing_intf.update_vlan_classifiers(|classifiers| classifiers.remove_mac(&mac, &mask));
+      fib.remove_intf_entries(ing_intf.name.clone());
+    },
+    Ok(IntfCmd::PortAddProtocolVlanClassifier(ether_type, vlan)) => {
+      ing_intf.update_vlan_classifiers(|classifiers| classifiers.add_protocol(ether_type, vlan));
+      fib.remove_intf_entries(ing_intf.name.clone());
+    },
+    Ok(IntfCmd::PortRemoveProtocolVlanClassifier(ether_type)) => {
+      ing_intf.update_vlan_classifiers(|classifiers| classifiers.remove_protocol(ether_type));
+      fib.remove_intf_entries(ing_intf.name.clone());
+    },
+    Ok(IntfCmd::PortDot1qEthertype(tpid)) => {
+      ing_intf.set_dot1q_ethertype(tpid);
+    },
+    Ok(IntfCmd::PortSecurity) => {
+      ing_intf.update_port_security(|_| ());
+    },
+    Ok(IntfCmd::PortSecurityMaximum(maximum)) => {
+      ing_intf.update_port_security(|port_security| port_security.maximum = maximum);
+    },
+    Ok(IntfCmd::PortSecurityVlanMaximum(vlan, maximum)) => {
+      ing_intf.update_port_security(|port_security| _ = port_security.vlan_maximum.insert(vlan, maximum));
+    },
+    Ok(IntfCmd::PortSecurityViolation(action)) => {
+      ing_intf.update_port_security(|port_security| port_security.violation = action);
+    },
+    Ok(IntfCmd::PortSecuritySticky(sticky)) => {
+      ing_intf.update_port_security(|port_security| port_security.sticky = sticky);
+    },
+    Ok(IntfCmd::NoPortSecurity) => {
+      ing_intf.disable_port_security();
+      fib.remove_intf_entries(ing_intf.name.clone());
+      fib.remove_intf_sticky_entries(&ing_intf.name);
+    },
     Err(crossbeam_channel::TryRecvError::Empty) => (),
     Err(err) => eprintln!("Error: {}", err),
   }
@@ -88,3 +203,28 @@ pub fn remove_monitoring_session(monitoring_intf: &Interface,
     });
   }
 }
+
+pub fn handle_learn_error(intf: &InterfaceView, err: &LearnError) {
+  match err {
+    LearnError::Blackholed | LearnError::TableFull => (),
+    LearnError::SecurityViolation(action) => handle_security_violation(intf, *action),
+    LearnError::MacFlapping => {
+      eprintln!("Err-disabling {}: MAC flapping", intf.name);
+      intf.send_cmd(IntfCmd::ErrDisable(ErrDisableReason::MacFlap));
+    }
+  }
+}
+
+pub fn handle_security_violation(intf: &InterfaceView, action: ViolationAction) {
+  if action == ViolationAction::Protect {
+    return;
+  }
+  // Violating frames can come at line rate, the interface counts them
+  let violations = intf.add_security_violation();
+  if intf.is_debug_mode() {
+    println!("Port security violation on {} (total {})", intf.name, violations);
+  }
+  if action == ViolationAction::Shutdown {
+    intf.send_cmd(IntfCmd::ErrDisable(ErrDisableReason::PortSecurity));
+  }
+}
@@ -1,121 +1,573 @@
+use std::collections::BTreeMap;
 use std::fmt;
-use std::sync::Arc;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use arc_swap::ArcSwap;
 use dashmap::{DashMap, DashSet, Entry};
 use macaddr::MacAddr6;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use super::network::interface::InterfaceView;
+use super::network::interface::{InterfaceView, ViolationAction};
+use events::{FibEvent, FibEvents};
+use groups::LearningGroups;
+use moves::{FlapAction, MoveTracker};
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub mod events;
+pub mod groups;
+pub mod moves;
+mod persist;
+
+pub const DEFAULT_AGING_TIME : u64 = 300;
+pub const SWEEP_INTERVAL : Duration = Duration::from_secs(1);
+// Fraction of the table picked as eviction candidates by one scan
+const LRU_BATCH_DIVISOR : usize = 16;
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 struct FibKey {
-  vlan: u16,
+  fid: u16, // filtering database, the vlan itself unless in a learning group
   mac_addr: MacAddr6,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntryType {
+  Dynamic,
+  Static,
+  Sticky, // static entry learned by sticky port security
+  Drop,
+}
+
+pub enum FibAction<'a> {
+  Forward(Arc<InterfaceView<'a>>),
+  Drop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+  Refuse,   // new hosts are not learned when table is full
+  EvictLru, // least recently used dynamic entry makes room for new hosts
+}
+
+pub enum FibFilter {
+  All,
+  Vlan(u16),
+  Interface(String),
+  Address(MacAddr6),
+}
+
+#[derive(Debug)]
+pub enum LearnError {
+  Blackholed,
+  SecurityViolation(ViolationAction),
+  MacFlapping,
+  TableFull,
+}
+
+impl LearnError {
+  // Whether the frame which failed to be learned must be dropped
+  pub fn drops_frame(&self) -> bool {
+    !matches!(self, LearnError::TableFull)
+  }
+}
+
+struct FibEntry<'a> {
+  intf: Option<Arc<InterfaceView<'a>>>, // None for drop entries
+  kind: EntryType,
+  vlan: u16, // vlan the entry was learned or configured on
+  last_seen: AtomicU64, // ms since epoch
+  hits: AtomicU64,
+  last_used: AtomicU64, // ms since epoch, 0 if never used
+}
+
 pub struct Fib<'a> {
-  table: DashMap<FibKey, Arc<InterfaceView<'a>>>,
+  table: DashMap<FibKey, FibEntry<'a>>,
   reverse_table: DashMap<String, DashMap<u16, DashSet<MacAddr6>>>,
+  aging_time: AtomicU64, // seconds, 0 disables aging
+  moves: MoveTracker,
+  events: FibEvents,
+  max_entries: AtomicUsize, // 0 means unlimited
+  overflow_policy: ArcSwap<OverflowPolicy>,
+  table_full_failures: AtomicU64,
+  evictions: AtomicU64,
+  lru_candidates: Mutex<Vec<(u64, FibKey)>>, // most recently active first
+  learning_groups: ArcSwap<LearningGroups>,
+  save_file: Mutex<Option<PathBuf>>,
 }
 
 impl<'a> Fib<'a> {
   pub fn new() -> Self {
-    Self {table: DashMap::new(), reverse_table: DashMap::new()}
+    Self {table: DashMap::new(), reverse_table: DashMap::new(),
+      aging_time: AtomicU64::new(DEFAULT_AGING_TIME), moves: MoveTracker::new(),
+      events: FibEvents::new(), max_entries: AtomicUsize::new(0), overflow_policy: ArcSwap::from_pointee(OverflowPolicy::Refuse),
+      table_full_failures: AtomicU64::new(0), evictions: AtomicU64::new(0),
+      lru_candidates: Mutex::new(Vec::new()), learning_groups: ArcSwap::from_pointee(LearningGroups::default()), save_file: Mutex::new(None)}
   }
 
-  pub fn lookup(&self, vlan: u16, mac: &MacAddr6) -> Option<Arc<InterfaceView<'a>>> {
-     self.table.get(&FibKey{ vlan: vlan, mac_addr: *mac}).map(|g| Arc::clone(g.value()))
+  pub fn lookup(&self, vlan: u16, mac: &MacAddr6) -> Option<FibAction<'a>> {
+    let entry = self.table.get(&self.key(vlan, mac))?;
+    entry.hits.fetch_add(1, Ordering::Relaxed);
+    entry.last_used.store(now_ms(), Ordering::Relaxed);
+    match &entry.intf {
+      Some(intf) => Some(FibAction::Forward(Arc::clone(intf))),
+      None => Some(FibAction::Drop),
+    }
   }
 
-  pub fn learn(&self, vlan: u16, mac: &MacAddr6, intf: Arc<InterfaceView<'a>>) {
-    let fib_key = FibKey{vlan: vlan, mac_addr: *mac};
-    match self.table.entry(fib_key.clone()) {
+  pub fn learn(&self, vlan: u16, mac: &MacAddr6, intf: Arc<InterfaceView<'a>>) -> Result<(), LearnError> {
+    let fib_key = self.key(vlan, mac);
+    let now = now_ms();
+
+    let known = match self.table.get(&fib_key) {
+      Some(entry) => {
+        match entry.kind {
+          EntryType::Drop => return Err(LearnError::Blackholed),
+          EntryType::Static | EntryType::Sticky => return Ok(()), // static entries are never moved by learning
+          EntryType::Dynamic if entry.is_on(&intf.name) => {
+            // Known host on the same port, only refresh its timestamp
+            entry.last_seen.store(now, Ordering::Relaxed);
+            return Ok(());
+          }
+          EntryType::Dynamic => true,
+        }
+      }
+      None => false,
+    };
+
+    // New host on this port
+    if self.moves.is_suppressed(&fib_key, now) {
+      return Ok(());
+    }
+    let kind = self.check_port_security(vlan, &intf)?;
+    if !known {
+      self.reserve_entry()?;
+    }
+    let previous = match self.table.entry(fib_key.clone()) {
       Entry::Occupied(mut entry) => {
-        if !Arc::ptr_eq(entry.get(), &intf) {
-          *entry.get_mut() = intf;
+        match entry.get().kind {
+          EntryType::Drop => return Err(LearnError::Blackholed),
+          EntryType::Static | EntryType::Sticky => return Ok(()),
+          EntryType::Dynamic => entry.insert(FibEntry::new(Some(Arc::clone(&intf)), kind, vlan, now)).intf,
         }
-      },
+      }
       Entry::Vacant(entry) => {
-        entry.insert(intf.clone());
-        match self.reverse_table.entry(intf.name.clone()) {
-          Entry::Occupied(mut rev_entry) => {
-            match rev_entry.get_mut().entry(vlan) {
-              Entry::Occupied(mut rev_vlan_entry) => {
-                _ = rev_vlan_entry.get_mut().insert(*mac)
-              }
-              Entry::Vacant(entry) => {
-                let set = DashSet::new();
-                set.insert(*mac);
-                entry.insert(set);
-              }
-            }
-          },
-          Entry::Vacant(rev_entry) => {
-            let set = DashSet::new();
-            set.insert(*mac);
-            let vlan_map = DashMap::new();
-            vlan_map.insert(vlan, set);
-            rev_entry.insert(vlan_map);
-          }
+        entry.insert(FibEntry::new(Some(Arc::clone(&intf)), kind, vlan, now));
+        None
+      }
+    };
+    if let Some(previous) = &previous {
+      self.reverse_remove(&previous.name, fib_key.fid, mac);
+    }
+    self.reverse_insert(&intf.name, fib_key.fid, mac);
+
+    match previous {
+      Some(previous) if previous.name != intf.name => {
+        intf.add_mac_move();
+        self.events.publish(FibEvent::Moved{vlan, mac: *mac, from: previous.name.clone(), to: intf.name.clone()});
+        if let Some(FlapAction::Shutdown) = self.moves.record(&fib_key, vlan, &previous.name, &intf, now) {
+          return Err(LearnError::MacFlapping);
         }
-      },
+      }
+      _ => self.events.publish(FibEvent::Learned{vlan, mac: *mac, intf: intf.name.clone()}),
     }
+    Ok(())
   }
 
-  pub fn remove_entry(&self, vlan: u16, mac: &MacAddr6) {
-    let result = self.table.remove(&FibKey{vlan: vlan, mac_addr: *mac});
-    if let Some(entry) = result {
-      match self.reverse_table.entry(entry.1.name.clone()) {
-        Entry::Occupied(mut intf_entry) => {
-          match intf_entry.get_mut().entry(vlan) {
-            Entry::Occupied(mut vlan_entry) => {
-              _ = vlan_entry.get_mut().remove(mac)
-            }
-            Entry::Vacant(_) => ()
-          }
+  pub fn moves(&self) -> &MoveTracker {
+    &self.moves
+  }
+
+  pub fn events(&self) -> &FibEvents {
+    &self.events
+  }
+
+  fn fid(&self, vlan: u16) -> u16 {
+    self.learning_groups.load().fid(vlan)
+  }
+
+  fn key(&self, vlan: u16, mac: &MacAddr6) -> FibKey {
+    FibKey{fid: self.fid(vlan), mac_addr: *mac}
+  }
+
+  pub fn set_learning_group(&self, name: &str, vlans: &[u16]) -> io::Result<()> {
+    let groups = self.learning_groups.load().with_group(name, vlans)?;
+    self.regroup(groups);
+    Ok(())
+  }
+
+  pub fn remove_learning_group(&self, name: &str) -> io::Result<()> {
+    let groups = self.learning_groups.load().without_group(name)?;
+    self.regroup(groups);
+    Ok(())
+  }
+
+  pub fn format_learning_groups(&self) -> String {
+    self.learning_groups.load().to_string()
+  }
+
+  // Move entries whose vlan changed of filtering database, dynamic entries
+  // are flushed and learned again while static ones are kept
+  fn regroup(&self, groups: LearningGroups) {
+    self.learning_groups.store(Arc::new(groups));
+    let groups = self.learning_groups.load();
+    let moved: Vec<FibKey> = self.table.iter()
+      .filter(|entry| entry.key().fid != groups.fid(entry.vlan))
+      .map(|entry| entry.key().clone())
+      .collect();
+    for key in moved {
+      if let Some(entry) = self.take_entry(&key) && entry.kind != EntryType::Dynamic {
+        let new_key = FibKey{fid: groups.fid(entry.vlan), mac_addr: key.mac_addr};
+        self.insert_entry(new_key, entry);
+      }
+    }
+  }
+
+  // Make room for a new entry according to the overflow policy
+  fn reserve_entry(&self) -> Result<(), LearnError> {
+    let max_entries = self.max_entries.load(Ordering::Relaxed);
+    if max_entries == 0 || self.table.len() < max_entries {
+      return Ok(());
+    }
+    if **self.overflow_policy.load() == OverflowPolicy::EvictLru && let Some(key) = self.lru_dynamic_entry(max_entries) {
+      if let Some(entry) = self.take_entry(&key) && let Some(intf) = entry.intf {
+        self.events.publish(FibEvent::Evicted{vlan: entry.vlan, mac: key.mac_addr, intf: intf.name.clone()});
+      }
+      self.evictions.fetch_add(1, Ordering::Relaxed);
+      return Ok(());
+    }
+    self.table_full_failures.fetch_add(1, Ordering::Relaxed);
+    Err(LearnError::TableFull)
+  }
+
+  // Scanning the table for every new host would make a MAC flood cost the
+  // table size per frame, a scan keeps a batch of the least recently active
+  // entries instead. Candidates active since the scan are skipped.
+  fn lru_dynamic_entry(&self, max_entries: usize) -> Option<FibKey> {
+    let mut candidates = self.lru_candidates.lock().unwrap();
+    for rescanned in [false, true] {
+      while let Some((activity, key)) = candidates.pop() {
+        if self.table.get(&key).is_some_and(|entry| entry.kind == EntryType::Dynamic && entry.last_activity() == activity) {
+          return Some(key);
         }
-        Entry::Vacant(_) => ()
       }
+      if rescanned {
+        break;
+      }
+      let mut entries: Vec<(u64, FibKey)> = self.table.iter()
+        .filter(|entry| entry.kind == EntryType::Dynamic)
+        .map(|entry| (entry.last_activity(), entry.key().clone()))
+        .collect();
+      let batch = (max_entries / LRU_BATCH_DIVISOR).clamp(1, entries.len().max(1));
+      if entries.len() > batch {
+        entries.select_nth_unstable(batch);
+        entries.truncate(batch);
+      }
+      entries.sort_unstable_by(|a, b| b.cmp(a));
+      *candidates = entries;
     }
+    None
   }
 
+  // 0 means unlimited
+  pub fn set_max_entries(&self, max_entries: usize) {
+    self.max_entries.store(max_entries, Ordering::Relaxed);
+  }
+
+  pub fn set_overflow_policy(&self, policy: OverflowPolicy) {
+    self.overflow_policy.store(Arc::new(policy));
+  }
+
+  pub fn reset_counters(&self) {
+    self.table_full_failures.store(0, Ordering::Relaxed);
+    self.evictions.store(0, Ordering::Relaxed);
+  }
+
+  // Type of the entry to create for a new host on intf, sticky port security
+  // turns learned addresses into static entries
+  fn check_port_security(&self, vlan: u16, intf: &InterfaceView) -> Result<EntryType, LearnError> {
+    let Some(port_security) = intf.get_port_security() else {
+      return Ok(EntryType::Dynamic);
+    };
+    if self.intf_entries_count(&intf.name, None) >= port_security.maximum {
+      return Err(LearnError::SecurityViolation(port_security.violation));
+    }
+    if let Some(maximum) = port_security.vlan_maximum.get(&vlan) &&
+      self.intf_entries_count(&intf.name, Some(vlan)) >= *maximum {
+      return Err(LearnError::SecurityViolation(port_security.violation));
+    }
+    Ok(if port_security.sticky { EntryType::Sticky } else { EntryType::Dynamic })
+  }
+
+  // Number of entries on interface, optionally restricted to the filtering
+  // database of a vlan
+  pub fn intf_entries_count(&self, if_name: &str, vlan: Option<u16>) -> usize {
+    let Some(vlans) = self.reverse_table.get(if_name) else {
+      return 0;
+    };
+    match vlan {
+      Some(vlan) => vlans.get(&self.fid(vlan)).map_or(0, |macs| macs.len()),
+      None => vlans.iter().map(|macs| macs.len()).sum(),
+    }
+  }
+
+  // Pin a MAC to a port, or blackhole it when no port is given
+  pub fn add_static_entry(&self, vlan: u16, mac: &MacAddr6, intf: Option<Arc<InterfaceView<'a>>>) {
+    let kind = if intf.is_some() { EntryType::Static } else { EntryType::Drop };
+    self.insert_entry(self.key(vlan, mac), FibEntry::new(intf, kind, vlan, now_ms()));
+  }
+
+  fn insert_entry(&self, key: FibKey, entry: FibEntry<'a>) {
+    let intf = entry.intf.clone();
+    if let Some(previous) = self.table.insert(key.clone(), entry) &&
+      let Some(previous) = previous.intf {
+      self.reverse_remove(&previous.name, key.fid, &key.mac_addr);
+    }
+    if let Some(intf) = intf {
+      self.reverse_insert(&intf.name, key.fid, &key.mac_addr);
+    }
+  }
+
+  pub fn remove_entry(&self, vlan: u16, mac: &MacAddr6) {
+    self.take_entry(&self.key(vlan, mac));
+  }
+
+  fn take_entry(&self, key: &FibKey) -> Option<FibEntry<'a>> {
+    let (_, entry) = self.table.remove(key)?;
+    if let Some(intf) = &entry.intf {
+      self.reverse_remove(&intf.name, key.fid, &key.mac_addr);
+    }
+    Some(entry)
+  }
+
+  // Flush dynamic entries learned on interface for given vlan
   pub fn remove_intf_vlan_entries(&self, if_name: String, vlan: u16) {
-    match self.reverse_table.entry(if_name) {
-      Entry::Occupied(mut intf_entry) => {
-        match intf_entry.get().entry(vlan) {
-          Entry::Occupied(vlan_entry) => {
-            for mac_entry in vlan_entry.get().iter() {
-              self.table.remove(&FibKey{vlan: vlan, mac_addr: *mac_entry});
-            }
-          }
-          Entry::Vacant(_) => ()
+    let mut flushed = false;
+    let fid = self.fid(vlan);
+    if let Some(vlans) = self.reverse_table.get(&if_name) && let Some(macs) = vlans.get(&fid) {
+      macs.retain(|mac| {
+        let removed = self.remove_dynamic(fid, mac, &if_name).is_some();
+        flushed |= removed;
+        !removed
+      });
+    }
+    if flushed {
+      self.events.publish(FibEvent::IntfVlanFlushed{intf: if_name, vlan});
+    }
+  }
+
+  // Flush dynamic entries learned on interface
+  pub fn remove_intf_entries(&self, if_name: String) {
+    let mut flushed = false;
+    if let Some(vlans) = self.reverse_table.get(&if_name) {
+      for macs in vlans.iter() {
+        macs.retain(|mac| {
+          let removed = self.remove_dynamic(*macs.key(), mac, &if_name).is_some();
+          flushed |= removed;
+          !removed
+        });
+      }
+    }
+    if flushed {
+      self.events.publish(FibEvent::IntfFlushed{intf: if_name});
+    }
+  }
+
+  // Flush entries learned by sticky port security on interface
+  pub fn remove_intf_sticky_entries(&self, if_name: &str) {
+    if let Some(vlans) = self.reverse_table.get(if_name) {
+      for macs in vlans.iter() {
+        macs.retain(|mac| self.table.remove_if(&FibKey{fid: *macs.key(), mac_addr: *mac},
+          |_, entry| entry.kind == EntryType::Sticky && entry.is_on(if_name)).is_none());
+      }
+    }
+  }
+
+  fn remove_dynamic(&self, fid: u16, mac: &MacAddr6, if_name: &str) -> Option<(FibKey, FibEntry<'a>)> {
+    self.table.remove_if(&FibKey{fid, mac_addr: *mac},
+      |_, entry| entry.kind == EntryType::Dynamic && entry.is_on(if_name))
+  }
+
+  // Flush dynamic entries matching filter
+  pub fn clear(&self, filter: &FibFilter) {
+    let if_names: Vec<String> = self.reverse_table.iter().map(|entry| entry.key().clone()).collect();
+    match filter {
+      FibFilter::All => {
+        for if_name in if_names {
+          self.remove_intf_entries(if_name);
+        }
+      }
+      FibFilter::Vlan(vlan) => {
+        for if_name in if_names {
+          self.remove_intf_vlan_entries(if_name, *vlan);
+        }
+      }
+      FibFilter::Interface(if_name) => self.remove_intf_entries(if_name.clone()),
+      FibFilter::Address(_) => {
+        let groups = self.learning_groups.load();
+        let keys: Vec<FibKey> = self.table.iter()
+          .filter(|entry| entry.kind == EntryType::Dynamic && filter.matches(entry.key(), entry.value(), &groups))
+          .map(|entry| entry.key().clone())
+          .collect();
+        for key in keys {
+          self.take_entry(&key);
         }
-        intf_entry.get_mut().remove(&vlan);
       }
-      Entry::Vacant(_) => ()
     }
   }
 
-  pub fn remove_intf_entries(&self, if_name: String) {
-    match self.reverse_table.entry(if_name.clone()) {
-      Entry::Occupied(intf_entry) => {
-        for vlan_entry in intf_entry.get().iter() {
-          for mac_entry in vlan_entry.value().iter() {
-            self.table.remove(&FibKey{vlan: *vlan_entry.key(), mac_addr: *mac_entry});
-          }
+  // Entries matching filter as a table sorted by vlan and MAC address, detail
+  // adds forwarding hits and last use
+  pub fn format_entries(&self, filter: &FibFilter, detail: bool) -> String {
+    let now = now_ms();
+    let groups = self.learning_groups.load();
+    let mut entries: Vec<((u16, MacAddr6), String)> = self.table.iter()
+      .filter(|entry| filter.matches(entry.key(), entry.value(), &groups))
+      .map(|entry| {
+        let intf = entry.intf.as_ref().map_or("-", |intf| intf.name.as_str());
+        let age = if entry.kind == EntryType::Dynamic { format!("{}s", entry.age(now) / 1000) } else { "-".to_string() };
+        let mut line = format!("{:<6} {:<18} {:<8} {:<16} {:<8}", entry.vlan,
+          entry.key().mac_addr.to_string(), entry.kind.to_string(), intf, age);
+        if detail {
+          let last_used = match entry.last_used.load(Ordering::Relaxed) {
+            0 => "never".to_string(),
+            last_used => format!("{}s", now.saturating_sub(last_used) / 1000),
+          };
+          line += &format!(" {:<10} {}", entry.hits.load(Ordering::Relaxed), last_used);
         }
+        ((entry.vlan, entry.key().mac_addr), line.trim_end().to_string())
+      })
+      .collect();
+    entries.sort_by_key(|(key, _)| *key);
+
+    let mut output = String::from("Vlan   MAC Address        Type     Interface        Age");
+    if detail {
+      output += "      Hits       Last used";
+    }
+    output.push('\n');
+    let total = entries.len();
+    for (_, entry) in entries {
+      output += &entry;
+      output.push('\n');
+    }
+    output += &format!("Total: {}\n", total);
+    output
+  }
+
+  // Entry totals per type, vlan and interface
+  pub fn format_counts(&self) -> String {
+    let mut per_type: BTreeMap<String, usize> = BTreeMap::new();
+    let mut per_vlan: BTreeMap<u16, usize> = BTreeMap::new();
+    let mut per_intf: BTreeMap<String, usize> = BTreeMap::new();
+    for entry in &self.table {
+      *per_type.entry(entry.kind.to_string()).or_default() += 1;
+      *per_vlan.entry(entry.vlan).or_default() += 1;
+      if let Some(intf) = &entry.intf {
+        *per_intf.entry(intf.name.clone()).or_default() += 1;
       }
-      Entry::Vacant(_) => ()
     }
-    self.reverse_table.remove(&if_name);
+
+    let max_entries = self.max_entries.load(Ordering::Relaxed);
+    let mut output = format!("Capacity: {}, overflow policy: {}\n",
+      if max_entries == 0 { "unlimited".to_string() } else { max_entries.to_string() },
+      self.overflow_policy.load());
+    output += &format!("Learn failures (table full): {}, Evictions: {}\n",
+      self.table_full_failures.load(Ordering::Relaxed), self.evictions.load(Ordering::Relaxed));
+    output += &format!("Total: {}\n", self.table.len());
+    for (kind, count) in per_type {
+      output += &format!("\t{}: {}\n", kind, count);
+    }
+    output += "Per Vlan:\n";
+    for (vlan, count) in per_vlan {
+      output += &format!("\t{}: {}\n", vlan, count);
+    }
+    output += "Per Interface:\n";
+    for (if_name, count) in per_intf {
+      output += &format!("\t{}: {}\n", if_name, count);
+    }
+    output
+  }
+
+  pub fn set_aging_time(&self, seconds: u64) {
+    self.aging_time.store(seconds, Ordering::Relaxed);
+  }
+
+  pub fn get_aging_time(&self) -> u64 {
+    self.aging_time.load(Ordering::Relaxed)
+  }
+
+  // Remove entries not refreshed by learning during the aging time, along with
+  // outdated MAC move tracking
+  pub fn age_entries(&self) {
+    let aging_time = self.get_aging_time();
+    let now = now_ms();
+    let expired: Vec<FibKey> = self.table.iter()
+      .filter(|entry| aging_time != 0 && entry.kind == EntryType::Dynamic && entry.value().age(now) > aging_time * 1000)
+      .map(|entry| entry.key().clone())
+      .collect();
+    // Entries refreshed or learned again since the scan are kept
+    for key in expired {
+      let removed = self.table.remove_if(&key,
+        |_, entry| entry.kind == EntryType::Dynamic && entry.age(now) > aging_time * 1000);
+      if let Some((key, entry)) = removed && let Some(intf) = entry.intf {
+        self.reverse_remove(&intf.name, key.fid, &key.mac_addr);
+        self.events.publish(FibEvent::Aged{vlan: entry.vlan, mac: key.mac_addr, intf: intf.name.clone()});
+      }
+    }
+    self.moves.expire(now);
+  }
+
+  fn reverse_insert(&self, if_name: &str, fid: u16, mac: &MacAddr6) {
+    self.reverse_table.entry(if_name.to_string()).or_default()
+      .entry(fid).or_default()
+      .insert(*mac);
+  }
+
+  fn reverse_remove(&self, if_name: &str, fid: u16, mac: &MacAddr6) {
+    if let Some(vlans) = self.reverse_table.get(if_name) && let Some(macs) = vlans.get(&fid) {
+      macs.remove(mac);
+    }
+  }
+}
+
+impl FibFilter {
+  // Vlan filter matches every entry of the vlan filtering database
+  fn matches(&self, key: &FibKey, entry: &FibEntry, groups: &LearningGroups) -> bool {
+    match self {
+      FibFilter::All => true,
+      FibFilter::Vlan(vlan) => key.fid == groups.fid(*vlan),
+      FibFilter::Interface(if_name) => entry.is_on(if_name),
+      FibFilter::Address(mac) => key.mac_addr == *mac,
+    }
+  }
+}
+
+impl<'a> FibEntry<'a> {
+  fn new(intf: Option<Arc<InterfaceView<'a>>>, kind: EntryType, vlan: u16, now: u64) -> Self {
+    FibEntry{intf, kind, vlan, last_seen: AtomicU64::new(now), hits: AtomicU64::new(0), last_used: AtomicU64::new(0)}
+  }
+
+  fn is_on(&self, if_name: &str) -> bool {
+    self.intf.as_ref().is_some_and(|intf| intf.name == if_name)
+  }
+
+  // Age in ms
+  fn age(&self, now: u64) -> u64 {
+    now.saturating_sub(self.last_seen.load(Ordering::Relaxed))
+  }
+
+  // Last time entry was learned or used for forwarding
+  fn last_activity(&self) -> u64 {
+    self.last_seen.load(Ordering::Relaxed).max(self.last_used.load(Ordering::Relaxed))
   }
 }
 
+fn now_ms() -> u64 {
+  SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
 impl fmt::Display for Fib<'_> {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    for entry in &self.table {
-      write!(f, "{} {}\n", entry.key(), entry.value().name)?
-    }
-    Ok(())
+    write!(f, "{}", self.format_entries(&FibFilter::All, false))
   }
 }
 
@@ -138,8 +590,41 @@ impl fmt::Debug for Fib<'_> {
   }
 }
 
+impl fmt::Display for EntryType {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}",
+    match self {
+      EntryType::Dynamic => "dynamic",
+      EntryType::Static => "static",
+      EntryType::Sticky => "sticky",
+      EntryType::Drop => "drop",
+    })
+  }
+}
+
+impl fmt::Display for OverflowPolicy {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}",
+    match self {
+      OverflowPolicy::Refuse => "refuse",
+      OverflowPolicy::EvictLru => "evict-lru",
+    })
+  }
+}
+
+impl fmt::Display for LearnError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      LearnError::Blackholed => write!(f, "source MAC is blackholed"),
+      LearnError::SecurityViolation(action) => write!(f, "port security violation ({})", action),
+      LearnError::MacFlapping => write!(f, "source MAC is flapping"),
+      LearnError::TableFull => write!(f, "FIB is full"),
+    }
+  }
+}
+
 impl fmt::Display for FibKey {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    write!(f, "({}, {})", self.vlan, self.mac_addr)
+    write!(f, "({}, {})", self.fid, self.mac_addr)
   }
 }
@@ -0,0 +1,63 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use crossbeam_channel::{bounded, Receiver, Sender, TrySendError};
+use dashmap::DashMap;
+use macaddr::MacAddr6;
+
+// Events a slow subscriber can lag behind before new ones get dropped
+pub const SUBSCRIBER_QUEUE_SIZE : usize = 1024;
+
+pub type SubscriptionId = u64;
+
+#[derive(Debug, Clone)]
+pub enum FibEvent {
+  Learned { vlan: u16, mac: MacAddr6, intf: String },
+  Moved { vlan: u16, mac: MacAddr6, from: String, to: String },
+  Aged { vlan: u16, mac: MacAddr6, intf: String },
+  Evicted { vlan: u16, mac: MacAddr6, intf: String },
+  IntfFlushed { intf: String },
+  IntfVlanFlushed { intf: String, vlan: u16 },
+}
+
+pub struct FibEvents {
+  subscribers: DashMap<SubscriptionId, Sender<FibEvent>>,
+  next_id: AtomicU64,
+}
+
+impl FibEvents {
+  pub fn new() -> Self {
+    FibEvents{subscribers: DashMap::new(), next_id: AtomicU64::new(0)}
+  }
+
+  pub fn subscribe(&self) -> (SubscriptionId, Receiver<FibEvent>) {
+    let (tx, rx) = bounded(SUBSCRIBER_QUEUE_SIZE);
+    let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+    self.subscribers.insert(id, tx);
+    (id, rx)
+  }
+
+  // Dropping the sender ends the subscriber receiving loop
+  pub fn unsubscribe(&self, id: SubscriptionId) {
+    self.subscribers.remove(&id);
+  }
+
+  pub(super) fn publish(&self, event: FibEvent) {
+    if self.subscribers.is_empty() {
+      return;
+    }
+    self.subscribers.retain(|_, tx| !matches!(tx.try_send(event.clone()), Err(TrySendError::Disconnected(_))));
+  }
+}
+
+impl fmt::Display for FibEvent {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      FibEvent::Learned{vlan, mac, intf} => write!(f, "learned {} vlan {} on {}", mac, vlan, intf),
+      FibEvent::Moved{vlan, mac, from, to} => write!(f, "moved {} vlan {} from {} to {}", mac, vlan, from, to),
+      FibEvent::Aged{vlan, mac, intf} => write!(f, "aged {} vlan {} on {}", mac, vlan, intf),
+      FibEvent::Evicted{vlan, mac, intf} => write!(f, "evicted {} vlan {} on {}", mac, vlan, intf),
+      FibEvent::IntfFlushed{intf} => write!(f, "flushed {}", intf),
+      FibEvent::IntfVlanFlushed{intf, vlan} => write!(f, "flushed {} vlan {}", intf, vlan),
+    }
+  }
+}
@@ -0,0 +1,62 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::io;
+
+// Shared VLAN learning groups, VLANs in a group share one filtering database
+// identified by the lowest VLAN of the group. A VLAN outside of any group is
+// its own filtering database.
+#[derive(Debug, Clone, Default)]
+pub struct LearningGroups {
+  groups: BTreeMap<String, BTreeSet<u16>>,
+  fids: HashMap<u16, u16>,
+}
+
+impl LearningGroups {
+  pub fn fid(&self, vlan: u16) -> u16 {
+    self.fids.get(&vlan).copied().unwrap_or(vlan)
+  }
+
+  // Copy with group set to vlans, a vlan can only belong to one group
+  pub fn with_group(&self, name: &str, vlans: &[u16]) -> io::Result<LearningGroups> {
+    if vlans.is_empty() {
+      return Err(io::Error::new(io::ErrorKind::InvalidInput, "Learning group needs at least one vlan"));
+    }
+    for vlan in vlans {
+      if let Some((other, _)) = self.groups.iter().find(|(other, members)| other.as_str() != name && members.contains(vlan)) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Vlan {} already belongs to learning group {}", vlan, other)));
+      }
+    }
+    let mut groups = self.clone();
+    groups.groups.insert(name.to_string(), vlans.iter().copied().collect());
+    groups.rebuild_fids();
+    Ok(groups)
+  }
+
+  pub fn without_group(&self, name: &str) -> io::Result<LearningGroups> {
+    let mut groups = self.clone();
+    if groups.groups.remove(name).is_none() {
+      return Err(io::Error::new(io::ErrorKind::NotFound, format!("Unknown learning group {}", name)));
+    }
+    groups.rebuild_fids();
+    Ok(groups)
+  }
+
+  fn rebuild_fids(&mut self) {
+    self.fids = self.groups.values()
+      .filter_map(|vlans| vlans.first().map(|fid| (vlans, *fid)))
+      .flat_map(|(vlans, fid)| vlans.iter().map(move |vlan| (*vlan, fid)))
+      .collect();
+  }
+}
+
+impl fmt::Display for LearningGroups {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(f, "Group            FID    Vlans")?;
+    for (name, vlans) in &self.groups {
+      let fid = vlans.first().copied().unwrap_or_default();
+      let vlans: Vec<String> = vlans.iter().map(|vlan| vlan.to_string()).collect();
+      writeln!(f, "{:<16} {:<6} {}", name, fid, vlans.join(","))?;
+    }
+    Ok(())
+  }
+}
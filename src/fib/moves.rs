@@ -0,0 +1,140 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use arc_swap::ArcSwap;
+use dashmap::DashMap;
+
+use crate::network::interface::InterfaceView;
+use super::{now_ms, FibKey};
+
+pub const MOVES_HISTORY_SIZE : usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlapAction {
+  Suppress(u64), // hold-down in seconds
+  Shutdown,
+}
+
+#[derive(Debug, Clone)]
+pub struct FlapDampening {
+  pub threshold: usize, // moves allowed in window
+  pub window: u64,      // seconds
+  pub action: FlapAction,
+}
+
+struct MacMove {
+  vlan: u16, // vlan of the frame, the key holds the filtering database
+  key: FibKey,
+  from: String,
+  to: String,
+  time: u64, // ms since epoch
+}
+
+pub struct MoveTracker {
+  history: Mutex<VecDeque<MacMove>>,
+  flaps: DashMap<FibKey, VecDeque<u64>>,
+  suppressed: DashMap<FibKey, u64>, // learning suppressed until
+  dampening: ArcSwap<Option<FlapDampening>>,
+}
+
+impl MoveTracker {
+  pub fn new() -> Self {
+    MoveTracker{history: Mutex::new(VecDeque::new()), flaps: DashMap::new(),
+      suppressed: DashMap::new(), dampening: ArcSwap::from_pointee(None)}
+  }
+
+  pub(super) fn is_suppressed(&self, key: &FibKey, now: u64) -> bool {
+    self.suppressed.get(key).is_some_and(|until| *until > now)
+  }
+
+  // Record a move, returns the action to apply when the MAC is flapping
+  pub(super) fn record(&self, key: &FibKey, vlan: u16, from: &str, to: &InterfaceView, now: u64) -> Option<FlapAction> {
+    if to.is_debug_mode() {
+      println!("MAC move: {} vlan {} moved from {} to {}", key.mac_addr, vlan, from, to.name);
+    }
+    {
+      let mut history = self.history.lock().unwrap();
+      if history.len() == MOVES_HISTORY_SIZE {
+        history.pop_front();
+      }
+      history.push_back(MacMove{vlan, key: key.clone(), from: from.to_string(), to: to.name.clone(), time: now});
+    }
+
+    let dampening = self.dampening.load();
+    let dampening = dampening.as_ref().as_ref()?;
+    let mut moves = self.flaps.entry(key.clone()).or_default();
+    moves.retain(|time| now.saturating_sub(*time) <= dampening.window * 1000);
+    moves.push_back(now);
+    if moves.len() <= dampening.threshold {
+      return None;
+    }
+    moves.clear();
+
+    if to.is_debug_mode() {
+      println!("MAC flapping: {} vlan {} moved more than {} times in {}s", key.mac_addr, vlan,
+        dampening.threshold, dampening.window);
+    }
+    if let FlapAction::Suppress(hold) = dampening.action {
+      self.suppressed.insert(key.clone(), now + hold * 1000);
+    }
+    Some(dampening.action)
+  }
+
+  // Forget flap history and suppressions that are over
+  pub(super) fn expire(&self, now: u64) {
+    self.suppressed.retain(|_, until| *until > now);
+    if let Some(dampening) = self.dampening.load().as_ref() {
+      self.flaps.retain(|_, moves| moves.back().is_some_and(|time| now.saturating_sub(*time) <= dampening.window * 1000));
+    } else {
+      self.flaps.clear();
+    }
+  }
+
+  pub fn get_dampening(&self) -> Option<FlapDampening> {
+    self.dampening.load().as_ref().clone()
+  }
+
+  // Enable flap dampening with default settings if not already enabled
+  pub fn update_dampening<F: FnOnce(&mut FlapDampening)>(&self, update: F) {
+    let mut dampening = self.get_dampening().unwrap_or_default();
+    update(&mut dampening);
+    self.dampening.store(Arc::new(Some(dampening)));
+  }
+
+  pub fn disable_dampening(&self) {
+    self.dampening.store(Arc::new(None));
+    self.suppressed.clear();
+  }
+}
+
+impl Default for FlapDampening {
+  fn default() -> Self {
+    FlapDampening{threshold: 5, window: 10, action: FlapAction::Suppress(60)}
+  }
+}
+
+impl fmt::Display for MoveTracker {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let now = now_ms();
+    match self.get_dampening() {
+      Some(dampening) => writeln!(f, "Flap dampening: more than {} moves in {}s, action {}",
+        dampening.threshold, dampening.window, dampening.action)?,
+      None => writeln!(f, "Flap dampening: disabled")?,
+    }
+    writeln!(f, "Vlan   MAC Address        From             To               Age")?;
+    for mac_move in self.history.lock().unwrap().iter().rev() {
+      writeln!(f, "{:<6} {:<18} {:<16} {:<16} {}s", mac_move.vlan, mac_move.key.mac_addr.to_string(),
+        mac_move.from, mac_move.to, now.saturating_sub(mac_move.time) / 1000)?;
+    }
+    Ok(())
+  }
+}
+
+impl fmt::Display for FlapAction {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      FlapAction::Suppress(hold) => write!(f, "suppress learning for {}s", hold),
+      FlapAction::Shutdown => write!(f, "shutdown"),
+    }
+  }
+}
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use macaddr::MacAddr6;
+
+use crate::network::interface::InterfaceView;
+use super::{now_ms, EntryType, Fib, FibEntry};
+
+// FIB dump kept across restarts, one entry per line:
+// <vlan> <mac> <dynamic|static|sticky|drop> <interface|-> <age in seconds>
+impl<'a> Fib<'a> {
+  // Write dynamic, static and drop entries to path, returns number of entries saved
+  pub fn save(&self, path: &Path) -> io::Result<usize> {
+    let now = now_ms();
+    let mut output = String::new();
+    let mut count = 0;
+    for entry in &self.table {
+      let intf = entry.intf.as_ref().map_or("-", |intf| intf.name.as_str());
+      output += &format!("{} {} {} {} {}\n", entry.vlan, entry.key().mac_addr, entry.kind, intf, entry.age(now) / 1000);
+      count += 1;
+    }
+    fs::write(path, output)?;
+    Ok(count)
+  }
+
+  // Load entries saved by a previous run, entries on interfaces not attached to
+  // the switch are skipped. Dynamic entries keep their age and expire normally.
+  // Nothing is restored from a malformed file. Returns number of entries restored.
+  pub fn restore(&self, path: &Path, intfs_view: &HashMap<&str, Arc<InterfaceView<'a>>>) -> io::Result<usize> {
+    let now = now_ms();
+    let content = fs::read_to_string(path)?;
+    let mut entries = Vec::new();
+    for (line_num, line) in content.lines().enumerate() {
+      if line.trim().is_empty() {
+        continue;
+      }
+      let Some(entry) = parse_line(line) else {
+        return Err(Error::new(ErrorKind::InvalidData, format!("{}:{}: invalid FIB entry \"{}\"", path.display(), line_num + 1, line)));
+      };
+      entries.push(entry);
+    }
+
+    let mut count = 0;
+    for (vlan, mac, kind, if_name, age) in entries {
+      let intf = match if_name {
+        "-" => None,
+        if_name => match intfs_view.get(if_name) {
+          Some(intf) => Some(Arc::clone(intf)),
+          None => continue,
+        }
+      };
+      if (kind == EntryType::Drop) != intf.is_none() {
+        continue;
+      }
+      if kind == EntryType::Dynamic && self.reserve_entry().is_err() {
+        continue;
+      }
+      let entry = FibEntry::new(intf, kind, vlan, now.saturating_sub(age * 1000));
+      self.insert_entry(self.key(vlan, &mac), entry);
+      count += 1;
+    }
+    Ok(count)
+  }
+
+  // File the FIB is saved to on exit
+  pub fn set_save_file(&self, path: Option<PathBuf>) {
+    *self.save_file.lock().unwrap() = path;
+  }
+
+  pub fn save_on_exit(&self) {
+    if let Some(path) = self.save_file.lock().unwrap().as_ref() {
+      match self.save(path) {
+        Ok(count) => println!("Saved {} FIB entries to {}", count, path.display()),
+        Err(err) => eprintln!("Error: unable to save FIB to {}: {}", path.display(), err),
+      }
+    }
+  }
+}
+
+fn parse_line(line: &str) -> Option<(u16, MacAddr6, EntryType, &str, u64)> {
+  let fields: Vec<&str> = line.split_whitespace().collect();
+  let [vlan, mac, kind, if_name, age] = fields[..] else {
+    return None;
+  };
+  let vlan = vlan.parse::<u16>().ok().filter(|vlan| *vlan > 0 && *vlan < 4096)?;
+  let kind = match kind {
+    "dynamic" => EntryType::Dynamic,
+    "static" => EntryType::Static,
+    "sticky" => EntryType::Sticky,
+    "drop" => EntryType::Drop,
+    _ => return None,
+  };
+  Some((vlan, mac.parse().ok()?, kind, if_name, age.parse().ok()?))
+}
@@ -0,0 +1,475 @@
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{self, Error, ErrorKind};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use macaddr::MacAddr6;
+
+use crate::fib::Fib;
+use crate::network::frame::Frame;
+use crate::network::interface::{InterfaceView, LagState};
+use lacpdu::{LacpInfo, Lacpdu, STATE_ACTIVITY, STATE_AGGREGATION, STATE_COLLECTING, STATE_DEFAULTED,
+  STATE_DISTRIBUTING, STATE_EXPIRED, STATE_SYNCHRONIZATION, STATE_TIMEOUT};
+
+pub mod lacpdu;
+
+pub const MAX_PORT_CHANNELS: usize = 8;
+pub const PORT_CHANNELS: [&str; MAX_PORT_CHANNELS] = ["port-channel1", "port-channel2", "port-channel3",
+  "port-channel4", "port-channel5", "port-channel6", "port-channel7", "port-channel8"];
+pub const DEFAULT_SYSTEM_PRIORITY: u16 = 32768;
+pub const DEFAULT_PORT_PRIORITY: u16 = 32768;
+pub const LAG_TICK: Duration = Duration::from_millis(200);
+
+const FAST_PERIODIC_TIME: Duration = Duration::from_secs(1);
+const SLOW_PERIODIC_TIME: Duration = Duration::from_secs(30);
+const SHORT_TIMEOUT_TIME: Duration = Duration::from_secs(3);
+const LONG_TIMEOUT_TIME: Duration = Duration::from_secs(90);
+
+const ETHER_TYPE_IPV4: u16 = 0x0800;
+const ETHER_TYPE_IPV6: u16 = 0x86dd;
+const IP_PROTOCOL_TCP: u8 = 6;
+const IP_PROTOCOL_UDP: u8 = 17;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LacpMode {
+  Active,  // send LACPDUs unconditionally
+  Passive, // only answer active partners
+}
+
+// Fields hashed to pick the member a frame leaves a port-channel on, IP
+// based methods fall back to MAC addresses for other frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadBalance {
+  SrcMac,
+  DstMac,
+  SrcDstMac,
+  SrcDstMacVlan,
+  SrcDstIp,
+  SrcDstIpL4,
+}
+
+// Partner information from the last LACPDU received on a port
+#[derive(Debug, Clone, Copy)]
+struct PartnerInfo {
+  info: LacpInfo,
+  at: Instant,
+  expires: Instant,
+  expired: bool, // short timeout running, partner no longer in sync
+}
+
+struct LacpPort<'a> {
+  intf: Arc<InterfaceView<'a>>,
+  number: u16,
+  group: Option<usize>, // port-channel index
+  mode: LacpMode,
+  fast: bool, // ask partner for fast periodic transmission
+  partner: Option<PartnerInfo>,
+  selected: bool, // attached to the port-channel
+  distributing: bool,
+  state: u8, // actor state last advertised
+  ntt: bool, // need to transmit
+  last_tx: Option<Instant>,
+}
+
+struct LagSystem<'a> {
+  mac: MacAddr6,
+  system_priority: u16,
+  load_balance: LoadBalance,
+  ports: Vec<LacpPort<'a>>,
+  aggregates: Vec<Arc<InterfaceView<'a>>>,
+}
+
+// Port-channels of the switch. Members of a channel group run LACP with
+// their partner and switch frames as their port-channel once both ends are
+// in sync, they never switch frames on their own.
+pub struct LinkAggregation<'a> {
+  system: Mutex<LagSystem<'a>>,
+  fib: Arc<Fib<'a>>,
+}
+
+impl<'a> LinkAggregation<'a> {
+  pub fn new(fib: Arc<Fib<'a>>) -> Self {
+    let system = LagSystem{mac: MacAddr6::nil(), system_priority: DEFAULT_SYSTEM_PRIORITY,
+      load_balance: LoadBalance::SrcDstMac, ports: Vec::new(), aggregates: Vec::new()};
+    LinkAggregation{system: Mutex::new(system), fib}
+  }
+
+  // Ports are numbered in the order they are added, system address is the
+  // lowest port address
+  pub fn add_port(&self, intf: Arc<InterfaceView<'a>>) {
+    let mut system = self.system.lock().unwrap();
+    if !intf.mac.is_nil() && (system.mac.is_nil() || intf.mac < system.mac) {
+      system.mac = intf.mac;
+    }
+    let number = system.ports.len() as u16 + 1;
+    system.ports.push(LacpPort{intf, number, group: None, mode: LacpMode::Active, fast: false, partner: None,
+      selected: false, distributing: false, state: 0, ntt: false, last_tx: None});
+  }
+
+  pub fn add_aggregate(&self, intf: Arc<InterfaceView<'a>>) {
+    let mut system = self.system.lock().unwrap();
+    intf.set_lag_state(LagState::Aggregate{members: Vec::new(), load_balance: system.load_balance});
+    system.aggregates.push(intf);
+  }
+
+  // Join or leave (None) channel group, addresses learned on the port are flushed
+  pub fn set_channel_group(&self, if_name: &str, group: Option<(usize, LacpMode)>) -> io::Result<()> {
+    let mut system = self.system.lock().unwrap();
+    let i = system.port_index(if_name)?;
+    if group.is_some() && system.ports[i].intf.is_monitoring() {
+      return Err(Error::new(ErrorKind::InvalidInput, "Monitoring interface cannot join a channel group"));
+    }
+    let port = &mut system.ports[i];
+    let previous = port.group;
+    match group {
+      Some((group, mode)) => {
+        port.group = Some(group);
+        port.mode = mode;
+      }
+      None => port.group = None,
+    }
+    if port.group != previous {
+      port.partner = None;
+      port.last_tx = None;
+      self.fib.remove_intf_entries(if_name.to_string());
+    }
+    port.ntt = true;
+    system.update();
+    Ok(())
+  }
+
+  pub fn set_rate(&self, if_name: &str, fast: bool) -> io::Result<()> {
+    let mut system = self.system.lock().unwrap();
+    let i = system.port_index(if_name)?;
+    system.ports[i].fast = fast;
+    system.ports[i].ntt = true;
+    system.update();
+    Ok(())
+  }
+
+  pub fn set_system_priority(&self, system_priority: u16) {
+    let mut system = self.system.lock().unwrap();
+    system.system_priority = system_priority;
+    for port in system.ports.iter_mut() {
+      port.ntt = true;
+    }
+  }
+
+  pub fn set_load_balance(&self, load_balance: LoadBalance) {
+    let mut system = self.system.lock().unwrap();
+    system.load_balance = load_balance;
+    system.push_states();
+  }
+
+  // Locally administered address derived from the system address
+  pub fn port_channel_mac(&self, group: usize) -> MacAddr6 {
+    let mut bytes = self.system.lock().unwrap().mac.into_array();
+    bytes[0] |= 0x02;
+    bytes[5] = bytes[5].wrapping_add(group as u8 + 1);
+    MacAddr6::from(bytes)
+  }
+
+  // Whether some interface is configured in channel group of port-channel
+  pub fn is_configured(&self, name: &str) -> bool {
+    let system = self.system.lock().unwrap();
+    let group = system.aggregates.iter().position(|aggregate| aggregate.name == name);
+    group.is_some() && system.ports.iter().any(|port| port.group == group)
+  }
+
+  // Handle frame sent to the slow protocols address on channel group member
+  pub fn receive(&self, intf: &InterfaceView, frame: &Frame) {
+    let Some(lacpdu) = Lacpdu::parse(frame) else {
+      if intf.is_debug_mode() {
+        println!("Dropping invalid LACPDU ingressing on {}", intf.name);
+      }
+      return
+    };
+    if intf.is_debug_mode() {
+      println!("Received LACPDU on {}: system {},{}, key {}, port {}, state 0x{:02x}", intf.name,
+        lacpdu.actor.system_priority, lacpdu.actor.system, lacpdu.actor.key, lacpdu.actor.port, lacpdu.actor.state);
+    }
+    let mut system = self.system.lock().unwrap();
+    if let Ok(i) = system.port_index(&intf.name) && system.ports[i].group.is_some() {
+      system.receive(i, &lacpdu);
+    }
+  }
+
+  // Expire partner information and send periodic LACPDUs
+  pub fn tick(&self) {
+    self.system.lock().unwrap().tick();
+  }
+
+  pub fn format_summary(&self) -> String {
+    let system = self.system.lock().unwrap();
+    let mut output = String::from("Flags: D - down, P - bundled in port-channel, w - waiting for partner, s - suspended\n");
+    output += "       U - port-channel in use\n";
+    output += &format!("Load balance: {}\n\n", system.load_balance);
+    output += &format!("{:<5} {:<20} {:<8} Ports\n", "Group", "Port-channel", "Protocol");
+    output += "----- -------------------- -------- ----------------------------------------\n";
+    for (g, aggregate) in system.aggregates.iter().enumerate() {
+      let members: Vec<String> = system.ports.iter()
+        .filter(|port| port.group == Some(g))
+        .map(|port| format!("{}({})", port.intf.name, port.flag()))
+        .collect();
+      if members.is_empty() {
+        continue;
+      }
+      let name = format!("{}({})", aggregate.name, if aggregate.is_up() { "U" } else { "D" });
+      output += &format!("{:<5} {:<20} {:<8} {}\n", g + 1, name, "LACP", members.join(" "));
+    }
+    output
+  }
+
+  pub fn format_neighbors(&self) -> String {
+    let system = self.system.lock().unwrap();
+    let mut output = String::from("Flags: S - partner sends slow LACPDUs, F - fast LACPDUs\n");
+    output += "       A - partner is in active mode, P - passive mode\n";
+    for (g, aggregate) in system.aggregates.iter().enumerate() {
+      let ports: Vec<&LacpPort> = system.ports.iter().filter(|port| port.group == Some(g)).collect();
+      if ports.is_empty() {
+        continue;
+      }
+      output += &format!("\nChannel group {} ({}) neighbors\n", g + 1, aggregate.name);
+      output += &format!("{:<16} {:<5} {:<24} {:<13} {:<6} {:<5} State\n", "Port", "Flags", "System ID", "Port ID",
+        "Key", "Age");
+      output += "---------------- ----- ------------------------ ------------- ------ ----- -----\n";
+      for port in ports {
+        let Some(partner) = &port.partner else {
+          output += &format!("{:<16} no partner\n", port.intf.name);
+          continue;
+        };
+        let info = &partner.info;
+        let flags = format!("{}{}", if info.state & STATE_TIMEOUT != 0 { "F" } else { "S" },
+          if info.state & STATE_ACTIVITY != 0 { "A" } else { "P" });
+        output += &format!("{:<16} {:<5} {:<24} {:<13} {:<6} {:<5} 0x{:02x}\n", port.intf.name, flags,
+          format!("{},{}", info.system_priority, info.system), format!("{},0x{:x}", info.port_priority, info.port),
+          format!("0x{:x}", info.key), format!("{}s", partner.at.elapsed().as_secs()), info.state);
+      }
+    }
+    output
+  }
+}
+
+impl<'a> LagSystem<'a> {
+  fn port_index(&self, if_name: &str) -> io::Result<usize> {
+    self.ports.iter().position(|port| port.intf.name == if_name)
+      .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("Interface {} cannot join a channel group", if_name)))
+  }
+
+  // Channel group number is the key of its members
+  fn actor_info(&self, i: usize) -> LacpInfo {
+    let port = &self.ports[i];
+    LacpInfo{system_priority: self.system_priority, system: self.mac, key: port.group.map_or(0, |g| g as u16 + 1),
+      port_priority: DEFAULT_PORT_PRIORITY, port: port.number, state: port.state}
+  }
+
+  fn receive(&mut self, i: usize, lacpdu: &Lacpdu) {
+    let now = Instant::now();
+    let port = &mut self.ports[i];
+    let timeout = if port.fast { SHORT_TIMEOUT_TIME } else { LONG_TIMEOUT_TIME };
+    port.partner = Some(PartnerInfo{info: lacpdu.actor, at: now, expires: now + timeout, expired: false});
+    // Partner has an outdated view of us
+    let actor = self.actor_info(i);
+    let mask = STATE_ACTIVITY | STATE_TIMEOUT | STATE_AGGREGATION | STATE_SYNCHRONIZATION;
+    let seen = &lacpdu.partner;
+    if (seen.system_priority, seen.system, seen.key, seen.port_priority, seen.port, seen.state & mask)
+      != (actor.system_priority, actor.system, actor.key, actor.port_priority, actor.port, actor.state & mask) {
+      self.ports[i].ntt = true;
+    }
+    self.update();
+  }
+
+  fn tick(&mut self) {
+    let now = Instant::now();
+    for port in self.ports.iter_mut() {
+      if !port.is_enabled() {
+        port.partner = None;
+        continue;
+      }
+      let Some(partner) = &mut port.partner else { continue };
+      if partner.expires > now {
+        continue;
+      }
+      if partner.expired {
+        port.partner = None; // defaulted
+      } else {
+        partner.expired = true;
+        partner.expires = now + SHORT_TIMEOUT_TIME;
+      }
+      port.ntt = true;
+    }
+    self.update();
+
+    for i in 0..self.ports.len() {
+      let port = &self.ports[i];
+      let interval = match port.partner {
+        Some(partner) if partner.info.state & STATE_TIMEOUT == 0 => SLOW_PERIODIC_TIME,
+        _ => FAST_PERIODIC_TIME,
+      };
+      if port.ntt || port.last_tx.is_none_or(|last| now.duration_since(last) >= interval) {
+        self.send(i);
+      }
+    }
+  }
+
+  // Select ports of each channel group aggregating with the same partner
+  // system and key, then let them distribute once the partner is in sync
+  fn update(&mut self) {
+    for g in 0..self.aggregates.len() {
+      let partner_id = |port: &LacpPort| port.partner
+        .filter(|partner| port.group == Some(g) && port.is_enabled() && partner.info.state & STATE_AGGREGATION != 0)
+        .map(|partner| (partner.info.system_priority, partner.info.system, partner.info.key));
+      let reference = self.ports.iter().find_map(partner_id);
+      for i in 0..self.ports.len() {
+        if self.ports[i].group != Some(g) {
+          continue;
+        }
+        let selected = reference.is_some() && partner_id(&self.ports[i]) == reference;
+        let port = &mut self.ports[i];
+        port.selected = selected;
+        port.distributing = selected && port.partner
+          .is_some_and(|partner| !partner.expired && partner.info.state & STATE_SYNCHRONIZATION != 0);
+      }
+    }
+    for port in self.ports.iter_mut() {
+      if port.group.is_none() {
+        port.selected = false;
+        port.distributing = false;
+      }
+      let state = port.actor_state();
+      if state != port.state {
+        port.state = state;
+        port.ntt = true;
+      }
+    }
+    self.push_states();
+  }
+
+  // Mirror membership in interfaces for the data plane
+  fn push_states(&self) {
+    for (g, aggregate) in self.aggregates.iter().enumerate() {
+      let members = self.ports.iter()
+        .filter(|port| port.group == Some(g) && port.distributing)
+        .map(|port| Arc::clone(&port.intf))
+        .collect();
+      aggregate.set_lag_state(LagState::Aggregate{members, load_balance: self.load_balance});
+    }
+    for port in self.ports.iter() {
+      let state = match port.group {
+        Some(g) => LagState::Member{aggregate: Arc::downgrade(&self.aggregates[g]), distributing: port.distributing},
+        None => LagState::Individual,
+      };
+      let bundled = port.intf.aggregate().is_some();
+      port.intf.set_lag_state(state);
+      if port.intf.is_debug_mode() && bundled != port.distributing {
+        println!("{} {} port-channel", port.intf.name, if port.distributing { "bundled in" } else { "unbundled from" });
+      }
+    }
+  }
+
+  // Passive ports only talk to active partners
+  fn send(&mut self, i: usize) {
+    let port = &self.ports[i];
+    let partner_active = port.partner.is_some_and(|partner| partner.info.state & STATE_ACTIVITY != 0);
+    if port.group.is_none() || !port.is_enabled() || (port.mode == LacpMode::Passive && !partner_active) {
+      return;
+    }
+    let partner = port.partner.map_or(LacpInfo{system_priority: 0, system: MacAddr6::nil(), key: 0, port_priority: 0,
+      port: 0, state: 0}, |partner| partner.info);
+    let lacpdu = Lacpdu{actor: self.actor_info(i), partner};
+    let intf = &port.intf;
+    if let Err(err) = intf.send(lacpdu.to_frame(intf.mac)) {
+      eprintln!("Error: unable to send LACPDU on {}: {}", intf.name, err);
+    }
+    let port = &mut self.ports[i];
+    port.ntt = false;
+    port.last_tx = Some(Instant::now());
+  }
+}
+
+impl LacpPort<'_> {
+  fn is_enabled(&self) -> bool {
+    self.intf.is_up() && !self.intf.is_monitoring()
+  }
+
+  fn actor_state(&self) -> u8 {
+    let mut state = STATE_AGGREGATION;
+    if self.mode == LacpMode::Active {
+      state |= STATE_ACTIVITY;
+    }
+    if self.fast {
+      state |= STATE_TIMEOUT;
+    }
+    if self.selected {
+      state |= STATE_SYNCHRONIZATION;
+    }
+    if self.distributing {
+      state |= STATE_COLLECTING | STATE_DISTRIBUTING;
+    }
+    match self.partner {
+      None => state |= STATE_DEFAULTED,
+      Some(partner) if partner.expired => state |= STATE_EXPIRED,
+      Some(_) => (),
+    }
+    state
+  }
+
+  fn flag(&self) -> &'static str {
+    if !self.is_enabled() {
+      "D"
+    } else if self.distributing {
+      "P"
+    } else if self.selected {
+      "w"
+    } else {
+      "s"
+    }
+  }
+}
+
+impl LoadBalance {
+  // Frames of a flow always leave on the same member
+  pub fn hash(&self, frame: &Frame) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    match self {
+      LoadBalance::SrcMac => frame.src_mac.hash(&mut hasher),
+      LoadBalance::DstMac => frame.dst_mac.hash(&mut hasher),
+      LoadBalance::SrcDstMac => (frame.src_mac, frame.dst_mac).hash(&mut hasher),
+      LoadBalance::SrcDstMacVlan => (frame.src_mac, frame.dst_mac, frame.get_vlan()).hash(&mut hasher),
+      LoadBalance::SrcDstIp | LoadBalance::SrcDstIpL4 => match ip_fields(frame, *self == LoadBalance::SrcDstIpL4) {
+        Some(fields) => fields.hash(&mut hasher),
+        None => (frame.src_mac, frame.dst_mac).hash(&mut hasher),
+      },
+    }
+    hasher.finish()
+  }
+}
+
+// IPv4 or IPv6 addresses of frame, followed by TCP or UDP ports if l4
+fn ip_fields(frame: &Frame, l4: bool) -> Option<(&[u8], &[u8])> {
+  let data = frame.get_data();
+  let (addresses, protocol, header_len) = match frame.get_ether_type() {
+    ETHER_TYPE_IPV4 if data.len() >= 20 => (&data[12..20], data[9], (data[0] & 0x0f) as usize * 4),
+    ETHER_TYPE_IPV6 if data.len() >= 40 => (&data[8..40], data[6], 40),
+    _ => return None,
+  };
+  let ports = match protocol {
+    IP_PROTOCOL_TCP | IP_PROTOCOL_UDP if l4 && data.len() >= header_len + 4 => &data[header_len..header_len + 4],
+    _ => &[],
+  };
+  Some((addresses, ports))
+}
+
+impl fmt::Display for LoadBalance {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}",
+    match self {
+      LoadBalance::SrcMac => "src-mac",
+      LoadBalance::DstMac => "dst-mac",
+      LoadBalance::SrcDstMac => "src-dst-mac",
+      LoadBalance::SrcDstMacVlan => "src-dst-mac-vlan",
+      LoadBalance::SrcDstIp => "src-dst-ip",
+      LoadBalance::SrcDstIpL4 => "src-dst-ip-l4",
+    })
+  }
+}
@@ -0,0 +1,92 @@
+use macaddr::MacAddr6;
+
+use crate::network::frame::Frame;
+
+// Slow protocols group address, frames sent to it never cross a bridge
+pub const SLOW_PROTOCOLS_DST_MAC: MacAddr6 = MacAddr6::new(0x01, 0x80, 0xc2, 0x00, 0x00, 0x02);
+pub const SLOW_PROTOCOLS_ETHER_TYPE: u16 = 0x8809;
+
+const LACP_SUBTYPE: u8 = 1;
+const LACP_VERSION: u8 = 1;
+const TLV_TERMINATOR: u8 = 0;
+const TLV_ACTOR: u8 = 1;
+const TLV_PARTNER: u8 = 2;
+const TLV_COLLECTOR: u8 = 3;
+const INFO_TLV_LEN: u8 = 20;
+const COLLECTOR_TLV_LEN: u8 = 16;
+const LACPDU_LEN: usize = 110;
+
+pub const STATE_ACTIVITY: u8 = 0x01;
+pub const STATE_TIMEOUT: u8 = 0x02; // short timeout, fast periodic transmission requested
+pub const STATE_AGGREGATION: u8 = 0x04;
+pub const STATE_SYNCHRONIZATION: u8 = 0x08;
+pub const STATE_COLLECTING: u8 = 0x10;
+pub const STATE_DISTRIBUTING: u8 = 0x20;
+pub const STATE_DEFAULTED: u8 = 0x40;
+pub const STATE_EXPIRED: u8 = 0x80;
+
+// Actor or partner information of an LACPDU, ports with the same system
+// and key can aggregate together
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LacpInfo {
+  pub system_priority: u16,
+  pub system: MacAddr6,
+  pub key: u16,
+  pub port_priority: u16,
+  pub port: u16,
+  pub state: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lacpdu {
+  pub actor: LacpInfo,
+  pub partner: LacpInfo,
+}
+
+impl Lacpdu {
+  // LACPDU from payload of a frame sent to the slow protocols address
+  pub fn parse(frame: &Frame) -> Option<Lacpdu> {
+    let data = frame.get_data();
+    if frame.get_ether_type() != SLOW_PROTOCOLS_ETHER_TYPE || data.len() < 4 + 2 * INFO_TLV_LEN as usize || data[0] != LACP_SUBTYPE {
+      return None;
+    }
+    if data[2] != TLV_ACTOR || data[3] != INFO_TLV_LEN || data[22] != TLV_PARTNER || data[23] != INFO_TLV_LEN {
+      return None;
+    }
+    Some(Lacpdu{actor: LacpInfo::parse(&data[4..22]), partner: LacpInfo::parse(&data[24..42])})
+  }
+
+  pub fn to_frame(&self, src_mac: MacAddr6) -> Frame {
+    let mut data = vec![LACP_SUBTYPE, LACP_VERSION];
+    for (kind, info) in [(TLV_ACTOR, &self.actor), (TLV_PARTNER, &self.partner)] {
+      data.extend([kind, INFO_TLV_LEN]);
+      data.extend(info.system_priority.to_be_bytes());
+      data.extend(info.system.as_bytes());
+      data.extend(info.key.to_be_bytes());
+      data.extend(info.port_priority.to_be_bytes());
+      data.extend(info.port.to_be_bytes());
+      data.push(info.state);
+      data.extend([0; 3]);
+    }
+    data.extend([TLV_COLLECTOR, COLLECTOR_TLV_LEN]);
+    data.extend(0u16.to_be_bytes()); // collector max delay
+    data.extend([0; 12]);
+    data.extend([TLV_TERMINATOR, 0]);
+    data.resize(LACPDU_LEN, 0);
+    Frame::new(SLOW_PROTOCOLS_DST_MAC, src_mac, SLOW_PROTOCOLS_ETHER_TYPE, data)
+  }
+}
+
+impl LacpInfo {
+  fn parse(data: &[u8]) -> LacpInfo {
+    let u16_at = |i: usize| u16::from_be_bytes([data[i], data[i + 1]]);
+    LacpInfo{
+      system_priority: u16_at(0),
+      system: MacAddr6::new(data[2], data[3], data[4], data[5], data[6], data[7]),
+      key: u16_at(8),
+      port_priority: u16_at(10),
+      port: u16_at(12),
+      state: data[14],
+    }
+  }
+}
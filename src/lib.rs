@@ -1,5 +1,6 @@
 use std::io;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::{thread, time};
 use crossbeam_channel::{unbounded, Receiver};
@@ -7,29 +8,58 @@ use dashmap::DashMap;
 
 use network::interface::{Interface, InterfaceView, IntfCmd};
 use network::frame::Frame;
-use fib::Fib;
+use fib::{Fib, FibAction, SWEEP_INTERVAL};
 use cli::shell::cli_run;
+use stp::{PortState, Stp, STP_TICK};
+use stp::bpdu::BPDU_DST_MAC;
+use lldp::{Lldp, LLDP_TICK};
+use lldp::lldpdu::LLDP_DST_MAC;
+use lag::{LinkAggregation, LAG_TICK, PORT_CHANNELS};
+use lag::lacpdu::SLOW_PROTOCOLS_DST_MAC;
+use vlan::VlanDb;
 
 mod network;
 mod fib;
 mod cli;
 mod control_plane;
+mod stp;
+mod lldp;
+mod lag;
+mod vlan;
 
 pub struct Switch<'a> {
   interfaces: Vec<Interface<'a>>,
+  aggregates: Vec<Interface<'a>>,
   intfs_view: HashMap<&'a str, Arc<InterfaceView<'a>>>,
   intfs_rx: HashMap<&'a str, Receiver<IntfCmd>>,
   fib: Arc<Fib<'a>>,
   mirrors: DashMap<String, Vec<Arc<InterfaceView<'a>>>>,
+  vlans: VlanDb,
+  stp: Stp<'a>,
+  lldp: Lldp<'a>,
+  lag: LinkAggregation<'a>,
+}
+
+// Control protocols consuming frames sent to reserved group addresses
+pub struct ControlProtocols<'s, 'a> {
+  stp: &'s Stp<'a>,
+  lldp: &'s Lldp<'a>,
+  lag: &'s LinkAggregation<'a>,
 }
 
 impl Switch<'_> {
   pub fn build(interfaces_name: &[String]) -> io::Result<Switch> {
+    let fib = Arc::new(Fib::new());
     let mut switch = Switch{ interfaces: Vec::new(),
+      aggregates: Vec::new(),
       intfs_view: HashMap::new(),
       intfs_rx: HashMap::new(),
-      fib: Arc::new(Fib::new()),
-      mirrors: DashMap::new()
+      fib: Arc::clone(&fib),
+      mirrors: DashMap::new(),
+      vlans: VlanDb::new(),
+      stp: Stp::new(Arc::clone(&fib)),
+      lldp: Lldp::new(),
+      lag: LinkAggregation::new(fib),
     };
     for name in interfaces_name {
       let (tx, rx) = unbounded::<IntfCmd>();
@@ -39,12 +69,36 @@ impl Switch<'_> {
       }
       switch.intfs_rx.insert(&name, rx);
       switch.intfs_view.insert(&name, Arc::clone(&intf.view));
+      switch.stp.add_port(Arc::clone(&intf.view));
+      switch.lldp.add_port(Arc::clone(&intf.view));
+      switch.lag.add_port(Arc::clone(&intf.view));
       switch.interfaces.push(intf);
       switch.mirrors.insert(name.clone(), Vec::new());
     }
+    // Port-channels exist from the start, they are used once members join their channel group
+    for (group, name) in PORT_CHANNELS.iter().enumerate() {
+      let (tx, rx) = unbounded::<IntfCmd>();
+      let intf = Interface::init_aggregate(name, switch.lag.port_channel_mac(group), tx);
+      switch.intfs_rx.insert(name, rx);
+      switch.intfs_view.insert(name, Arc::clone(&intf.view));
+      switch.stp.add_port(Arc::clone(&intf.view));
+      switch.lag.add_aggregate(Arc::clone(&intf.view));
+      switch.aggregates.push(intf);
+      switch.mirrors.insert(name.to_string(), Vec::new());
+    }
     Ok(switch)
   }
 
+  // Reload FIB saved by a previous run, it is saved back to the same file on exit
+  pub fn restore_fib(&self, path: &str) {
+    let path = PathBuf::from(path);
+    match self.fib.restore(&path, &self.intfs_view) {
+      Ok(count) => println!("Restored {} FIB entries from {}", count, path.display()),
+      Err(err) => eprintln!("Error: unable to restore FIB from {}: {}", path.display(), err),
+    }
+    self.fib.set_save_file(Some(path));
+  }
+
   pub fn start(&mut self) {
     thread::scope(|scope| {
       let interfaces = std::mem::take(&mut self.interfaces);
@@ -55,20 +109,50 @@ impl Switch<'_> {
         egr_intfs.remove(&ing_intf.name[..]);
         let fib = Arc::clone(&self.fib);
         let mirrors = &self.mirrors;
+        let vlans = &self.vlans;
+        let protocols = ControlProtocols{stp: &self.stp, lldp: &self.lldp, lag: &self.lag};
 
         let _ = scope.spawn( move || {
-          run_interface_worker(ing_intf, rx, egr_intfs, fib, mirrors);
+          run_interface_worker(ing_intf, rx, egr_intfs, fib, mirrors, vlans, protocols);
         });
       }
 
-      cli_run(&self.intfs_view, &self.fib);
+      let fib = Arc::clone(&self.fib);
+      let _ = scope.spawn( move || {
+        run_fib_sweeper(fib);
+      });
+
+      let stp = &self.stp;
+      let _ = scope.spawn( move || {
+        run_stp_timer(stp);
+      });
+
+      let lldp = &self.lldp;
+      let _ = scope.spawn( move || {
+        run_lldp_timer(lldp);
+      });
+
+      let aggregates = std::mem::take(&mut self.aggregates).into_iter()
+        .map(|aggregate| {
+          let rx = self.intfs_rx[&aggregate.name.as_str()].clone();
+          (aggregate, rx)
+        })
+        .collect();
+      let fib = Arc::clone(&self.fib);
+      let mirrors = &self.mirrors;
+      let lag = &self.lag;
+      let _ = scope.spawn( move || {
+        run_lag_worker(aggregates, fib, mirrors, lag);
+      });
+
+      cli_run(self);
     });
   }
 }
 
 pub fn run_interface_worker<'a>(mut ing_intf: Interface<'a>, rx: Receiver<IntfCmd>,
   egr_intfs: HashMap<&str, Arc<InterfaceView<'a>>>, fib: Arc<Fib<'a>>,
-  mirrors: &DashMap<String, Vec<Arc<InterfaceView<'a>>>>,) {
+  mirrors: &DashMap<String, Vec<Arc<InterfaceView<'a>>>>, vlans: &VlanDb, protocols: ControlProtocols) {
   loop {
 
     // Control plane
@@ -84,17 +168,92 @@ pub fn run_interface_worker<'a>(mut ing_intf: Interface<'a>, rx: Receiver<IntfCm
     match ing_intf.receive() {
       Ok(Some(frame)) => {
 
-        if let Some(frame) = ing_intf.ing_process_frame(frame.clone()) {
-          fib.learn(frame.get_vlan(), &frame.src_mac, Arc::clone(&ing_intf.view));
-
-          if !frame.is_broadcast() &&
-            let Some(egr_intf) = fib.lookup(frame.get_vlan(), &frame.dst_mac) &&
-            egr_intf.is_up() && !egr_intf.is_monitoring() &&
-            egr_intf.allows_vlan_in(frame.get_vlan()) {
-            // Unicast
-            egr_process_and_send(&egr_intf, &frame, mirrors);
-          } else {
-            flood(&egr_intfs, &frame, &mirrors);
+        // LACPDUs are consumed by channel group members, flooded like any frame on other ports
+        if frame.dst_mac == SLOW_PROTOCOLS_DST_MAC && ing_intf.view.is_channel_member() {
+          protocols.lag.receive(&ing_intf.view, &frame);
+          continue
+        }
+        // LLDPDUs are consumed by the agent, flooded like any frame when it is disabled
+        if frame.dst_mac == LLDP_DST_MAC && protocols.lldp.is_enabled() {
+          protocols.lldp.receive(&ing_intf.view, &frame);
+          continue
+        }
+
+        // Members of a port-channel switch frames as their port-channel
+        let aggregate = ing_intf.view.aggregate();
+        if aggregate.is_none() && ing_intf.view.is_channel_member() {
+          if ing_intf.view.is_debug_mode() {
+            println!("Dropping frame ingressing on {}: port is not bundled in its port-channel", ing_intf.name);
+          }
+          continue
+        }
+        let ing_view = match &aggregate {
+          Some(aggregate) => {
+            aggregate.add_in_frame(frame.len());
+            aggregate
+          }
+          None => &ing_intf.view,
+        };
+
+        // BPDUs are consumed by spanning tree, flooded like any frame when it is
+        // disabled unless BPDU filter or guard is set on the port
+        if frame.dst_mac == BPDU_DST_MAC {
+          if protocols.stp.guard_bpdu(ing_view) {
+            continue
+          }
+          if protocols.stp.is_enabled() {
+            protocols.stp.receive(ing_view, &frame);
+            continue
+          }
+        }
+        if let Some(frame) = ing_view.ing_process_frame(frame.clone()) {
+          if !vlans.is_active(frame.get_vlan()) {
+            if ing_view.is_debug_mode() {
+              println!("Dropping frame ingressing on {}: vlan {} is not active", ing_view.name, frame.get_vlan());
+            }
+            continue
+          }
+          // Port state of the spanning tree instance carrying the vlan
+          let stp_state = ing_view.get_stp_state(frame.get_vlan());
+          if stp_state == PortState::Discarding {
+            if ing_view.is_debug_mode() {
+              println!("Dropping frame ingressing on {}: port is discarding in vlan {}", ing_view.name, frame.get_vlan());
+            }
+            continue
+          }
+          vlans.count_in(frame.get_vlan(), frame.len());
+
+          // Private vlan domains share the FIB of their primary vlan
+          let fib_vlan = vlans.primary_vlan(frame.get_vlan());
+          if let Err(err) = fib.learn(fib_vlan, &frame.src_mac, Arc::clone(ing_view)) {
+            control_plane::handle_learn_error(ing_view, &err);
+            if err.drops_frame() {
+              if ing_view.is_debug_mode() {
+                println!("Dropping frame ingressing on {}: {}", ing_view.name, err);
+              }
+              continue
+            }
+          }
+          if stp_state == PortState::Learning {
+            continue
+          }
+
+          let dst = if frame.is_broadcast() { None } else { fib.lookup(fib_vlan, &frame.dst_mac) };
+          match dst {
+            Some(FibAction::Drop) => {
+              if ing_view.is_debug_mode() {
+                println!("Dropping frame ingressing on {}: destination MAC is blackholed", ing_view.name);
+              }
+              continue
+            }
+            Some(FibAction::Forward(egr_intf)) if egr_intf.is_up() && !egr_intf.is_monitoring() &&
+              egr_intf.get_stp_state(frame.get_vlan()) == PortState::Forwarding &&
+              egr_intf.allows_vlan_in(frame.get_vlan()) && vlans.private_vlan_allows(frame.get_vlan(), &egr_intf) => {
+              // Unicast
+              vlans.count_forwarded(frame.get_vlan());
+              egr_process_and_send(&egr_intf, &frame, mirrors, vlans);
+            }
+            _ => flood(&egr_intfs, ing_view, &frame, mirrors, vlans),
           }
         } else { // frame dropped
           continue
@@ -108,26 +267,69 @@ pub fn run_interface_worker<'a>(mut ing_intf: Interface<'a>, rx: Receiver<IntfCm
   }
 }
 
-// Frame flooding
-pub fn flood(intfs: &HashMap<&str, Arc<InterfaceView>>, frame: &Frame,
-  mirrors: &DashMap<String, Vec<Arc<InterfaceView>>>) {
+pub fn run_fib_sweeper(fib: Arc<Fib>) {
+  loop {
+    thread::sleep(SWEEP_INTERVAL);
+    fib.age_entries();
+  }
+}
+
+pub fn run_stp_timer(stp: &Stp) {
+  loop {
+    thread::sleep(STP_TICK);
+    stp.tick();
+  }
+}
 
+pub fn run_lldp_timer(lldp: &Lldp) {
+  loop {
+    thread::sleep(LLDP_TICK);
+    lldp.tick();
+  }
+}
+
+// Port-channels have no interface worker, their configuration is applied here
+pub fn run_lag_worker<'a>(mut aggregates: Vec<(Interface<'a>, Receiver<IntfCmd>)>, fib: Arc<Fib<'a>>,
+  mirrors: &DashMap<String, Vec<Arc<InterfaceView<'a>>>>, lag: &LinkAggregation) {
+  loop {
+    for (aggregate, rx) in aggregates.iter_mut() {
+      while !rx.is_empty() {
+        control_plane::handle_control_plane(aggregate, rx, &fib, mirrors);
+      }
+    }
+    lag.tick();
+    thread::sleep(LAG_TICK);
+  }
+}
+
+// Frame flooding, channel group members send through their port-channel
+pub fn flood(intfs: &HashMap<&str, Arc<InterfaceView>>, ing_intf: &InterfaceView, frame: &Frame,
+  mirrors: &DashMap<String, Vec<Arc<InterfaceView>>>, vlans: &VlanDb) {
+
+  vlans.count_flooded(frame.get_vlan());
   for (_, intf) in intfs {
-    if intf.is_up() && !intf.is_monitoring() && intf.allows_vlan_out(frame.get_vlan()) {
-      egr_process_and_send(intf, &frame, mirrors);
+    if intf.name == ing_intf.name || intf.is_channel_member() {
+      continue;
+    }
+    if intf.is_up() && !intf.is_monitoring() && intf.get_stp_state(frame.get_vlan()) == PortState::Forwarding
+      && intf.allows_vlan_out(frame.get_vlan())
+      && vlans.private_vlan_allows(frame.get_vlan(), intf) {
+      egr_process_and_send(intf, frame, mirrors, vlans);
     }
   }
 }
 
 
 pub fn egr_process_and_send(egr_intf: &InterfaceView, frame: &Frame,
-  mirrors: &DashMap<String, Vec<Arc<InterfaceView>>>) {
+  mirrors: &DashMap<String, Vec<Arc<InterfaceView>>>, vlans: &VlanDb) {
 
   // untag frame
   let out_frame = egr_intf.egr_process_frame(frame.clone());
 
   if let Err(err) = egr_intf.send(out_frame.clone()) {
     eprintln!("Error: {}", err);
+  } else {
+    vlans.count_out(frame.get_vlan(), out_frame.len());
   }
 
   mirror_frame(mirrors, egr_intf, &out_frame);
@@ -0,0 +1,247 @@
+use std::io::{self, Error, ErrorKind};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use macaddr::MacAddr6;
+
+use crate::network::frame::Frame;
+use crate::network::interface::InterfaceView;
+use lldpdu::Lldpdu;
+
+pub mod lldpdu;
+
+pub const LLDP_TICK: Duration = Duration::from_secs(1);
+pub const DEFAULT_TX_INTERVAL: u16 = 30;
+pub const DEFAULT_HOLD_MULTIPLIER: u16 = 4;
+const MAX_NEIGHBORS: usize = 1024;
+
+struct LldpPort<'a> {
+  intf: Arc<InterfaceView<'a>>,
+  transmit: bool,
+  receive: bool,
+}
+
+// Remote system advertised on a local interface, identified by chassis and port IDs
+struct Neighbor {
+  local_intf: String,
+  lldpdu: Lldpdu,
+  expires: Instant,
+}
+
+struct LldpAgent<'a> {
+  chassis_id: MacAddr6,
+  system_name: String,
+  last_tx: Option<Instant>,
+  ports: Vec<LldpPort<'a>>,
+  neighbors: Vec<Neighbor>,
+}
+
+// LLDP agent of the switch, advertises every up interface and keeps the
+// systems advertised by neighbours until their TTL expires. Disabled by
+// default, LLDPDUs are then flooded like any frame.
+pub struct Lldp<'a> {
+  enabled: AtomicBool,
+  agent: Mutex<LldpAgent<'a>>,
+}
+
+impl<'a> Lldp<'a> {
+  pub fn new() -> Self {
+    let system_name = std::fs::read_to_string("/proc/sys/kernel/hostname")
+      .map(|name| name.trim().to_string())
+      .unwrap_or_default();
+    let agent = LldpAgent{chassis_id: MacAddr6::nil(), system_name, last_tx: None, ports: Vec::new(),
+      neighbors: Vec::new()};
+    Lldp{enabled: AtomicBool::new(false), agent: Mutex::new(agent)}
+  }
+
+  // Chassis ID is the lowest interface address
+  pub fn add_port(&self, intf: Arc<InterfaceView<'a>>) {
+    let mut agent = self.agent.lock().unwrap();
+    if !intf.mac.is_nil() && (agent.chassis_id.is_nil() || intf.mac < agent.chassis_id) {
+      agent.chassis_id = intf.mac;
+    }
+    agent.ports.push(LldpPort{intf, transmit: true, receive: true});
+  }
+
+  pub fn is_enabled(&self) -> bool {
+    self.enabled.load(Ordering::Relaxed)
+  }
+
+  // Neighbours are told to forget us with a zero TTL when the agent stops
+  pub fn set_enabled(&self, enabled: bool) {
+    let mut agent = self.agent.lock().unwrap();
+    if self.enabled.swap(enabled, Ordering::Relaxed) == enabled {
+      return;
+    }
+    agent.last_tx = None;
+    if !enabled {
+      for i in 0..agent.ports.len() {
+        agent.send_shutdown(i);
+      }
+      agent.neighbors.clear();
+    }
+  }
+
+  pub fn set_port_transmit(&self, if_name: &str, transmit: bool) -> io::Result<()> {
+    let mut agent = self.agent.lock().unwrap();
+    let i = agent.port_index(if_name)?;
+    if agent.ports[i].transmit == transmit {
+      return Ok(());
+    }
+    if self.is_enabled() {
+      if transmit {
+        agent.send(i);
+      } else {
+        agent.send_shutdown(i);
+      }
+    }
+    agent.ports[i].transmit = transmit;
+    Ok(())
+  }
+
+  pub fn set_port_receive(&self, if_name: &str, receive: bool) -> io::Result<()> {
+    let mut agent = self.agent.lock().unwrap();
+    let i = agent.port_index(if_name)?;
+    agent.ports[i].receive = receive;
+    if !receive {
+      agent.neighbors.retain(|neighbor| neighbor.local_intf != if_name);
+    }
+    Ok(())
+  }
+
+  // Handle frame sent to the nearest bridge group address on interface
+  pub fn receive(&self, intf: &InterfaceView, frame: &Frame) {
+    let Some(lldpdu) = Lldpdu::parse(frame) else {
+      if intf.is_debug_mode() {
+        println!("Dropping invalid LLDPDU ingressing on {}", intf.name);
+      }
+      return
+    };
+    if intf.is_debug_mode() {
+      println!("Received LLDPDU on {}: chassis {}, port {}, ttl {}", intf.name, lldpdu.chassis_id,
+        lldpdu.port_id, lldpdu.ttl);
+    }
+    let mut agent = self.agent.lock().unwrap();
+    if !agent.port_index(&intf.name).is_ok_and(|i| agent.ports[i].receive) {
+      return;
+    }
+    let known = agent.neighbors.iter().position(|neighbor| neighbor.local_intf == intf.name
+      && neighbor.lldpdu.chassis_id == lldpdu.chassis_id && neighbor.lldpdu.port_id == lldpdu.port_id);
+    let expires = Instant::now() + Duration::from_secs(lldpdu.ttl as u64);
+    match known {
+      Some(n) if lldpdu.ttl == 0 => _ = agent.neighbors.remove(n),
+      Some(n) => agent.neighbors[n] = Neighbor{local_intf: intf.name.clone(), lldpdu, expires},
+      None if lldpdu.ttl == 0 => (),
+      None if agent.neighbors.len() >= MAX_NEIGHBORS => {
+        if intf.is_debug_mode() {
+          println!("Ignoring LLDPDU ingressing on {}: neighbor table full", intf.name);
+        }
+      }
+      None => agent.neighbors.push(Neighbor{local_intf: intf.name.clone(), lldpdu, expires}),
+    }
+  }
+
+  // Expire neighbours and advertise interfaces every transmit interval
+  pub fn tick(&self) {
+    if !self.is_enabled() {
+      return;
+    }
+    let mut agent = self.agent.lock().unwrap();
+    let now = Instant::now();
+    let down: Vec<String> = agent.ports.iter()
+      .filter(|port| !port.is_enabled())
+      .map(|port| port.intf.name.clone())
+      .collect();
+    agent.neighbors.retain(|neighbor| neighbor.expires > now && !down.contains(&neighbor.local_intf));
+
+    if agent.last_tx.is_some_and(|last| now.duration_since(last) < Duration::from_secs(DEFAULT_TX_INTERVAL as u64)) {
+      return;
+    }
+    agent.last_tx = Some(now);
+    for i in 0..agent.ports.len() {
+      agent.send(i);
+    }
+  }
+
+  pub fn format_neighbors(&self) -> String {
+    let agent = self.agent.lock().unwrap();
+    let mut output = format!("{:<16} {:<18} {:<16} {:<9} System Name\n", "Local Intf", "Chassis ID", "Port ID", "Hold-time");
+    output += "---------------- ------------------ ---------------- --------- ----------------\n";
+    for neighbor in agent.sorted_neighbors() {
+      let lldpdu = &neighbor.lldpdu;
+      output += &format!("{:<16} {:<18} {:<16} {:<9} {}\n", neighbor.local_intf, lldpdu.chassis_id, lldpdu.port_id,
+        neighbor.time_remaining(), lldpdu.system_name.as_deref().unwrap_or("-"));
+    }
+    output += &format!("\nTotal entries displayed: {}\n", agent.neighbors.len());
+    output
+  }
+
+  pub fn format_neighbors_detail(&self) -> String {
+    let agent = self.agent.lock().unwrap();
+    let mut output = String::new();
+    for neighbor in agent.sorted_neighbors() {
+      let lldpdu = &neighbor.lldpdu;
+      output += "------------------------------------------------\n";
+      output += &format!("Local Intf: {}\n", neighbor.local_intf);
+      output += &format!("Chassis ID: {}\n", lldpdu.chassis_id);
+      output += &format!("Port ID: {}\n", lldpdu.port_id);
+      output += &format!("Port Description: {}\n", lldpdu.port_description.as_deref().unwrap_or("-"));
+      output += &format!("System Name: {}\n", lldpdu.system_name.as_deref().unwrap_or("-"));
+      output += &format!("Port VLAN ID: {}\n", lldpdu.port_vlan.map_or("-".to_string(), |vlan| vlan.to_string()));
+      output += &format!("Time remaining: {} sec\n", neighbor.time_remaining());
+    }
+    output += &format!("\nTotal entries displayed: {}\n", agent.neighbors.len());
+    output
+  }
+}
+
+impl<'a> LldpAgent<'a> {
+  fn port_index(&self, if_name: &str) -> io::Result<usize> {
+    self.ports.iter().position(|port| port.intf.name == if_name)
+      .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("Interface {} not found", if_name)))
+  }
+
+  fn lldpdu(&self, i: usize, ttl: u16) -> Lldpdu {
+    let mode = self.ports[i].intf.get_port_mode();
+    Lldpdu{chassis_id: self.chassis_id.to_string(), port_id: self.ports[i].intf.name.clone(), ttl,
+      port_description: Some(format!("{} port", mode)),
+      system_name: Some(self.system_name.clone()).filter(|name| !name.is_empty()),
+      port_vlan: mode.pvid()}
+  }
+
+  fn send(&self, i: usize) {
+    self.send_lldpdu(i, DEFAULT_TX_INTERVAL * DEFAULT_HOLD_MULTIPLIER);
+  }
+
+  fn send_shutdown(&self, i: usize) {
+    self.send_lldpdu(i, 0);
+  }
+
+  fn send_lldpdu(&self, i: usize, ttl: u16) {
+    let port = &self.ports[i];
+    if !port.transmit || !port.is_enabled() {
+      return;
+    }
+    if let Err(err) = port.intf.send(self.lldpdu(i, ttl).to_frame(port.intf.mac)) {
+      eprintln!("Error: unable to send LLDPDU on {}: {}", port.intf.name, err);
+    }
+  }
+
+  fn sorted_neighbors(&self) -> Vec<&Neighbor> {
+    let mut neighbors: Vec<&Neighbor> = self.neighbors.iter().collect();
+    neighbors.sort_by(|a, b| (&a.local_intf, &a.lldpdu.chassis_id).cmp(&(&b.local_intf, &b.lldpdu.chassis_id)));
+    neighbors
+  }
+}
+
+impl LldpPort<'_> {
+  fn is_enabled(&self) -> bool {
+    self.intf.is_up() && !self.intf.is_monitoring()
+  }
+}
+
+impl Neighbor {
+  fn time_remaining(&self) -> u64 {
+    self.expires.saturating_duration_since(Instant::now()).as_secs()
+  }
+}
@@ -0,0 +1,131 @@
+use macaddr::MacAddr6;
+
+use crate::network::frame::Frame;
+
+// Nearest bridge group address, LLDPDUs sent to it never cross a bridge
+pub const LLDP_DST_MAC: MacAddr6 = MacAddr6::new(0x01, 0x80, 0xc2, 0x00, 0x00, 0x0e);
+pub const LLDP_ETHER_TYPE: u16 = 0x88cc;
+
+const TLV_END: u8 = 0;
+const TLV_CHASSIS_ID: u8 = 1;
+const TLV_PORT_ID: u8 = 2;
+const TLV_TTL: u8 = 3;
+const TLV_PORT_DESCRIPTION: u8 = 4;
+const TLV_SYSTEM_NAME: u8 = 5;
+const TLV_ORG_SPECIFIC: u8 = 127;
+const TLV_MAX_LEN: usize = 511;
+
+const CHASSIS_SUBTYPE_MAC: u8 = 4;
+const CHASSIS_SUBTYPE_LOCAL: u8 = 7;
+const PORT_SUBTYPE_MAC: u8 = 3;
+const PORT_SUBTYPE_INTF_NAME: u8 = 5;
+
+// IEEE 802.1 organizationally specific TLVs
+const DOT1_OUI: [u8; 3] = [0x00, 0x80, 0xc2];
+const DOT1_SUBTYPE_PORT_VLAN: u8 = 1;
+const MIN_FRAME_DATA_LEN: usize = 46;
+
+// LLDP data unit, only TLVs used by the agent are kept
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lldpdu {
+  pub chassis_id: String,
+  pub port_id: String,
+  pub ttl: u16, // seconds, 0 when the neighbour shuts its agent down
+  pub port_description: Option<String>,
+  pub system_name: Option<String>,
+  pub port_vlan: Option<u16>,
+}
+
+impl Lldpdu {
+  // Chassis ID, port ID and TTL TLVs are mandatory and come first in this order
+  pub fn parse(frame: &Frame) -> Option<Lldpdu> {
+    if frame.get_ether_type() != LLDP_ETHER_TYPE {
+      return None;
+    }
+    let mut tlvs = Tlvs{data: frame.get_data()};
+    let (TLV_CHASSIS_ID, [chassis_subtype, chassis_id @ ..]) = tlvs.next()? else { return None };
+    let (TLV_PORT_ID, [port_subtype, port_id @ ..]) = tlvs.next()? else { return None };
+    let (TLV_TTL, &[ttl_high, ttl_low, ..]) = tlvs.next()? else { return None };
+    let mut lldpdu = Lldpdu{
+      chassis_id: format_id(chassis_id, *chassis_subtype == CHASSIS_SUBTYPE_MAC),
+      port_id: format_id(port_id, *port_subtype == PORT_SUBTYPE_MAC),
+      ttl: u16::from_be_bytes([ttl_high, ttl_low]),
+      port_description: None,
+      system_name: None,
+      port_vlan: None,
+    };
+    for (kind, value) in tlvs {
+      match (kind, value) {
+        (TLV_END, _) => break,
+        (TLV_PORT_DESCRIPTION, _) => lldpdu.port_description = Some(String::from_utf8_lossy(value).to_string()),
+        (TLV_SYSTEM_NAME, _) => lldpdu.system_name = Some(String::from_utf8_lossy(value).to_string()),
+        (TLV_ORG_SPECIFIC, [oui @ .., DOT1_SUBTYPE_PORT_VLAN, high, low]) if *oui == DOT1_OUI => {
+          lldpdu.port_vlan = Some(u16::from_be_bytes([*high, *low]));
+        }
+        _ => (), // unsupported TLV
+      }
+    }
+    Some(lldpdu)
+  }
+
+  // Chassis ID is sent as a MAC address when it is one, port ID as an interface name
+  pub fn to_frame(&self, src_mac: MacAddr6) -> Frame {
+    let mut data = Vec::new();
+    let chassis_id = match self.chassis_id.parse::<MacAddr6>() {
+      Ok(mac) => [&[CHASSIS_SUBTYPE_MAC], mac.as_bytes()].concat(),
+      Err(_) => [&[CHASSIS_SUBTYPE_LOCAL], self.chassis_id.as_bytes()].concat(),
+    };
+    push_tlv(&mut data, TLV_CHASSIS_ID, &chassis_id);
+    push_tlv(&mut data, TLV_PORT_ID, &[&[PORT_SUBTYPE_INTF_NAME], self.port_id.as_bytes()].concat());
+    push_tlv(&mut data, TLV_TTL, &self.ttl.to_be_bytes());
+    if let Some(port_description) = &self.port_description {
+      push_tlv(&mut data, TLV_PORT_DESCRIPTION, port_description.as_bytes());
+    }
+    if let Some(system_name) = &self.system_name {
+      push_tlv(&mut data, TLV_SYSTEM_NAME, system_name.as_bytes());
+    }
+    if let Some(port_vlan) = self.port_vlan {
+      push_tlv(&mut data, TLV_ORG_SPECIFIC, &[&DOT1_OUI[..], &[DOT1_SUBTYPE_PORT_VLAN], &port_vlan.to_be_bytes()].concat());
+    }
+    push_tlv(&mut data, TLV_END, &[]);
+    data.resize(data.len().max(MIN_FRAME_DATA_LEN), 0);
+    Frame::new(LLDP_DST_MAC, src_mac, LLDP_ETHER_TYPE, data)
+  }
+}
+
+// Type and value of TLVs, stops on truncated ones
+struct Tlvs<'d> {
+  data: &'d [u8],
+}
+
+impl<'d> Iterator for Tlvs<'d> {
+  type Item = (u8, &'d [u8]);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let [high, low, rest @ ..] = self.data else { return None };
+    let kind = high >> 1;
+    let len = ((*high as usize & 1) << 8) | *low as usize;
+    if rest.len() < len {
+      return None;
+    }
+    let (value, rest) = rest.split_at(len);
+    self.data = rest;
+    Some((kind, value))
+  }
+}
+
+// Type is 7 bits and length 9 bits, longer values are truncated
+fn push_tlv(data: &mut Vec<u8>, kind: u8, value: &[u8]) {
+  let value = &value[..value.len().min(TLV_MAX_LEN)];
+  data.extend((((kind as u16) << 9) | value.len() as u16).to_be_bytes());
+  data.extend(value);
+}
+
+// Printable identifier, hex bytes when it is neither a MAC address nor text
+fn format_id(value: &[u8], mac: bool) -> String {
+  match (value, std::str::from_utf8(value)) {
+    (&[a, b, c, d, e, f], _) if mac => MacAddr6::new(a, b, c, d, e, f).to_string(),
+    (_, Ok(text)) if !text.is_empty() && text.chars().all(|c| !c.is_control()) => text.to_string(),
+    _ => value.iter().map(|byte| format!("{:02x}", byte)).collect(),
+  }
+}
@@ -12,11 +12,32 @@ fn main() {
       println!("\tINTERFACES\tList of interfaces to attach to the switch");
       println!("Options:");
       println!("\t-h, --help\tShow this help message");
+      println!("\t--fib-restore <FILE>\tRestore FIB from FILE at startup and save it back on exit");
       process::exit(0);
 		}
 
-    match Switch::build(&args[1..]) {
-      Ok(mut switch) => switch.start(),
+    let mut interfaces = Vec::new();
+    let mut fib_restore = None;
+    let mut args_iter = args[1..].iter();
+    while let Some(arg) = args_iter.next() {
+      if arg == "--fib-restore" {
+        let Some(file) = args_iter.next() else {
+          eprintln!("Error: --fib-restore requires a file");
+          process::exit(1);
+        };
+        fib_restore = Some(file);
+      } else {
+        interfaces.push(arg.clone());
+      }
+    }
+
+    match Switch::build(&interfaces) {
+      Ok(mut switch) => {
+        if let Some(file) = fib_restore {
+          switch.restore_fib(file);
+        }
+        switch.start()
+      },
       Err(_) => process::exit(1),
     }
 }
@@ -1,2 +1,3 @@
+pub mod classify;
 pub mod frame;
 pub mod interface;
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::fmt;
+use macaddr::MacAddr6;
+
+use super::frame::Frame;
+
+// Vlan classification of untagged frames on access ports. Source MAC rules
+// are tried first, longest mask first, then ether type rules.
+#[derive(Debug, Clone, Default)]
+pub struct VlanClassifiers {
+  macs: Vec<(MacAddr6, MacAddr6, u16)>, // masked mac, mask, vlan
+  protocols: HashMap<u16, u16>, // ether type -> vlan
+}
+
+impl VlanClassifiers {
+  pub fn classify(&self, frame: &Frame) -> Option<u16> {
+    let src_mac = mac_to_u64(&frame.src_mac);
+    self.macs.iter()
+      .find(|(mac, mask, _)| src_mac & mac_to_u64(mask) == mac_to_u64(mac))
+      .map(|(_, _, vlan)| *vlan)
+      .or_else(|| self.protocols.get(&frame.get_ether_type()).copied())
+  }
+
+  pub fn add_mac(&mut self, mac: &MacAddr6, mask: &MacAddr6, vlan: u16) {
+    let mac = u64_to_mac(mac_to_u64(mac) & mac_to_u64(mask));
+    self.remove_mac(&mac, mask);
+    self.macs.push((mac, *mask, vlan));
+    self.macs.sort_by_key(|(mac, mask, _)| (std::cmp::Reverse(mac_to_u64(mask).count_ones()), mac_to_u64(mac)));
+  }
+
+  pub fn remove_mac(&mut self, mac: &MacAddr6, mask: &MacAddr6) {
+    let mac = mac_to_u64(mac) & mac_to_u64(mask);
+    self.macs.retain(|(other, other_mask, _)| !(mac_to_u64(other) == mac && other_mask == mask));
+  }
+
+  pub fn add_protocol(&mut self, ether_type: u16, vlan: u16) {
+    self.protocols.insert(ether_type, vlan);
+  }
+
+  pub fn remove_protocol(&mut self, ether_type: u16) {
+    self.protocols.remove(&ether_type);
+  }
+
+  // Whether frames of vlan may be classified by a rule
+  pub fn contains_vlan(&self, vlan: u16) -> bool {
+    self.macs.iter().any(|(_, _, other)| *other == vlan) || self.protocols.values().any(|other| *other == vlan)
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.macs.is_empty() && self.protocols.is_empty()
+  }
+}
+
+fn mac_to_u64(mac: &MacAddr6) -> u64 {
+  mac.as_bytes().iter().fold(0, |acc, byte| (acc << 8) | *byte as u64)
+}
+
+fn u64_to_mac(value: u64) -> MacAddr6 {
+  let bytes = value.to_be_bytes();
+  MacAddr6::new(bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7])
+}
+
+impl fmt::Display for VlanClassifiers {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    for (mac, mask, vlan) in &self.macs {
+      writeln!(f, "\tMAC {}/{} -> {}", mac, mask, vlan)?;
+    }
+    let mut protocols: Vec<(&u16, &u16)> = self.protocols.iter().collect();
+    protocols.sort();
+    for (ether_type, vlan) in protocols {
+      writeln!(f, "\tProtocol 0x{:04x} -> {}", ether_type, vlan)?;
+    }
+    Ok(())
+  }
+}
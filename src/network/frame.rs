@@ -4,6 +4,9 @@ use std::collections::VecDeque;
 
 use macaddr::MacAddr6;
 
+pub const DOT1Q_TPID: u16 = 0x8100;
+pub const QINQ_TPID: u16 = 0x88a8;
+
 #[derive(Debug,Clone)]
 struct Tag {
   tpid: u16,
@@ -15,10 +18,10 @@ impl Tag {
     debug_assert!(bytes.len() == 4);
     let tpid = ((bytes[0] as u16) << 8) | bytes[1] as u16;
     let tci = ((bytes[2] as u16) << 8) | bytes[3] as u16;
-    Tag{tpid: tpid, tci: tci}
+    Tag{tpid, tci: tci}
   }
 
-  pub fn build(pcp : u8, dei: bool, vlan: u16) -> Self {
+  pub fn build(tpid: u16, pcp : u8, dei: bool, vlan: u16) -> Self {
     debug_assert!(vlan < 4096);
     let mut tci = 0 as u16;
     tci |= (pcp as u16) << 13;
@@ -26,7 +29,7 @@ impl Tag {
       tci |= (1 as u16) << 12;
     }
     tci |= vlan;
-    Tag{tpid: 0x8100, tci: tci}
+    Tag{tpid, tci: tci}
   }
 
   pub fn build_from_u16(tpid : u16, tci: u16) -> Self {
@@ -44,7 +47,8 @@ pub struct Frame {
 }
 
 impl Frame {
-  pub fn parse(bytes: &[u8], size: usize, aux_data: Option<libc::tpacket_auxdata>) -> Frame {
+  // Tags are stacked as long as the ether type is one of tpids
+  pub fn parse(bytes: &[u8], size: usize, aux_data: Option<libc::tpacket_auxdata>, tpids: &[u16]) -> Frame {
     if size < 13 {
       panic!("Array too small to contain valid frame")
     }
@@ -54,18 +58,21 @@ impl Frame {
     let mut cursor = 14;
     let mut tags = VecDeque::new();
     if let Some(aux_data) = aux_data { // offloaded outermost dot1q tag handling
-      tags.push_back(Tag::build_from_u16(aux_data.tp_vlan_tpid, aux_data.tp_vlan_tci));
+      // Kernels not reporting the TPID only offload 802.1Q tags
+      let tpid = if aux_data.tp_status & libc::TP_STATUS_VLAN_TPID_VALID != 0 { aux_data.tp_vlan_tpid } else { DOT1Q_TPID };
+      if !tpids.contains(&tpid) {
+        // Not a tag for this port, put it back in the payload
+        let mut data = vec![(aux_data.tp_vlan_tci >> 8) as u8, aux_data.tp_vlan_tci as u8];
+        data.extend(&bytes[12..size]);
+        return Frame{dst_mac, src_mac, tags, ether_type: tpid, data}
+      }
+      tags.push_back(Tag::build_from_u16(tpid, aux_data.tp_vlan_tci));
     }
-    while ether_type == 0x88a8 {
-      tags.push_back(Tag::parse(&bytes[cursor - 2..16]));
+    while tpids.contains(&ether_type) && size >= cursor + 4 { // inline tag handling
+      tags.push_back(Tag::parse(&bytes[cursor - 2..cursor + 2]));
       cursor += 4;
       ether_type = ((bytes[cursor - 2] as u16) << 8) | bytes[cursor - 1] as u16;
     }
-    if ether_type == 0x8100 { // inline dot1q handling
-       tags.push_back(Tag::parse(&bytes[12..16]));
-       cursor += 4;
-       ether_type = ((bytes[cursor - 2] as u16) << 8) | bytes[cursor - 1] as u16;
-    }
     let data = bytes[cursor..size].to_vec();
 
     Frame{dst_mac: dst_mac, src_mac: src_mac, tags: tags, ether_type: ether_type, data: data}
@@ -73,6 +80,11 @@ impl Frame {
 }
 
 impl Frame {
+  // Untagged frame
+  pub fn new(dst_mac: MacAddr6, src_mac: MacAddr6, ether_type: u16, data: Vec<u8>) -> Frame {
+    Frame{dst_mac, src_mac, tags: VecDeque::new(), ether_type, data}
+  }
+
   pub fn to_bytes(&self) -> Vec<u8> {
     let mut bytes = Vec::new();
     bytes.extend(self.dst_mac.as_bytes());
@@ -94,18 +106,38 @@ impl Frame {
       0x0800 => "IPv4",
       0x0806 => "ARP",
       0x86dd => "IPv6",
-      0x88a8 => "QinQ",
+      QINQ_TPID => "QinQ",
       _ => "UNKNOWN",
     }
   }
 
+  // Size of the frame on the wire
+  pub fn len(&self) -> usize {
+    14 + 4 * self.tags.len() + self.data.len()
+  }
+
+  pub fn get_ether_type(&self) -> u16 {
+    self.ether_type
+  }
+
+  // Payload following the ether type
+  pub fn get_data(&self) -> &[u8] {
+    &self.data
+  }
+
   pub fn is_broadcast(&self) -> bool {
     self.dst_mac.is_broadcast()
   }
 
-  pub fn tag(&mut self, vlan: u16) {
+  pub fn tag(&mut self, vlan: u16, tpid: u16) {
     debug_assert!(vlan < 4096);
-    self.tags.push_front(Tag::build(0, false, vlan));
+    self.tags.push_front(Tag::build(tpid, 0, false, vlan));
+  }
+
+  // Rewrite TPID of outermost tag
+  pub fn set_tpid(&mut self, tpid: u16) {
+    debug_assert!(!self.tags.is_empty());
+    self.tags[0].tpid = tpid;
   }
 
   pub fn untag(&mut self) {
@@ -3,11 +3,12 @@ use std::collections::{HashMap, HashSet};
 use std::ffi::CString;
 use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, BorrowedFd};
 use std::ptr;
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, SystemTime};
 use arc_swap::ArcSwap;
 use crossbeam_channel::Sender;
+use macaddr::MacAddr6;
 
 use libc;
 use libc::{
@@ -33,50 +34,148 @@ use libc::{
   TP_STATUS_VLAN_VALID,
 };
 
-use super::frame::Frame;
+use super::classify::VlanClassifiers;
+use super::frame::{Frame, DOT1Q_TPID, QINQ_TPID};
+use crate::lag::LoadBalance;
+use crate::stp::PortState;
+use crate::vlan::format_vlan_list;
 
 pub const DEFAULT_VLAN : u16 = 1;
 
 pub enum IntfCmd {
   Shutdown,
   NoShutdown,
+  ErrDisable(ErrDisableReason),
   PortModeAccess,
   PortModeVlanTunnel,
   PortModeVlanTunnelSetVlan(u16),
+  PortVlanTunnelMap(Vec<u16>, u16),
+  PortVlanTunnelUnmap(Vec<u16>),
+  PortVlanTunnelDropUnmatched(bool),
   PortModeMonitoring(String),
   PortModeTrunk,
+  PortModeHybrid,
   PortAccessVlan(u16),
   PortTrunkAddVlans(Vec<u16>),
   PortTrunkRemoveVlans(Vec<u16>),
+  PortTrunkSetVlans(Vec<u16>),
+  PortTrunkNativeVlan(Option<u16>),
+  PortHybridPvid(u16),
+  PortHybridAddVlans(Vec<u16>, bool), // vlans, untagged
+  PortHybridRemoveVlans(Vec<u16>),
   PortAddVlanTranslation(u16, u16),
   PortRemoveVlanTranslation(u16, u16),
   PortRemoveAllVlanTranslations,
+  PortAddIngressVlanTranslation(u16, u16),
+  PortRemoveIngressVlanTranslation(u16),
+  PortAddEgressVlanTranslation(u16, u16),
+  PortRemoveEgressVlanTranslation(u16),
+  PortDot1qEthertype(u16),
+  PortAddMacVlanClassifier(MacAddr6, MacAddr6, u16), // mac, mask, vlan
+  PortRemoveMacVlanClassifier(MacAddr6, MacAddr6),
+  PortAddProtocolVlanClassifier(u16, u16), // ether type, vlan
+  PortRemoveProtocolVlanClassifier(u16),
+  PortPrivateVlanHost(u16, u16), // primary, secondary
+  PortPrivateVlanPromiscuous(u16, Vec<u16>), // primary, secondaries
+  PortSecurity,
+  PortSecurityMaximum(usize),
+  PortSecurityVlanMaximum(u16, usize),
+  PortSecurityViolation(ViolationAction),
+  PortSecuritySticky(bool),
+  NoPortSecurity,
 }
 
 #[derive(Debug,Clone)]
 pub enum PortMode {
   Access { vlan: u16 },
-  VlanTunnel { service_vlan: u16 },
-  Trunk { vlans: HashSet<u16>, vlan_translations: (HashMap<u16, u16>, HashMap<u16,u16>)},
+  // C-vlans not in cvlan_map go in service_vlan unless drop_unmatched is set
+  VlanTunnel { service_vlan: u16, cvlan_map: HashMap<u16, u16>, drop_unmatched: bool },
+  Trunk { vlans: HashSet<u16>, native_vlan: Option<u16>, vlan_translations: (HashMap<u16, u16>, HashMap<u16,u16>)},
+  Hybrid { pvid: u16, tagged: HashSet<u16>, untagged: HashSet<u16> },
+  // Untagged frames are classified in secondary vlan, frames of primary and
+  // secondary vlans are sent untagged
+  PrivateVlanHost { primary: u16, secondary: u16 },
+  // Untagged frames are classified in primary vlan, frames of primary and
+  // mapped secondary vlans are sent untagged
+  PrivateVlanPromiscuous { primary: u16, secondaries: HashSet<u16> },
   Monitoring(String),
 }
 
+impl PortMode {
+  // Vlan untagged frames are classified in, None on monitoring ports
+  pub fn pvid(&self) -> Option<u16> {
+    match self {
+      PortMode::Access{vlan} => Some(*vlan),
+      PortMode::VlanTunnel{service_vlan, ..} => Some(*service_vlan),
+      PortMode::Trunk{native_vlan, ..} => *native_vlan,
+      PortMode::Hybrid{pvid, ..} => Some(*pvid),
+      PortMode::PrivateVlanHost{secondary, ..} => Some(*secondary),
+      PortMode::PrivateVlanPromiscuous{primary, ..} => Some(*primary),
+      PortMode::Monitoring(_) => None,
+    }
+  }
+}
+
+// Cause of a port shut down by the switch itself, cleared by no shutdown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrDisableReason {
+  BpduGuard,
+  PortSecurity,
+  MacFlap,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViolationAction {
+  Protect,  // drop frames from unknown sources
+  Restrict, // drop and count
+  Shutdown, // count and shut the port down
+}
+
+// Link aggregation role of an interface, maintained by the LACP agent
+#[derive(Debug, Clone)]
+pub enum LagState<'a> {
+  Individual,
+  // Channel group member, it switches frames as its port-channel while distributing
+  Member { aggregate: Weak<InterfaceView<'a>>, distributing: bool },
+  // Port-channel, egress frames are spread over distributing members
+  Aggregate { members: Vec<Arc<InterfaceView<'a>>>, load_balance: LoadBalance },
+}
+
+#[derive(Debug, Clone)]
+pub struct PortSecurity {
+  pub maximum: usize,
+  pub vlan_maximum: HashMap<u16, usize>,
+  pub violation: ViolationAction,
+  pub sticky: bool,
+}
+
 #[derive(Clone, Debug)]
 pub struct InterfaceRoData<'a> {
   //TODO move fd out of here and have one egr fd for each sender thread intf
   fd: Option<BorrowedFd<'a>>,
   mode: PortMode,
+  port_security: Option<PortSecurity>,
+  tpid: u16, // outer tag ether type
+  vlan_classifiers: VlanClassifiers, // access ports only
+  err_disabled: Option<(ErrDisableReason, SystemTime)>,
+  shutdown: bool,
 }
 
 #[derive(Debug)]
 pub struct InterfaceView<'a> {
   pub name: String,
+  pub mac: MacAddr6,
   tx: Sender<IntfCmd>,
   in_pkts: AtomicU64,
   out_pkts: AtomicU64,
   in_bytes: AtomicU64,
   out_bytes: AtomicU64,
+  security_violations: AtomicU64,
+  mac_moves: AtomicU64,
   debug_mode: AtomicBool,
+  tag_native: AtomicBool, // keep native vlan tagged on trunks
+  stp_states: Box<[AtomicU8]>, // spanning tree port state per vlan
+  lag: ArcSwap<LagState<'a>>,
   intf_ro_data: ArcSwap<InterfaceRoData<'a>>,
 }
 
@@ -87,19 +186,45 @@ pub struct Interface<'a> {
   pub view: Arc<InterfaceView<'a>>,
 }
 
-impl Interface<'_> {
-  pub fn init(name: &str, tx: Sender<IntfCmd>) -> io::Result<Interface> {
+impl<'a> Interface<'a> {
+  pub fn init(name: &str, tx: Sender<IntfCmd>) -> io::Result<Interface<'a>> {
     let if_index = get_if_index(name)?;
-    let intf_view = InterfaceView{ name: name.to_string(), tx: tx,
+    Ok(Interface::build(name, if_index, get_if_mac(name), tx))
+  }
+
+  // Port-channel, it has no socket of its own and is up while a member distributes
+  pub fn init_aggregate(name: &str, mac: MacAddr6, tx: Sender<IntfCmd>) -> Interface<'a> {
+    let intf = Interface::build(name, 0, mac, tx);
+    intf.view.set_lag_state(LagState::Aggregate{members: Vec::new(), load_balance: LoadBalance::SrcDstMac});
+    let mut intf_ro_data = intf.view.intf_ro_data.load().as_ref().clone();
+    intf_ro_data.shutdown = false;
+    intf.view.intf_ro_data.store(Arc::new(intf_ro_data));
+    intf
+  }
+
+  fn build(name: &str, if_index: u32, mac: MacAddr6, tx: Sender<IntfCmd>) -> Interface<'a> {
+    let intf_view = InterfaceView{ name: name.to_string(), mac, tx: tx,
       in_pkts: AtomicU64::new(0), out_pkts: AtomicU64::new(0),
       in_bytes: AtomicU64::new(0), out_bytes: AtomicU64::new(0),
-      debug_mode: AtomicBool::new(false),
-      intf_ro_data: ArcSwap::from_pointee(InterfaceRoData{ fd: None, mode: PortMode::Access{vlan: 1 }})
+      security_violations: AtomicU64::new(0), mac_moves: AtomicU64::new(0),
+      debug_mode: AtomicBool::new(false), tag_native: AtomicBool::new(false),
+      stp_states: (0..4096).map(|_| AtomicU8::new(PortState::Forwarding as u8)).collect(),
+      lag: ArcSwap::from_pointee(LagState::Individual),
+      intf_ro_data: ArcSwap::from_pointee(InterfaceRoData{ fd: None, mode: PortMode::Access{vlan: 1 },
+        port_security: None, tpid: DOT1Q_TPID,
+        vlan_classifiers: VlanClassifiers::default(), err_disabled: None, shutdown: true})
     };
-    Ok(Interface{name: name.to_string(), if_index: if_index, fd: None, view: Arc::new(intf_view)})
+    Interface{name: name.to_string(), if_index: if_index, fd: None, view: Arc::new(intf_view)}
   }
 
   pub fn open(&mut self) -> io::Result<()> {
+    if self.view.is_aggregate() {
+      let mut intf_ro_data = self.view.intf_ro_data.load().as_ref().clone();
+      intf_ro_data.shutdown = false;
+      intf_ro_data.err_disabled = None;
+      self.view.intf_ro_data.store(Arc::new(intf_ro_data));
+      return Ok(())
+    }
     let fd = unsafe { socket(AF_PACKET, SOCK_RAW, ETH_P_ALL.to_be()) };
     if fd < 0 {
       return Err(io::Error::last_os_error());
@@ -131,6 +256,8 @@ impl Interface<'_> {
     self.fd = unsafe { Some(OwnedFd::from_raw_fd(fd)) };
     let mut intf_ro_data = self.view.intf_ro_data.load().as_ref().clone();
     intf_ro_data.fd = unsafe { Some(BorrowedFd::borrow_raw(fd)) };
+    intf_ro_data.err_disabled = None;
+    intf_ro_data.shutdown = false;
     self.view.intf_ro_data.store(Arc::new(intf_ro_data));
     return Ok(())
   }
@@ -138,10 +265,18 @@ impl Interface<'_> {
   pub fn close(&mut self) {
     let mut intf_ro_data = self.view.intf_ro_data.load().as_ref().clone();
     intf_ro_data.fd = None;
+    intf_ro_data.shutdown = true;
     self.view.intf_ro_data.store(Arc::new(intf_ro_data));
     self.fd = None;
   }
 
+  pub fn err_disable(&mut self, reason: ErrDisableReason) {
+    self.close();
+    let mut intf_ro_data = self.view.intf_ro_data.load().as_ref().clone();
+    intf_ro_data.err_disabled = Some((reason, SystemTime::now()));
+    self.view.intf_ro_data.store(Arc::new(intf_ro_data));
+  }
+
   pub fn receive(&self) -> io::Result<Option<Frame>> {
     // TODO handle frame bigger than buffer
     let mut buf : Vec<u8> = vec![0; 4096];
@@ -187,7 +322,9 @@ impl Interface<'_> {
         }
       }
 
-      let frame = Frame::parse(&buf, n as usize, aux_data);
+      // 802.1ad outer tags are recognized whatever the configured ether type
+      let tpid = self.view.intf_ro_data.load().tpid;
+      let frame = Frame::parse(&buf, n as usize, aux_data, &[tpid, QINQ_TPID, DOT1Q_TPID]);
 
       self.view.in_pkts.fetch_add(1, Ordering::Relaxed);
       self.view.in_bytes.fetch_add(n as u64, Ordering::Relaxed);
@@ -200,41 +337,6 @@ impl Interface<'_> {
     }
   }
 
-  pub fn ing_process_frame(&self, mut frame: Frame) -> Option<Frame> {
-    match self.view.intf_ro_data.load().mode {
-      PortMode::Access{vlan} => {
-        if frame.get_vlan() != 0 {
-          if self.view.debug_mode.load(Ordering::Relaxed) {
-            println!("Dropping tagged frame ingressing on access port");
-          }
-          return None; // Drop tagged frame
-        }
-        frame.tag(vlan);
-      },
-      PortMode::Trunk{ref vlans, ref vlan_translations} => {
-        let vlan = frame.get_vlan();
-        if vlan == 0 {
-          if self.view.debug_mode.load(Ordering::Relaxed) {
-            println!("Dropping untagged frame ingressing on trunk port");
-          }
-          return None; // Drop untagged & bad vlan frame
-        } else if let Some(new_vlan) = vlan_translations.0.get(&vlan) {
-          frame.update_vlan(*new_vlan)
-        } else if !vlans.contains(&vlan) {
-          if self.view.debug_mode.load(Ordering::Relaxed) {
-            println!("Dropping frame taggued {} ingressing on trunk port allowing {:?}", vlan, vlans);
-          }
-          return None; // Drop untagged & bad vlan frame
-        }
-      }
-      PortMode::VlanTunnel{service_vlan} => {
-        frame.tag(service_vlan);
-      }
-      PortMode::Monitoring(_) => return None, // Drop ingress on monitoring ports
-    }
-    Some(frame)
-  }
-
   pub fn is_up(&self) -> bool {
     self.fd.is_some() 
   }
@@ -262,16 +364,114 @@ impl Interface<'_> {
   pub fn set_port_mode_vlan_tunnel(&self, vlan: u16) {
     debug_assert!(vlan > 0 && vlan < 4096);
     let mut intf_ro_data = self.view.intf_ro_data.load().as_ref().clone();
-    intf_ro_data.mode = PortMode::VlanTunnel{service_vlan: vlan};
+    intf_ro_data.mode = PortMode::VlanTunnel{service_vlan: vlan, cvlan_map: HashMap::new(), drop_unmatched: false};
     self.view.intf_ro_data.store(Arc::new(intf_ro_data));
   }
 
+  // Default s-vlan of unmapped c-vlans, c-vlan mappings are kept
+  pub fn set_vlan_tunnel_service_vlan(&self, vlan: u16) {
+    debug_assert!(vlan > 0 && vlan < 4096);
+    self.update_vlan_tunnel(|service_vlan, _, _| *service_vlan = vlan);
+  }
+
+  pub fn map_vlan_tunnel_cvlans(&self, cvlans: &[u16], svlan: u16) {
+    debug_assert!(cvlans.iter().chain([svlan].iter()).all(| x | *x > 0 && *x < 4096));
+    self.update_vlan_tunnel(|_, cvlan_map, _| cvlan_map.extend(cvlans.iter().map(|cvlan| (*cvlan, svlan))));
+  }
+
+  pub fn unmap_vlan_tunnel_cvlans(&self, cvlans: &[u16]) {
+    self.update_vlan_tunnel(|_, cvlan_map, _| cvlan_map.retain(|cvlan, _| !cvlans.contains(cvlan)));
+  }
+
+  pub fn set_vlan_tunnel_drop_unmatched(&self, drop: bool) {
+    self.update_vlan_tunnel(|_, _, drop_unmatched| *drop_unmatched = drop);
+  }
+
+  fn update_vlan_tunnel<F: FnOnce(&mut u16, &mut HashMap<u16, u16>, &mut bool)>(&self, update: F) {
+    let mut intf_ro_data = self.view.intf_ro_data.load().as_ref().clone();
+    if let PortMode::VlanTunnel{ref mut service_vlan, ref mut cvlan_map, ref mut drop_unmatched} = intf_ro_data.mode  {
+      update(service_vlan, cvlan_map, drop_unmatched);
+      self.view.intf_ro_data.store(Arc::new(intf_ro_data));
+    } else {
+      debug_assert!(false);
+    }
+  }
+
   pub fn set_port_mode_trunk_vlan(&self) {
     let mut intf_ro_data = self.view.intf_ro_data.load().as_ref().clone();
-    intf_ro_data.mode = PortMode::Trunk{vlans: HashSet::new(), vlan_translations: (HashMap::new(), HashMap::new())};
+    intf_ro_data.mode = PortMode::Trunk{vlans: HashSet::new(), native_vlan: None, vlan_translations: (HashMap::new(), HashMap::new())};
     self.view.intf_ro_data.store(Arc::new(intf_ro_data));
   }
 
+  pub fn set_port_mode_private_vlan_host(&self, primary: u16, secondary: u16) {
+    debug_assert!(vec![primary, secondary].into_iter().all(| x | x > 0 && x < 4096));
+    let mut intf_ro_data = self.view.intf_ro_data.load().as_ref().clone();
+    intf_ro_data.mode = PortMode::PrivateVlanHost{primary, secondary};
+    self.view.intf_ro_data.store(Arc::new(intf_ro_data));
+  }
+
+  pub fn set_port_mode_private_vlan_promiscuous(&self, primary: u16, secondaries: &[u16]) {
+    debug_assert!(secondaries.iter().chain([primary].iter()).all(| x | *x > 0 && *x < 4096));
+    let mut intf_ro_data = self.view.intf_ro_data.load().as_ref().clone();
+    intf_ro_data.mode = PortMode::PrivateVlanPromiscuous{primary, secondaries: secondaries.iter().copied().collect()};
+    self.view.intf_ro_data.store(Arc::new(intf_ro_data));
+  }
+
+  // Untagged frames are classified in pvid which is sent untagged
+  pub fn set_port_mode_hybrid(&self) {
+    let mut intf_ro_data = self.view.intf_ro_data.load().as_ref().clone();
+    intf_ro_data.mode = PortMode::Hybrid{pvid: DEFAULT_VLAN, tagged: HashSet::new(), untagged: HashSet::from([DEFAULT_VLAN])};
+    self.view.intf_ro_data.store(Arc::new(intf_ro_data));
+  }
+
+  pub fn set_hybrid_pvid(&self, vlan: u16) {
+    debug_assert!(vlan > 0 && vlan < 4096);
+    self.update_hybrid_vlans(|pvid, _, _| *pvid = vlan);
+  }
+
+  // Vlans are moved from the other set if already present
+  pub fn add_hybrid_vlans(&self, vlans: &[u16], untagged_vlans: bool) {
+    debug_assert!(vlans.iter().all(| x | *x > 0 && *x < 4096));
+    self.update_hybrid_vlans(|_, tagged, untagged| {
+      let (add, remove) = if untagged_vlans { (untagged, tagged) } else { (tagged, untagged) };
+      for vlan in vlans {
+        remove.remove(vlan);
+        add.insert(*vlan);
+      }
+    });
+  }
+
+  pub fn remove_hybrid_vlans(&self, vlans: &[u16]) {
+    self.update_hybrid_vlans(|_, tagged, untagged| {
+      for vlan in vlans {
+        tagged.remove(vlan);
+        untagged.remove(vlan);
+      }
+    });
+  }
+
+  fn update_hybrid_vlans<F: FnOnce(&mut u16, &mut HashSet<u16>, &mut HashSet<u16>)>(&self, update: F) {
+    let mut intf_ro_data = self.view.intf_ro_data.load().as_ref().clone();
+    if let PortMode::Hybrid{ref mut pvid, ref mut tagged, ref mut untagged} = intf_ro_data.mode  {
+      update(pvid, tagged, untagged);
+      self.view.intf_ro_data.store(Arc::new(intf_ro_data));
+    } else {
+      debug_assert!(false);
+    }
+  }
+
+  // Vlan of untagged frames on trunk, None drops them
+  pub fn set_trunk_native_vlan(&self, vlan: Option<u16>) {
+    debug_assert!(vlan.is_none_or(|vlan| vlan > 0 && vlan < 4096));
+    let mut intf_ro_data = self.view.intf_ro_data.load().as_ref().clone();
+    if let PortMode::Trunk{ref mut native_vlan, ..} = intf_ro_data.mode  {
+      *native_vlan = vlan;
+      self.view.intf_ro_data.store(Arc::new(intf_ro_data));
+    } else {
+      debug_assert!(false);
+    }
+  }
+
   pub fn add_trunk_allowed_vlan(&self, vlans: &Vec<u16>) {
     debug_assert!(vlans.into_iter().all(| x | *x > 0 && *x < 4096));
     let mut intf_ro_data = self.view.intf_ro_data.load().as_ref().clone();
@@ -285,6 +485,17 @@ impl Interface<'_> {
     }
   }
 
+  pub fn set_trunk_allowed_vlan(&self, vlans: &[u16]) {
+    debug_assert!(vlans.iter().all(| x | *x > 0 && *x < 4096));
+    let mut intf_ro_data = self.view.intf_ro_data.load().as_ref().clone();
+    if let PortMode::Trunk{vlans: ref mut allowed_vlans, ..} = intf_ro_data.mode  {
+      *allowed_vlans = vlans.iter().copied().collect();
+      self.view.intf_ro_data.store(Arc::new(intf_ro_data));
+    } else {
+      debug_assert!(false);
+    }
+  }
+
   pub fn remove_trunk_allowed_vlan(&self, vlans: &Vec<u16>) {
     debug_assert!(vlans.into_iter().all(| x | *x > 0 && *x < 4096));
     let mut intf_ro_data = self.view.intf_ro_data.load().as_ref().clone();
@@ -322,6 +533,63 @@ impl Interface<'_> {
     }
   }
 
+  // Translate in_vlan tagged frames to new_vlan on ingress only, several vlans
+  // may be translated to the same one
+  pub fn add_ingress_vlan_translation(&self, in_vlan: u16, new_vlan: u16) {
+    debug_assert!(vec![in_vlan, new_vlan].into_iter().all(| x | x > 0 && x < 4096));
+    self.update_vlan_translations(|(ingress, _)| _ = ingress.insert(in_vlan, new_vlan));
+  }
+
+  pub fn remove_ingress_vlan_translation(&self, in_vlan: u16) {
+    self.update_vlan_translations(|(ingress, _)| _ = ingress.remove(&in_vlan));
+  }
+
+  // Translate vlan frames to new_vlan on egress only
+  pub fn add_egress_vlan_translation(&self, vlan: u16, new_vlan: u16) {
+    debug_assert!(vec![vlan, new_vlan].into_iter().all(| x | x > 0 && x < 4096));
+    self.update_vlan_translations(|(_, egress)| _ = egress.insert(vlan, new_vlan));
+  }
+
+  pub fn remove_egress_vlan_translation(&self, vlan: u16) {
+    self.update_vlan_translations(|(_, egress)| _ = egress.remove(&vlan));
+  }
+
+  fn update_vlan_translations<F: FnOnce(&mut (HashMap<u16, u16>, HashMap<u16, u16>))>(&self, update: F) {
+    let mut intf_ro_data = self.view.intf_ro_data.load().as_ref().clone();
+    if let PortMode::Trunk{ref mut vlan_translations, ..} = intf_ro_data.mode  {
+      update(vlan_translations);
+      self.view.intf_ro_data.store(Arc::new(intf_ro_data));
+    } else {
+      debug_assert!(false);
+    }
+  }
+
+  // Ether type of the outer tag on ingress and egress
+  pub fn set_dot1q_ethertype(&self, tpid: u16) {
+    let mut intf_ro_data = self.view.intf_ro_data.load().as_ref().clone();
+    intf_ro_data.tpid = tpid;
+    self.view.intf_ro_data.store(Arc::new(intf_ro_data));
+  }
+
+  pub fn update_vlan_classifiers<F: FnOnce(&mut VlanClassifiers)>(&self, update: F) {
+    let mut intf_ro_data = self.view.intf_ro_data.load().as_ref().clone();
+    update(&mut intf_ro_data.vlan_classifiers);
+    self.view.intf_ro_data.store(Arc::new(intf_ro_data));
+  }
+
+  // Enable port security with default settings if not already enabled
+  pub fn update_port_security<F: FnOnce(&mut PortSecurity)>(&self, update: F) {
+    let mut intf_ro_data = self.view.intf_ro_data.load().as_ref().clone();
+    update(intf_ro_data.port_security.get_or_insert_with(PortSecurity::default));
+    self.view.intf_ro_data.store(Arc::new(intf_ro_data));
+  }
+
+  pub fn disable_port_security(&self) {
+    let mut intf_ro_data = self.view.intf_ro_data.load().as_ref().clone();
+    intf_ro_data.port_security = None;
+    self.view.intf_ro_data.store(Arc::new(intf_ro_data));
+  }
+
   pub fn remove_all_vlan_translations(&self) {
     let mut intf_ro_data = self.view.intf_ro_data.load().as_ref().clone();
     if let PortMode::Trunk{ref mut vlan_translations, ..} = intf_ro_data.mode  {
@@ -334,9 +602,19 @@ impl Interface<'_> {
   }
 }
 
-impl InterfaceView<'_> {
+impl<'a> InterfaceView<'a> {
 
   pub fn send(&self, frame: Frame) -> io::Result<()> {
+    if let LagState::Aggregate{members, load_balance} = self.lag.load().as_ref() {
+      if members.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::BrokenPipe, "no member distributing"));
+      }
+      let len = frame.len() as u64;
+      members[(load_balance.hash(&frame) % members.len() as u64) as usize].send(frame)?;
+      self.out_pkts.fetch_add(1, Ordering::Relaxed);
+      self.out_bytes.fetch_add(len, Ordering::Relaxed);
+      return Ok(())
+    }
     if let Some(fd) = &self.intf_ro_data.load().fd {
 
       let data = frame.to_bytes();
@@ -360,21 +638,110 @@ impl InterfaceView<'_> {
     }
   }
 
+  pub fn ing_process_frame(&self, mut frame: Frame) -> Option<Frame> {
+    let ro_data = self.intf_ro_data.load();
+    match ro_data.mode {
+      PortMode::Access{vlan} => {
+        if frame.get_vlan() != 0 {
+          if self.debug_mode.load(Ordering::Relaxed) {
+            println!("Dropping tagged frame ingressing on access port");
+          }
+          return None; // Drop tagged frame
+        }
+        let vlan = ro_data.vlan_classifiers.classify(&frame).unwrap_or(vlan);
+        frame.tag(vlan, DOT1Q_TPID);
+      },
+      PortMode::Trunk{ref vlans, native_vlan, ref vlan_translations} => {
+        let vlan = frame.get_vlan();
+        if vlan == 0 {
+          match native_vlan {
+            Some(native_vlan) if !self.tag_native.load(Ordering::Relaxed) => frame.tag(native_vlan, DOT1Q_TPID),
+            _ => {
+              if self.debug_mode.load(Ordering::Relaxed) {
+                println!("Dropping untagged frame ingressing on trunk port");
+              }
+              return None; // Drop untagged & bad vlan frame
+            }
+          }
+        } else if let Some(new_vlan) = vlan_translations.0.get(&vlan) {
+          frame.update_vlan(*new_vlan)
+        } else if !vlans.contains(&vlan) && native_vlan != Some(vlan) {
+          if self.debug_mode.load(Ordering::Relaxed) {
+            println!("Dropping frame taggued {} ingressing on trunk port allowing {:?}", vlan, vlans);
+          }
+          return None; // Drop untagged & bad vlan frame
+        }
+      }
+      PortMode::Hybrid{pvid, ref tagged, ref untagged} => {
+        let vlan = frame.get_vlan();
+        if vlan == 0 {
+          frame.tag(pvid, DOT1Q_TPID);
+        } else if !tagged.contains(&vlan) && !untagged.contains(&vlan) {
+          if self.debug_mode.load(Ordering::Relaxed) {
+            println!("Dropping frame taggued {} ingressing on hybrid port", vlan);
+          }
+          return None;
+        }
+      }
+      PortMode::PrivateVlanHost{secondary: vlan, ..} | PortMode::PrivateVlanPromiscuous{primary: vlan, ..} => {
+        if frame.get_vlan() != 0 {
+          if self.debug_mode.load(Ordering::Relaxed) {
+            println!("Dropping tagged frame ingressing on private vlan port");
+          }
+          return None;
+        }
+        frame.tag(vlan, DOT1Q_TPID);
+      }
+      PortMode::VlanTunnel{service_vlan, ref cvlan_map, drop_unmatched} => {
+        match cvlan_map.get(&frame.get_vlan()) {
+          Some(svlan) => frame.tag(*svlan, DOT1Q_TPID),
+          None if !drop_unmatched => frame.tag(service_vlan, DOT1Q_TPID),
+          None => {
+            if self.debug_mode.load(Ordering::Relaxed) {
+              println!("Dropping frame with unmapped c-vlan {} ingressing on dot1q-tunnel port", frame.get_vlan());
+            }
+            return None;
+          }
+        }
+      }
+      PortMode::Monitoring(_) => return None, // Drop ingress on monitoring ports
+    }
+    Some(frame)
+  }
+
   pub fn egr_process_frame(&self, mut frame: Frame) -> Frame {
-     match self.intf_ro_data.load().mode {
+     let ro_data = self.intf_ro_data.load();
+     match ro_data.mode {
       PortMode::Access{vlan} => {
-       debug_assert!(vlan == frame.get_vlan()); //vlan should be checked before
+       debug_assert!(vlan == frame.get_vlan() || ro_data.vlan_classifiers.contains_vlan(frame.get_vlan())); //vlan should be checked before
        frame.untag()
       }
-      PortMode::Trunk{ref vlans, ref vlan_translations} => {
-        debug_assert!(vlans.contains(&frame.get_vlan()) || vlan_translations.1.contains_key(&frame.get_vlan()));
+      PortMode::Trunk{ref vlans, native_vlan, ref vlan_translations} => {
+        debug_assert!(vlans.contains(&frame.get_vlan()) || native_vlan == Some(frame.get_vlan())
+          || vlan_translations.1.contains_key(&frame.get_vlan()));
         if let Some(new_vlan) = vlan_translations.1.get(&frame.get_vlan()) {
           frame.update_vlan(*new_vlan)
+        } else if native_vlan == Some(frame.get_vlan()) && !self.tag_native.load(Ordering::Relaxed) {
+          frame.untag();
+          return frame
         }
+        frame.set_tpid(ro_data.tpid)
       },
-      PortMode::VlanTunnel{service_vlan} => {
-       debug_assert!(service_vlan == frame.get_vlan()); //vlan should be checked before
-       frame.untag()
+      PortMode::Hybrid{ref tagged, ref untagged, ..} => {
+        debug_assert!(tagged.contains(&frame.get_vlan()) || untagged.contains(&frame.get_vlan()));
+        if untagged.contains(&frame.get_vlan()) {
+          frame.untag()
+        } else {
+          frame.set_tpid(ro_data.tpid)
+        }
+      }
+      PortMode::PrivateVlanHost{..} | PortMode::PrivateVlanPromiscuous{..} => {
+        debug_assert!(self.allows_vlan_out(frame.get_vlan()));
+        frame.untag()
+      }
+      PortMode::VlanTunnel{service_vlan, ref cvlan_map, ..} => {
+       debug_assert!(service_vlan == frame.get_vlan() || cvlan_map.values().any(|svlan| *svlan == frame.get_vlan())); //vlan should be checked before
+       frame.untag() // strip s-tag
       }
       PortMode::Monitoring(_) => ()
      }
@@ -382,7 +749,40 @@ impl InterfaceView<'_> {
   }
 
   pub fn is_up(&self) -> bool {
-    self.intf_ro_data.load().fd.is_some() 
+    let ro_data = self.intf_ro_data.load();
+    match self.lag.load().as_ref() {
+      LagState::Aggregate{members, ..} => !ro_data.shutdown && !members.is_empty(),
+      _ => ro_data.fd.is_some(),
+    }
+  }
+
+  pub fn get_lag_state(&self) -> Arc<LagState<'a>> {
+    self.lag.load_full()
+  }
+
+  pub fn set_lag_state(&self, state: LagState<'a>) {
+    self.lag.store(Arc::new(state));
+  }
+
+  pub fn is_aggregate(&self) -> bool {
+    matches!(self.lag.load().as_ref(), LagState::Aggregate{..})
+  }
+
+  pub fn is_channel_member(&self) -> bool {
+    matches!(self.lag.load().as_ref(), LagState::Member{..})
+  }
+
+  // Port-channel frames received on this member belong to, while distributing
+  pub fn aggregate(&self) -> Option<Arc<InterfaceView<'a>>> {
+    match self.lag.load().as_ref() {
+      LagState::Member{aggregate, distributing: true} => aggregate.upgrade(),
+      _ => None,
+    }
+  }
+
+  pub fn add_in_frame(&self, len: usize) {
+    self.in_pkts.fetch_add(1, Ordering::Relaxed);
+    self.in_bytes.fetch_add(len as u64, Ordering::Relaxed);
   }
 
   pub fn get_port_mode(&self) -> PortMode {
@@ -393,11 +793,49 @@ impl InterfaceView<'_> {
     matches!( self.intf_ro_data.load().mode, PortMode::Monitoring(_))
   }
 
+  pub fn is_private_vlan_host(&self) -> bool {
+    matches!(self.intf_ro_data.load().mode, PortMode::PrivateVlanHost{..})
+  }
+
+  pub fn is_debug_mode(&self) -> bool {
+    self.debug_mode.load(Ordering::Relaxed)
+  }
+
+  pub fn set_tag_native(&self, value: bool) {
+    self.tag_native.store(value, Ordering::Relaxed);
+  }
+
+  pub fn get_stp_state(&self, vlan: u16) -> PortState {
+    match self.stp_states[vlan as usize].load(Ordering::Relaxed) {
+      state if state == PortState::Discarding as u8 => PortState::Discarding,
+      state if state == PortState::Learning as u8 => PortState::Learning,
+      _ => PortState::Forwarding,
+    }
+  }
+
+  pub fn set_stp_state(&self, vlan: u16, state: PortState) {
+    self.stp_states[vlan as usize].store(state as u8, Ordering::Relaxed);
+  }
+
   pub fn set_debug_mode(&self, value: bool) {
     self.debug_mode.store(value, Ordering::Relaxed);
   }
 
+  pub fn get_port_security(&self) -> Option<PortSecurity> {
+    self.intf_ro_data.load().port_security.clone()
+  }
+
+  pub fn add_security_violation(&self) -> u64 {
+    self.security_violations.fetch_add(1, Ordering::Relaxed) + 1
+  }
+
+  pub fn add_mac_move(&self) {
+    self.mac_moves.fetch_add(1, Ordering::Relaxed);
+  }
+
   pub fn reset_counters(&self) {
+    self.security_violations.store(0, Ordering::Relaxed);
+    self.mac_moves.store(0, Ordering::Relaxed);
     self.in_pkts.store(0, Ordering::Relaxed);
     self.out_pkts.store(0, Ordering::Relaxed);
     self.in_bytes.store(0, Ordering::Relaxed);
@@ -411,22 +849,56 @@ impl InterfaceView<'_> {
   }
 
   pub fn allows_vlan_in(&self, vlan: u16) -> bool {
-    match &self.intf_ro_data.load().mode {
-      PortMode::Access{vlan: port_vlan} => *port_vlan == vlan,
-      PortMode::Trunk{vlans, vlan_translations} => vlans.contains(&vlan) || vlan_translations.0.contains_key(&vlan),
-      PortMode::VlanTunnel{service_vlan} => *service_vlan == vlan,
+    let ro_data = self.intf_ro_data.load();
+    match &ro_data.mode {
+      PortMode::Access{vlan: port_vlan} => *port_vlan == vlan || ro_data.vlan_classifiers.contains_vlan(vlan),
+      PortMode::Trunk{vlans, native_vlan, vlan_translations} => vlans.contains(&vlan) || *native_vlan == Some(vlan)
+        || vlan_translations.0.contains_key(&vlan),
+      PortMode::Hybrid{tagged, untagged, ..} => tagged.contains(&vlan) || untagged.contains(&vlan),
+      PortMode::PrivateVlanHost{primary, secondary} => *primary == vlan || *secondary == vlan,
+      PortMode::PrivateVlanPromiscuous{primary, secondaries} => *primary == vlan || secondaries.contains(&vlan),
+      PortMode::VlanTunnel{service_vlan, cvlan_map, drop_unmatched} => (!drop_unmatched && *service_vlan == vlan)
+        || cvlan_map.values().any(|svlan| *svlan == vlan),
       PortMode::Monitoring(_) => panic!("Unexpected path")
     }
   }
 
   pub fn allows_vlan_out(&self, vlan: u16) -> bool {
-    match &self.intf_ro_data.load().mode {
-      PortMode::Access{vlan: port_vlan} => *port_vlan == vlan,
-      PortMode::Trunk{vlans, vlan_translations} => vlans.contains(&vlan) || vlan_translations.1.contains_key(&vlan),
-      PortMode::VlanTunnel{service_vlan} => *service_vlan == vlan,
+    let ro_data = self.intf_ro_data.load();
+    match &ro_data.mode {
+      PortMode::Access{vlan: port_vlan} => *port_vlan == vlan || ro_data.vlan_classifiers.contains_vlan(vlan),
+      PortMode::Trunk{vlans, native_vlan, vlan_translations} => vlans.contains(&vlan) || *native_vlan == Some(vlan)
+        || vlan_translations.1.contains_key(&vlan),
+      PortMode::Hybrid{tagged, untagged, ..} => tagged.contains(&vlan) || untagged.contains(&vlan),
+      PortMode::PrivateVlanHost{primary, secondary} => *primary == vlan || *secondary == vlan,
+      PortMode::PrivateVlanPromiscuous{primary, secondaries} => *primary == vlan || secondaries.contains(&vlan),
+      PortMode::VlanTunnel{service_vlan, cvlan_map, drop_unmatched} => (!drop_unmatched && *service_vlan == vlan)
+        || cvlan_map.values().any(|svlan| *svlan == vlan),
       PortMode::Monitoring(_) => panic!("Unexpected path")
     }
   }
+  // Whether interface switches frames of given vlan, monitoring ports are
+  // members of none
+  pub fn is_vlan_member(&self, vlan: u16) -> bool {
+    !self.is_monitoring() && self.allows_vlan_out(vlan)
+  }
+
+}
+
+// Translation rules sorted by original vlan, e.g. 10 -> 100, 11 -> 100
+fn format_vlan_translations(translations: &HashMap<u16, u16>) -> String {
+  let mut rules: Vec<(&u16, &u16)> = translations.iter().collect();
+  rules.sort();
+  let rules: Vec<String> = rules.into_iter().map(|(vlan, new_vlan)| format!("{} -> {}", vlan, new_vlan)).collect();
+  if rules.is_empty() { "none".to_string() } else { rules.join(", ") }
+}
+
+// Hardware address of interface, nil if unknown
+fn get_if_mac(if_name: &str) -> MacAddr6 {
+  std::fs::read_to_string(format!("/sys/class/net/{}/address", if_name))
+    .ok()
+    .and_then(|mac| mac.trim().parse().ok())
+    .unwrap_or(MacAddr6::nil())
 }
 
 fn get_if_index(if_name: &str) -> io::Result<u32> {
@@ -443,12 +915,22 @@ fn get_if_index(if_name: &str) -> io::Result<u32> {
 impl fmt::Display for InterfaceView<'_> {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     let ro_data = self.intf_ro_data.load();
+    let status = match (self.is_up(), ro_data.err_disabled) {
+      (true, _) => "running".to_string(),
+      (false, Some((reason, since))) => format!("err-disabled ({}, {}s ago)", reason,
+        since.elapsed().unwrap_or_default().as_secs()),
+      (false, None) if ro_data.shutdown => "shutdown".to_string(),
+      (false, None) => "down".to_string(),
+    };
     let mut output = format!("{}\n----------\nStatus: {}\nMode: {}\n",
       self.name,
-      if let Some(_) = &ro_data.fd { "running" } else { "shutdown" },
+      status,
       match ro_data.mode {
         PortMode::Access{..} => "Access",
         PortMode::Trunk{..} => "Trunk",
+        PortMode::Hybrid{..} => "Hybrid",
+        PortMode::PrivateVlanHost{..} => "Private Vlan Host",
+        PortMode::PrivateVlanPromiscuous{..} => "Private Vlan Promiscuous",
         PortMode::VlanTunnel{..} => "Dot1q Tunnel",
         PortMode::Monitoring(_) => "Monitoring",
       }
@@ -456,29 +938,75 @@ impl fmt::Display for InterfaceView<'_> {
 
     if let PortMode::Access{vlan} = &ro_data.mode {
       output += &format!("Vlan: {}\n", vlan);
-    }
-    if let PortMode::Trunk{vlans, vlan_translations} = &ro_data.mode {
-      output += &format!("Allowed Vlans: {:?}\nVlan Translations: \n\tIngress: ", vlans);
-      for in_vlan in vlan_translations.0.keys() {
-        output += &format!("{} -> {}, ", in_vlan, vlan_translations.0[in_vlan]);
+      if !ro_data.vlan_classifiers.is_empty() {
+        output += &format!("Vlan Classifiers:\n{}", ro_data.vlan_classifiers);
       }
-      output += &format!("\n\tEgress: ");
-      for egr_vlan in vlan_translations.1.keys() {
-        output += &format!("{} -> {}, ", egr_vlan, vlan_translations.1[egr_vlan]);
+    }
+    if let PortMode::Trunk{vlans, native_vlan, vlan_translations} = &ro_data.mode {
+      output += &format!("Allowed Vlans: {}\n", format_vlan_list(vlans));
+      if let Some(native_vlan) = native_vlan {
+        output += &format!("Native Vlan: {}{}\n", native_vlan,
+          if self.tag_native.load(Ordering::Relaxed) { " (tagged)" } else { "" });
       }
-      output += &format!("\n");
+      output += "Vlan Translations:\n";
+      output += &format!("\tIngress: {}\n", format_vlan_translations(&vlan_translations.0));
+      output += &format!("\tEgress: {}\n", format_vlan_translations(&vlan_translations.1));
+    }
+    if let PortMode::Hybrid{pvid, tagged, untagged} = &ro_data.mode {
+      output += &format!("PVID: {}\nTagged Vlans: {}\nUntagged Vlans: {}\n", pvid,
+        format_vlan_list(tagged), format_vlan_list(untagged));
+    }
+    if let PortMode::PrivateVlanHost{primary, secondary} = &ro_data.mode {
+      output += &format!("Primary Vlan: {}\nSecondary Vlan: {}\n", primary, secondary);
+    }
+    if let PortMode::PrivateVlanPromiscuous{primary, secondaries} = &ro_data.mode {
+      output += &format!("Primary Vlan: {}\nSecondary Vlans: {}\n", primary, format_vlan_list(secondaries));
     }
     if let PortMode::Monitoring(target) = &ro_data.mode {
       output += &format!("Monitoring: {}\n", target);
     }
-    if let PortMode::VlanTunnel{service_vlan} = &ro_data.mode {
+    if let PortMode::VlanTunnel{service_vlan, cvlan_map, drop_unmatched} = &ro_data.mode {
       output += &format!("Vlan: {}\n", service_vlan);
+      let mut svlans: Vec<&u16> = cvlan_map.values().collect();
+      svlans.sort();
+      svlans.dedup();
+      for svlan in svlans {
+        let cvlans = cvlan_map.iter().filter(|(_, mapped)| *mapped == svlan).map(|(cvlan, _)| cvlan);
+        output += &format!("\tC-Vlans {} -> S-Vlan {}\n", format_vlan_list(cvlans), svlan);
+      }
+      output += &format!("Unmatched C-Vlans: {}\n",
+        if *drop_unmatched { "drop".to_string() } else { format!("S-Vlan {}", service_vlan) });
     }
 
+    match self.lag.load().as_ref() {
+      LagState::Aggregate{members, ..} => {
+        let members: Vec<&str> = members.iter().map(|member| member.name.as_str()).collect();
+        output += &format!("Bundled Members: {}\n", if members.is_empty() { "none".to_string() } else { members.join(", ") });
+      }
+      LagState::Member{aggregate, distributing} => {
+        output += &format!("Channel Group: {}{}\n", aggregate.upgrade().map_or(String::new(), |aggregate| aggregate.name.clone()),
+          if *distributing { " (bundled)" } else { " (not bundled)" });
+      }
+      LagState::Individual => (),
+    }
+    if let Some(port_security) = &ro_data.port_security {
+      output += &format!("Port Security: maximum {}, violation {}{}\n", port_security.maximum,
+        port_security.violation, if port_security.sticky { ", sticky" } else { "" });
+      let mut vlans: Vec<_> = port_security.vlan_maximum.keys().collect();
+      vlans.sort();
+      for vlan in vlans {
+        output += &format!("\tVlan {}: maximum {}\n", vlan, port_security.vlan_maximum[vlan]);
+      }
+      output += &format!("Security Violations: {}\n", self.security_violations.load(Ordering::Relaxed));
+    }
+    if ro_data.tpid != DOT1Q_TPID {
+      output += &format!("Dot1q Ethertype: 0x{:04x}\n", ro_data.tpid);
+    }
     output += &format!("Mode Debug: {}\n", self.debug_mode.load(Ordering::Relaxed));
     output += &format!("\nIn Pkts: {}, Out Pkts: {}\nIn bytes: {}, Out bytes: {}\n",
       self.in_pkts.load(Ordering::Relaxed), self.out_pkts.load(Ordering::Relaxed),
       self.in_bytes.load(Ordering::Relaxed), self.out_bytes.load(Ordering::Relaxed));
+    output += &format!("MAC moves: {}\n", self.mac_moves.load(Ordering::Relaxed));
     write!(f, "{}", output)
   }
 }
@@ -489,8 +1017,39 @@ impl fmt::Display for PortMode {
     match self {
       PortMode::Access{..} => "access",
       PortMode::Trunk{..} => "trunk",
+      PortMode::Hybrid{..} => "hybrid",
+      PortMode::PrivateVlanHost{..} => "private-vlan host",
+      PortMode::PrivateVlanPromiscuous{..} => "private-vlan promiscuous",
       PortMode::VlanTunnel{..} => "dot1q-tunnel",
       PortMode::Monitoring(_) => "monitoring",
     })
   }
 }
+
+impl Default for PortSecurity {
+  fn default() -> Self {
+    PortSecurity{maximum: 1, vlan_maximum: HashMap::new(), violation: ViolationAction::Shutdown, sticky: false}
+  }
+}
+
+impl fmt::Display for ErrDisableReason {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}",
+    match self {
+      ErrDisableReason::BpduGuard => "bpduguard",
+      ErrDisableReason::PortSecurity => "psecure-violation",
+      ErrDisableReason::MacFlap => "mac-flap",
+    })
+  }
+}
+
+impl fmt::Display for ViolationAction {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}",
+    match self {
+      ViolationAction::Protect => "protect",
+      ViolationAction::Restrict => "restrict",
+      ViolationAction::Shutdown => "shutdown",
+    })
+  }
+}
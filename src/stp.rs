@@ -0,0 +1,1100 @@
+use std::fmt;
+use std::io::{self, Error, ErrorKind};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use macaddr::MacAddr6;
+
+use crate::fib::Fib;
+use crate::network::frame::Frame;
+use crate::network::interface::{ErrDisableReason, InterfaceView, IntfCmd};
+use crate::vlan::format_vlan_list;
+use bpdu::{bridge_id, format_bridge_id, Bpdu, BpduRole, BpduType, MstBpdu, MstiMessage, PriorityVector,
+  FLAG_AGREEMENT, FLAG_FORWARDING, FLAG_LEARNING, FLAG_PROPOSAL, FLAG_TC, FLAG_TC_ACK};
+use mst::{MstRegion, MAX_MST_INSTANCES, MAX_MST_INSTANCE_ID};
+
+pub mod bpdu;
+pub mod mst;
+
+pub const DEFAULT_BRIDGE_PRIORITY: u16 = 32768;
+pub const DEFAULT_PORT_PRIORITY: u8 = 128;
+pub const DEFAULT_PORT_COST: u32 = 20000; // 1Gb/s
+pub const DEFAULT_HELLO_TIME: u16 = 2;
+pub const DEFAULT_MAX_AGE: u16 = 20;
+pub const DEFAULT_FORWARD_DELAY: u16 = 15;
+pub const DEFAULT_MAX_HOPS: u8 = 20;
+pub const STP_TICK: Duration = Duration::from_secs(1);
+
+const BRIDGE_MAC_MASK: u64 = 0xffff_ffff_ffff;
+const VLAN_COUNT: u16 = 4096;
+const CIST: usize = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StpVersion {
+  Stp,  // 802.1D, timer based transitions only
+  Rstp, // 802.1w, falls back to 802.1D on ports with legacy neighbours
+  Mstp, // 802.1s, one rapid spanning tree per instance inside a region
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortRole {
+  Root,
+  Designated,
+  Alternate,
+  Backup,
+  Master, // region boundary port towards the CIST root, instances follow the CIST
+  Disabled,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PortState {
+  Discarding, // no learning, no forwarding
+  Learning,   // learning, no forwarding
+  Forwarding,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortGuard {
+  None,
+  Root, // never become root port, block on superior BPDUs
+  Loop, // block instead of becoming designated when BPDUs stop
+}
+
+// Cause of a port blocked by a guard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Inconsistency {
+  Root,
+  Loop,
+}
+
+// Port information received from the designated bridge of the segment.
+// Age is in seconds for the CIST, in hops for other instances.
+#[derive(Debug, Clone, Copy)]
+struct ReceivedInfo {
+  vector: PriorityVector,
+  age: u16,
+  at: Instant,
+}
+
+struct StpPort<'a> {
+  intf: Arc<InterfaceView<'a>>,
+  number: u16,
+  rstp: bool, // neighbour speaks rstp
+  internal: bool, // neighbour belongs to our MST region
+  tc_ack: bool, // acknowledge TCN in next BPDU
+  edge: bool, // portfast configured
+  oper_edge: bool, // no BPDU received since the port came up
+  bpdu_guard: bool,
+  bpdu_filter: bool,
+  guard: PortGuard,
+}
+
+// Port of a spanning tree instance
+struct TreePort {
+  priority: u8,
+  cost: u32,
+  role: PortRole,
+  state: PortState,
+  state_since: Instant,
+  received: Option<ReceivedInfo>,
+  proposing: bool,
+  agreed: bool,
+  agree: bool, // send agreement in next BPDU
+  tc_while: Option<Instant>, // send topology change flag until
+  inconsistent: Option<Inconsistency>,
+}
+
+// Spanning tree instance, the first one is the common and internal
+// spanning tree (CIST) carrying vlans not mapped to another instance
+struct StpTree {
+  instance: u16,
+  priority: u16,
+  root: PriorityVector,
+  root_port: Option<usize>,
+  root_age: u16,
+  topology_changes: u64,
+  last_topology_change: Option<Instant>,
+  ports: Vec<TreePort>,
+}
+
+struct StpBridge<'a> {
+  version: StpVersion,
+  mac: MacAddr6,
+  hello_time: u16,
+  max_age: u16,
+  forward_delay: u16,
+  max_hops: u8,
+  region: MstRegion,
+  tcn_pending: bool, // notify root bridge of topology change
+  last_hello: Option<Instant>,
+  ports: Vec<StpPort<'a>>,
+  trees: Vec<StpTree>,
+}
+
+// Spanning tree of the switch, port states are mirrored per vlan in each
+// InterfaceView for the data plane. Disabled by default, all ports are then
+// forwarding.
+pub struct Stp<'a> {
+  enabled: AtomicBool,
+  bridge: Mutex<StpBridge<'a>>,
+  fib: Arc<Fib<'a>>,
+}
+
+impl<'a> Stp<'a> {
+  pub fn new(fib: Arc<Fib<'a>>) -> Self {
+    let bridge = StpBridge{version: StpVersion::Rstp, mac: MacAddr6::nil(),
+      hello_time: DEFAULT_HELLO_TIME, max_age: DEFAULT_MAX_AGE, forward_delay: DEFAULT_FORWARD_DELAY,
+      max_hops: DEFAULT_MAX_HOPS, region: MstRegion::default(), tcn_pending: false, last_hello: None,
+      ports: Vec::new(), trees: vec![StpTree::new(0, 0)]};
+    Stp{enabled: AtomicBool::new(false), bridge: Mutex::new(bridge), fib}
+  }
+
+  // Ports are numbered in the order they are added, bridge address is the
+  // lowest port address
+  pub fn add_port(&self, intf: Arc<InterfaceView<'a>>) {
+    let mut bridge = self.bridge.lock().unwrap();
+    if !intf.mac.is_nil() && (bridge.mac.is_nil() || intf.mac < bridge.mac) {
+      bridge.mac = intf.mac;
+    }
+    let number = bridge.ports.len() as u16 + 1;
+    bridge.ports.push(StpPort{intf, number, rstp: true, internal: false, tc_ack: false, edge: false,
+      oper_edge: false, bpdu_guard: false, bpdu_filter: false, guard: PortGuard::None});
+    for tree in bridge.trees.iter_mut() {
+      tree.ports.push(TreePort::new(PortState::Forwarding));
+    }
+  }
+
+  pub fn is_enabled(&self) -> bool {
+    self.enabled.load(Ordering::Relaxed)
+  }
+
+  pub fn set_enabled(&self, enabled: bool) {
+    let mut bridge = self.bridge.lock().unwrap();
+    if self.enabled.swap(enabled, Ordering::Relaxed) == enabled {
+      return;
+    }
+    for port in bridge.ports.iter_mut() {
+      port.rstp = true;
+      port.internal = false;
+      port.oper_edge = port.edge;
+    }
+    let state = if enabled { PortState::Discarding } else { PortState::Forwarding };
+    for t in 0..bridge.trees.len() {
+      bridge.reset_tree(t, state);
+    }
+    bridge.last_hello = None;
+    if enabled {
+      bridge.update(&self.fib);
+    }
+    bridge.push_states();
+  }
+
+  pub fn set_version(&self, version: StpVersion) {
+    let state = if self.is_enabled() { PortState::Discarding } else { PortState::Forwarding };
+    self.configure(|bridge| {
+      if bridge.version != version {
+        for t in 1..bridge.trees.len() {
+          bridge.reset_tree(t, state);
+        }
+      }
+      bridge.version = version;
+      for port in bridge.ports.iter_mut() {
+        port.rstp = true;
+        port.internal = false;
+      }
+    });
+  }
+
+  pub fn set_priority(&self, instance: u16, priority: u16) -> io::Result<()> {
+    if !priority.is_multiple_of(4096) {
+      return Err(Error::new(ErrorKind::InvalidInput, "Bridge priority must be a multiple of 4096"));
+    }
+    self.configure_tree(instance, |tree, _| tree.priority = priority)
+  }
+
+  pub fn set_timers(&self, hello_time: Option<u16>, max_age: Option<u16>, forward_delay: Option<u16>) {
+    self.configure(|bridge| {
+      bridge.hello_time = hello_time.unwrap_or(bridge.hello_time);
+      bridge.max_age = max_age.unwrap_or(bridge.max_age);
+      bridge.forward_delay = forward_delay.unwrap_or(bridge.forward_delay);
+    });
+  }
+
+  pub fn set_max_hops(&self, max_hops: u8) {
+    self.configure(|bridge| bridge.max_hops = max_hops);
+  }
+
+  pub fn set_port_cost(&self, if_name: &str, instance: u16, cost: u32) -> io::Result<()> {
+    self.configure_tree(instance, |tree, ports| {
+      let i = port_index(ports, if_name)?;
+      tree.ports[i].cost = cost;
+      Ok(())
+    })?
+  }
+
+  pub fn set_port_priority(&self, if_name: &str, instance: u16, priority: u8) -> io::Result<()> {
+    if !priority.is_multiple_of(16) {
+      return Err(Error::new(ErrorKind::InvalidInput, "Port priority must be a multiple of 16"));
+    }
+    self.configure_tree(instance, |tree, ports| {
+      let i = port_index(ports, if_name)?;
+      tree.ports[i].priority = priority;
+      Ok(())
+    })?
+  }
+
+  // Edge ports go straight to forwarding until they receive a BPDU
+  pub fn set_port_edge(&self, if_name: &str, edge: bool) -> io::Result<()> {
+    self.configure_port(if_name, |port| {
+      port.edge = edge;
+      port.oper_edge = edge;
+    })
+  }
+
+  pub fn set_port_bpdu_guard(&self, if_name: &str, bpdu_guard: bool) -> io::Result<()> {
+    self.configure_port(if_name, |port| port.bpdu_guard = bpdu_guard)
+  }
+
+  pub fn set_port_bpdu_filter(&self, if_name: &str, bpdu_filter: bool) -> io::Result<()> {
+    self.configure_port(if_name, |port| port.bpdu_filter = bpdu_filter)
+  }
+
+  pub fn set_port_guard(&self, if_name: &str, guard: PortGuard) -> io::Result<()> {
+    self.configure_port(if_name, |port| port.guard = guard)
+  }
+
+  pub fn set_mst_name(&self, name: &str) {
+    self.configure(|bridge| bridge.region.set_name(name.to_string()));
+  }
+
+  pub fn set_mst_revision(&self, revision: u16) {
+    self.configure(|bridge| bridge.region.set_revision(revision));
+  }
+
+  // Move vlans to instance, their addresses are flushed as their port states may change
+  pub fn map_mst_vlans(&self, instance: u16, vlans: &[u16]) -> io::Result<()> {
+    self.configure_tree(instance, |_, _| ())?;
+    self.configure(|bridge| bridge.region.map_vlans(instance, vlans));
+    self.flush_vlans(vlans);
+    Ok(())
+  }
+
+  // Give vlans of instance back to the CIST, all of them when vlans is None
+  pub fn unmap_mst_vlans(&self, instance: u16, vlans: Option<&[u16]>) -> io::Result<()> {
+    let vlans = {
+      let bridge = self.bridge.lock().unwrap();
+      bridge.tree_index(instance)?;
+      let mapped = bridge.region.vlans(instance);
+      match vlans {
+        Some(vlans) => mapped.into_iter().filter(|vlan| vlans.contains(vlan)).collect(),
+        None => mapped,
+      }
+    };
+    self.configure(|bridge| bridge.region.unmap_vlans(instance, Some(&vlans)));
+    self.flush_vlans(&vlans);
+    Ok(())
+  }
+
+  pub fn remove_mst_instance(&self, instance: u16) -> io::Result<()> {
+    if instance == 0 {
+      return Err(Error::new(ErrorKind::InvalidInput, "CIST cannot be removed"));
+    }
+    self.unmap_mst_vlans(instance, None)?;
+    self.configure(|bridge| {
+      if let Ok(t) = bridge.tree_index(instance) {
+        bridge.trees.remove(t);
+      }
+    });
+    Ok(())
+  }
+
+  fn flush_vlans(&self, vlans: &[u16]) {
+    let bridge = self.bridge.lock().unwrap();
+    for port in bridge.ports.iter() {
+      for vlan in vlans {
+        self.fib.remove_intf_vlan_entries(port.intf.name.clone(), *vlan);
+      }
+    }
+  }
+
+  fn configure<F: FnOnce(&mut StpBridge<'a>)>(&self, update: F) {
+    let mut bridge = self.bridge.lock().unwrap();
+    update(&mut bridge);
+    if self.is_enabled() {
+      bridge.update(&self.fib);
+    }
+    bridge.push_states();
+  }
+
+  fn configure_port<F: FnOnce(&mut StpPort<'a>)>(&self, if_name: &str, update: F) -> io::Result<()> {
+    let mut result = Ok(());
+    self.configure(|bridge| match port_index(&bridge.ports, if_name) {
+      Ok(i) => update(&mut bridge.ports[i]),
+      Err(err) => result = Err(err),
+    });
+    result
+  }
+
+  // Configure spanning tree instance, created if missing
+  fn configure_tree<T, F: FnOnce(&mut StpTree, &[StpPort]) -> T>(&self, instance: u16, update: F) -> io::Result<T> {
+    let mut bridge = self.bridge.lock().unwrap();
+    let t = match bridge.tree_index(instance) {
+      Ok(t) => t,
+      Err(_) if instance > MAX_MST_INSTANCE_ID => {
+        return Err(Error::new(ErrorKind::InvalidInput, format!("MST instance must be between 1 and {}", MAX_MST_INSTANCE_ID)));
+      }
+      Err(_) if bridge.trees.len() > MAX_MST_INSTANCES => {
+        return Err(Error::new(ErrorKind::OutOfMemory, format!("Maximum of {} MST instances reached", MAX_MST_INSTANCES)));
+      }
+      Err(_) => {
+        let mut tree = StpTree::new(instance, bridge.ports.len());
+        let state = if self.is_enabled() { PortState::Discarding } else { PortState::Forwarding };
+        for port in tree.ports.iter_mut() {
+          port.state = state;
+        }
+        let t = bridge.trees.partition_point(|other| other.instance < instance);
+        bridge.trees.insert(t, tree);
+        t
+      }
+    };
+    let bridge = &mut *bridge;
+    let result = update(&mut bridge.trees[t], &bridge.ports);
+    if self.is_enabled() {
+      bridge.update(&self.fib);
+    }
+    bridge.push_states();
+    Ok(result)
+  }
+
+  // Handle frame sent to the bridge group address on interface
+  pub fn receive(&self, intf: &InterfaceView, frame: &Frame) {
+    let Some(bpdu) = Bpdu::parse(frame) else {
+      if intf.is_debug_mode() {
+        println!("Dropping invalid BPDU ingressing on {}", intf.name);
+      }
+      return
+    };
+    if intf.is_debug_mode() {
+      println!("Received {} BPDU on {}: root {}, cost {}, bridge {}, port 0x{:04x}, flags 0x{:02x}", bpdu.kind,
+        intf.name, format_bridge_id(bpdu.vector.root), bpdu.vector.root_cost, format_bridge_id(bpdu.vector.bridge),
+        bpdu.vector.port, bpdu.flags);
+    }
+    let mut bridge = self.bridge.lock().unwrap();
+    let Some(i) = bridge.ports.iter().position(|port| port.intf.name == intf.name) else {
+      return
+    };
+    if !bridge.ports[i].is_enabled() {
+      return;
+    }
+    bridge.receive(i, &bpdu, &self.fib);
+  }
+
+  // BPDU filter and guard apply whether spanning tree runs or not, returns
+  // whether they consumed the BPDU
+  pub fn guard_bpdu(&self, intf: &InterfaceView) -> bool {
+    let bridge = self.bridge.lock().unwrap();
+    let Some(port) = bridge.ports.iter().find(|port| port.intf.name == intf.name) else {
+      return false
+    };
+    if port.bpdu_filter {
+      if intf.is_debug_mode() {
+        println!("Dropping BPDU ingressing on {}: bpdufilter enabled", intf.name);
+      }
+      return true;
+    }
+    if port.bpdu_guard && port.is_enabled() {
+      eprintln!("Err-disabling {}: BPDU received with bpduguard enabled", intf.name);
+      port.intf.send_cmd(IntfCmd::ErrDisable(ErrDisableReason::BpduGuard));
+      return true;
+    }
+    false
+  }
+
+  // Age received information, run timers and send hellos
+  pub fn tick(&self) {
+    if self.is_enabled() {
+      self.bridge.lock().unwrap().tick(&self.fib);
+    }
+  }
+
+  pub fn format(&self) -> String {
+    if !self.is_enabled() {
+      return "Spanning tree disabled\n".to_string();
+    }
+    self.bridge.lock().unwrap().format_tree(CIST)
+  }
+
+  pub fn format_mst(&self, instance: u16) -> io::Result<String> {
+    let bridge = self.bridge.lock().unwrap();
+    let t = bridge.tree_index(instance)?;
+    if !self.is_enabled() || bridge.version != StpVersion::Mstp {
+      return Ok("Multiple spanning tree disabled\n".to_string());
+    }
+    Ok(bridge.format_tree(t))
+  }
+
+  pub fn format_mst_configuration(&self) -> String {
+    let bridge = self.bridge.lock().unwrap();
+    let mut output = format!("Name      {}\n", bridge.region.name());
+    output += &format!("Revision  {}\n", bridge.region.revision());
+    output += &format!("Digest    0x{}\n\n", bridge.region.config_id().format_digest());
+    output += "Instance Vlans mapped\n";
+    output += "-------- ---------------------------------------\n";
+    for tree in bridge.trees.iter() {
+      let vlans = bridge.region.vlans(tree.instance);
+      output += &format!("{:<8} {}\n", tree.instance,
+        format_vlan_list(vlans.iter().filter(|vlan| (1..VLAN_COUNT - 1).contains(*vlan))));
+    }
+    output
+  }
+}
+
+impl<'a> StpBridge<'a> {
+  fn tree_index(&self, instance: u16) -> io::Result<usize> {
+    self.trees.iter().position(|tree| tree.instance == instance)
+      .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("MST instance {} not found", instance)))
+  }
+
+  // Only the CIST runs outside of MST mode
+  fn active_trees(&self) -> usize {
+    if self.version == StpVersion::Mstp { self.trees.len() } else { 1 }
+  }
+
+  // Vlans whose port states follow tree, None for all of them
+  fn tree_vlans(&self, t: usize) -> Option<Vec<u16>> {
+    match self.version {
+      StpVersion::Mstp => Some(self.region.vlans(self.trees[t].instance)),
+      _ if t == CIST => None,
+      _ => Some(Vec::new()),
+    }
+  }
+
+  fn bridge_id(&self, t: usize) -> u64 {
+    // Instance number is the system id extension of the bridge priority
+    bridge_id(self.trees[t].priority | self.trees[t].instance, &self.mac)
+  }
+
+  fn port_id(&self, t: usize, i: usize) -> u16 {
+    (self.trees[t].ports[i].priority as u16) << 8 | self.ports[i].number
+  }
+
+  fn is_root(&self, t: usize) -> bool {
+    self.trees[t].root_port.is_none()
+  }
+
+  fn is_rapid(&self, i: usize) -> bool {
+    self.version != StpVersion::Stp && self.ports[i].rstp
+  }
+
+  // Instances other than the CIST only run on ports inside the region
+  fn is_boundary(&self, t: usize, i: usize) -> bool {
+    t != CIST && !self.ports[i].internal
+  }
+
+  // Maximum age of received information, in seconds for the CIST and hops otherwise
+  fn max_age(&self, t: usize) -> u16 {
+    if t == CIST { self.max_age } else { self.max_hops as u16 }
+  }
+
+  fn designated_vector(&self, t: usize, i: usize) -> PriorityVector {
+    let root = &self.trees[t].root;
+    PriorityVector{root: root.root, root_cost: root.root_cost, bridge: self.bridge_id(t), port: self.port_id(t, i)}
+  }
+
+  fn reset_tree(&mut self, t: usize, state: PortState) {
+    self.trees[t].root_port = None;
+    for port in self.trees[t].ports.iter_mut() {
+      *port = TreePort{priority: port.priority, cost: port.cost, ..TreePort::new(state)};
+    }
+  }
+
+  // Mirror port states in interfaces for each vlan
+  fn push_states(&self) {
+    let trees: Vec<usize> = (0..VLAN_COUNT).map(|vlan| self.vlan_tree(vlan)).collect();
+    for (i, port) in self.ports.iter().enumerate() {
+      for (vlan, t) in trees.iter().enumerate() {
+        port.intf.set_stp_state(vlan as u16, self.trees[*t].ports[i].state);
+      }
+    }
+  }
+
+  fn vlan_tree(&self, vlan: u16) -> usize {
+    if self.version != StpVersion::Mstp {
+      return CIST;
+    }
+    let instance = self.region.instance(vlan);
+    self.trees.iter().position(|tree| tree.instance == instance).unwrap_or(CIST)
+  }
+
+  fn receive(&mut self, i: usize, bpdu: &Bpdu, fib: &Fib) {
+    // A bridge is attached, the port is no longer an edge port
+    self.ports[i].oper_edge = false;
+    if bpdu.kind == BpduType::Tcn {
+      // Only 802.1D bridges send topology change notifications
+      self.ports[i].rstp = false;
+      self.ports[i].internal = false;
+      if self.trees[CIST].ports[i].role == PortRole::Designated {
+        self.ports[i].tc_ack = true;
+        self.topology_change(CIST, i, true, fib);
+        self.send_bpdu(i);
+      }
+      return;
+    }
+
+    self.ports[i].rstp = self.version != StpVersion::Stp && bpdu.kind != BpduType::Config;
+    let internal = self.version == StpVersion::Mstp
+      && bpdu.mst.as_ref().is_some_and(|mst| mst.config_id == *self.region.config_id());
+    if self.ports[i].internal != internal {
+      self.ports[i].internal = internal;
+      for t in 1..self.trees.len() {
+        self.trees[t].ports[i].received = None;
+      }
+    }
+    if bpdu.kind == BpduType::Config && bpdu.flags & FLAG_TC_ACK != 0 && self.trees[CIST].root_port == Some(i) {
+      self.tcn_pending = false;
+    }
+    let info = ReceivedInfo{vector: bpdu.vector, age: bpdu.message_age, at: Instant::now()};
+    self.receive_message(CIST, i, bpdu.role(), bpdu.flags, info, fib);
+
+    if let Some(mst) = &bpdu.mst && internal {
+      for msti in mst.instances.iter() {
+        if let Ok(t) = self.tree_index(msti.instance) && t != CIST {
+          let age = self.max_hops.saturating_sub(msti.remaining_hops) as u16;
+          let info = ReceivedInfo{vector: msti.vector, age, at: Instant::now()};
+          self.receive_message(t, i, msti.role(), msti.flags, info, fib);
+        }
+      }
+    }
+  }
+
+  // Handle priority vector and flags of tree received on port
+  fn receive_message(&mut self, t: usize, i: usize, role: BpduRole, flags: u8, info: ReceivedInfo, fib: &Fib) {
+    if flags & FLAG_TC != 0 {
+      self.topology_change(t, i, true, fib);
+    }
+    match role {
+      BpduRole::Designated => {
+        if info.age >= self.max_age(t).max(1) {
+          return; // information too old to be used
+        }
+        let port = &mut self.trees[t].ports[i];
+        port.received = Some(info);
+        if port.inconsistent == Some(Inconsistency::Loop) {
+          port.inconsistent = None;
+          eprintln!("Loop guard unblocking {} in instance {}: BPDU received", self.ports[i].intf.name,
+            self.trees[t].instance);
+        }
+        self.update(fib);
+        if self.is_rapid(i) && flags & FLAG_PROPOSAL != 0 && self.trees[t].ports[i].role == PortRole::Root {
+          self.sync(t, i, fib);
+          self.trees[t].ports[i].agree = true;
+          self.advance_states(fib);
+          self.send_bpdu(i);
+        }
+      }
+      BpduRole::Root | BpduRole::AlternateBackup => {
+        // Neighbour answer to our designated port
+        let port = &mut self.trees[t].ports[i];
+        if flags & FLAG_AGREEMENT != 0 && port.role == PortRole::Designated && port.proposing {
+          port.agreed = true;
+          port.proposing = false;
+          self.advance_states(fib);
+        }
+      }
+      BpduRole::Unknown => (),
+    }
+  }
+
+  // Block designated ports below a new root port before agreeing, they
+  // propose to their own neighbours in turn
+  fn sync(&mut self, t: usize, root_port: usize, fib: &Fib) {
+    for i in 0..self.ports.len() {
+      if i == root_port || self.trees[t].ports[i].role != PortRole::Designated || !self.ports[i].is_enabled()
+        || self.is_boundary(t, i) || self.ports[i].oper_edge {
+        continue;
+      }
+      if self.trees[t].ports[i].state != PortState::Discarding {
+        self.set_state(t, i, PortState::Discarding, fib);
+      }
+      let rapid = self.is_rapid(i);
+      let port = &mut self.trees[t].ports[i];
+      port.agreed = false;
+      port.proposing = rapid;
+      self.send_bpdu(i);
+    }
+  }
+
+  fn tick(&mut self, fib: &Fib) {
+    let now = Instant::now();
+    for i in 0..self.ports.len() {
+      let timeout = if self.is_rapid(i) {
+        Duration::from_secs(3 * self.hello_time as u64)
+      } else {
+        Duration::from_secs(self.max_age as u64)
+      };
+      let enabled = self.ports[i].is_enabled();
+      if !enabled {
+        self.ports[i].rstp = true;
+        self.ports[i].internal = false;
+        self.ports[i].oper_edge = self.ports[i].edge;
+      }
+      let loop_guard = self.ports[i].guard == PortGuard::Loop;
+      for (t, tree) in self.trees.iter_mut().enumerate() {
+        let port = &mut tree.ports[i];
+        let age = |info: ReceivedInfo| if t == CIST { Duration::from_secs(info.age as u64) } else { Duration::ZERO };
+        if !enabled {
+          port.received = None;
+          port.inconsistent = None;
+        } else if port.received.is_some_and(|info| now.duration_since(info.at) + age(info) >= timeout) {
+          port.received = None;
+          // Neighbour stopped sending BPDUs, keep blocking rather than becoming designated
+          if loop_guard && matches!(port.role, PortRole::Root | PortRole::Alternate | PortRole::Backup) {
+            port.inconsistent = Some(Inconsistency::Loop);
+            eprintln!("Loop guard blocking {} in instance {}: BPDUs no longer received", self.ports[i].intf.name,
+              tree.instance);
+          }
+        }
+        if port.tc_while.is_some_and(|until| until <= now) {
+          port.tc_while = None;
+        }
+      }
+      if self.trees[CIST].ports[i].received.is_none() {
+        self.ports[i].internal = false;
+      }
+    }
+    self.update(fib);
+
+    if self.last_hello.is_some_and(|last| now.duration_since(last) < Duration::from_secs(self.hello_time as u64)) {
+      return;
+    }
+    self.last_hello = Some(now);
+    if self.tcn_pending && let Some(root_port) = self.trees[CIST].root_port {
+      self.send_tcn(root_port);
+    }
+    for i in 0..self.ports.len() {
+      let rapid = self.is_rapid(i);
+      let sends = self.trees[..self.active_trees()].iter().any(|tree| {
+        let port = &tree.ports[i];
+        port.role == PortRole::Designated || (port.role == PortRole::Root && rapid && port.tc_while.is_some())
+      });
+      if sends {
+        self.send_bpdu(i);
+      }
+    }
+  }
+
+  // Select root port and port roles of each tree, then move port states accordingly
+  fn update(&mut self, fib: &Fib) {
+    let previous_roots: Vec<(PriorityVector, Option<usize>)> = self.trees.iter()
+      .map(|tree| (tree.root, tree.root_port))
+      .collect();
+    for t in 0..self.active_trees() {
+      self.update_roles(t, fib);
+    }
+    self.advance_states(fib);
+
+    // Advertise new root information right away
+    let changed: Vec<usize> = (0..self.active_trees())
+      .filter(|t| (self.trees[*t].root, self.trees[*t].root_port) != previous_roots[*t])
+      .collect();
+    for i in 0..self.ports.len() {
+      if changed.iter().any(|t| self.trees[*t].ports[i].role == PortRole::Designated) {
+        self.send_bpdu(i);
+      }
+    }
+  }
+
+  fn update_roles(&mut self, t: usize, fib: &Fib) {
+    let own = self.bridge_id(t);
+    let mut best: Option<(PriorityVector, u16, usize)> = None;
+    for (i, port) in self.trees[t].ports.iter().enumerate() {
+      let Some(info) = port.received.filter(|_| self.ports[i].is_enabled() && !self.is_boundary(t, i)
+        && self.ports[i].guard != PortGuard::Root) else {
+        continue;
+      };
+      if info.vector.bridge & BRIDGE_MAC_MASK == own & BRIDGE_MAC_MASK {
+        continue; // our own BPDU looped back
+      }
+      let vector = PriorityVector{root_cost: info.vector.root_cost.saturating_add(port.cost), ..info.vector};
+      let port_id = self.port_id(t, i);
+      if best.is_none_or(|(best_vector, best_port, _)| (vector, port_id) < (best_vector, best_port)) {
+        best = Some((vector, port_id, i));
+      }
+    }
+    let own_vector = PriorityVector{root: own, root_cost: 0, bridge: own, port: 0};
+    let tree = &mut self.trees[t];
+    match best {
+      Some((vector, _, i)) if vector < own_vector => {
+        tree.root = vector;
+        tree.root_port = Some(i);
+        tree.root_age = tree.ports[i].received.map_or(0, |info| info.age);
+      }
+      _ => {
+        tree.root = own_vector;
+        tree.root_port = None;
+        tree.root_age = 0;
+        if t == CIST {
+          self.tcn_pending = false;
+        }
+      }
+    }
+
+    for i in 0..self.ports.len() {
+      let designated = self.designated_vector(t, i);
+      let guard = self.ports[i].guard;
+      let tree = &self.trees[t];
+      let port = &tree.ports[i];
+      let superior = port.received.is_some_and(|info| info.vector < designated);
+      let inconsistent = match port.inconsistent {
+        _ if guard == PortGuard::Root && superior && !self.is_boundary(t, i) => Some(Inconsistency::Root),
+        Some(Inconsistency::Loop) if guard == PortGuard::Loop => Some(Inconsistency::Loop),
+        _ => None,
+      };
+      if inconsistent != port.inconsistent && inconsistent != Some(Inconsistency::Loop) {
+        let action = if inconsistent.is_some() { "blocking" } else { "unblocking" };
+        eprintln!("Root guard {} {} in instance {}", action, self.ports[i].intf.name, tree.instance);
+      }
+      self.trees[t].ports[i].inconsistent = inconsistent;
+      let tree = &self.trees[t];
+      let port = &tree.ports[i];
+      let role = if !self.ports[i].is_enabled() {
+        PortRole::Disabled
+      } else if self.is_boundary(t, i) {
+        match self.trees[CIST].ports[i].role {
+          PortRole::Root => PortRole::Master,
+          role => role,
+        }
+      } else if inconsistent.is_some() {
+        PortRole::Alternate
+      } else if tree.root_port == Some(i) {
+        PortRole::Root
+      } else if let Some(info) = port.received && info.vector < designated {
+        if info.vector.bridge & BRIDGE_MAC_MASK == own & BRIDGE_MAC_MASK { PortRole::Backup } else { PortRole::Alternate }
+      } else {
+        PortRole::Designated
+      };
+      self.set_role(t, i, role, fib);
+    }
+  }
+
+  fn set_role(&mut self, t: usize, i: usize, role: PortRole, fib: &Fib) {
+    if self.trees[t].ports[i].role == role {
+      return;
+    }
+    let rapid = self.is_rapid(i);
+    let port = &mut self.trees[t].ports[i];
+    port.role = role;
+    if port.state != PortState::Forwarding {
+      port.state_since = Instant::now(); // restart forward delay timers
+    }
+    port.agreed = false;
+    port.agree = false;
+    port.proposing = false;
+    match role {
+      PortRole::Designated => port.proposing = rapid && port.state != PortState::Forwarding,
+      PortRole::Root | PortRole::Master => (),
+      PortRole::Alternate | PortRole::Backup | PortRole::Disabled => self.set_state(t, i, PortState::Discarding, fib),
+    }
+  }
+
+  // Rapid transitions rely on proposal/agreement, other ports wait for
+  // forward delay in discarding then learning state. Ports on the region
+  // boundary follow the CIST in every instance.
+  fn advance_states(&mut self, fib: &Fib) {
+    let now = Instant::now();
+    let forward_delay = Duration::from_secs(self.forward_delay as u64);
+    for t in 0..self.active_trees() {
+      for i in 0..self.ports.len() {
+        let port = &self.trees[t].ports[i];
+        let rapid = self.is_rapid(i);
+        let next = match port.role {
+          PortRole::Disabled => PortState::Discarding,
+          _ if self.is_boundary(t, i) => self.trees[CIST].ports[i].state,
+          PortRole::Root if rapid => PortState::Forwarding,
+          PortRole::Designated if port.agreed || self.ports[i].oper_edge => PortState::Forwarding,
+          PortRole::Root | PortRole::Designated | PortRole::Master => {
+            if port.state == PortState::Forwarding || now.duration_since(port.state_since) < forward_delay {
+              port.state
+            } else if port.state == PortState::Discarding {
+              PortState::Learning
+            } else {
+              PortState::Forwarding
+            }
+          }
+          PortRole::Alternate | PortRole::Backup => PortState::Discarding,
+        };
+        self.set_state(t, i, next, fib);
+      }
+    }
+  }
+
+  fn set_state(&mut self, t: usize, i: usize, state: PortState, fib: &Fib) {
+    let port = &mut self.trees[t].ports[i];
+    if port.state == state {
+      return;
+    }
+    let was_forwarding = port.state == PortState::Forwarding;
+    port.state = state;
+    port.state_since = Instant::now();
+    if state == PortState::Forwarding {
+      port.proposing = false;
+    }
+    let role = port.role;
+    let intf = &self.ports[i].intf;
+    match self.tree_vlans(t) {
+      Some(vlans) => vlans.into_iter().for_each(|vlan| intf.set_stp_state(vlan, state)),
+      None => (0..VLAN_COUNT).for_each(|vlan| intf.set_stp_state(vlan, state)),
+    }
+    // Hosts behind edge ports do not move addresses of other ports
+    if role == PortRole::Disabled || self.ports[i].oper_edge {
+      return;
+    }
+    // 802.1w only signals ports becoming forwarding, 802.1D also the ones being blocked
+    if state == PortState::Forwarding || (was_forwarding && !self.is_rapid(i)) {
+      self.topology_change(t, i, false, fib);
+    }
+  }
+
+  // Flush addresses of tree vlans learned on other ports and propagate the change
+  fn topology_change(&mut self, t: usize, origin: usize, received: bool, fib: &Fib) {
+    let now = Instant::now();
+    let tc_time = if self.version != StpVersion::Stp {
+      Duration::from_secs(2 * self.hello_time as u64)
+    } else {
+      Duration::from_secs((self.max_age + self.forward_delay) as u64)
+    };
+    let vlans = self.tree_vlans(t);
+    let tree = &mut self.trees[t];
+    tree.topology_changes += 1;
+    tree.last_topology_change = Some(now);
+    for (i, port) in tree.ports.iter_mut().enumerate() {
+      if i == origin {
+        if !received && matches!(port.role, PortRole::Root | PortRole::Designated | PortRole::Master) {
+          port.tc_while = Some(now + tc_time);
+        }
+        continue;
+      }
+      let if_name = &self.ports[i].intf.name;
+      match &vlans {
+        Some(vlans) => vlans.iter().for_each(|vlan| fib.remove_intf_vlan_entries(if_name.clone(), *vlan)),
+        None => fib.remove_intf_entries(if_name.clone()),
+      }
+      if matches!(port.role, PortRole::Root | PortRole::Designated | PortRole::Master) {
+        port.tc_while = Some(now + tc_time);
+      }
+    }
+    if t == CIST && let Some(root_port) = tree.root_port && root_port != origin && !self.is_rapid(root_port) {
+      self.tcn_pending = true;
+      self.send_tcn(root_port);
+    }
+  }
+
+  // Flags of tree port, pending agreement is consumed
+  fn message_flags(&mut self, t: usize, i: usize, rapid: bool) -> u8 {
+    let port = &mut self.trees[t].ports[i];
+    let mut flags = 0;
+    if port.tc_while.is_some() {
+      flags |= FLAG_TC;
+    }
+    if !rapid {
+      return flags;
+    }
+    if port.proposing {
+      flags |= FLAG_PROPOSAL;
+    }
+    if port.agree {
+      flags |= FLAG_AGREEMENT;
+      port.agree = false;
+    }
+    if port.state >= PortState::Learning {
+      flags |= FLAG_LEARNING;
+    }
+    if port.state == PortState::Forwarding {
+      flags |= FLAG_FORWARDING;
+    }
+    flags
+  }
+
+  fn bpdu_role(&self, t: usize, i: usize) -> BpduRole {
+    match self.trees[t].ports[i].role {
+      PortRole::Root => BpduRole::Root,
+      PortRole::Designated => BpduRole::Designated,
+      PortRole::Alternate | PortRole::Backup => BpduRole::AlternateBackup,
+      PortRole::Master | PortRole::Disabled => BpduRole::Unknown, // master role is encoded as 0
+    }
+  }
+
+  fn send_bpdu(&mut self, i: usize) {
+    if !self.ports[i].is_enabled() || self.ports[i].bpdu_filter {
+      return;
+    }
+    let rapid = self.is_rapid(i);
+    let kind = match self.version {
+      _ if !rapid => BpduType::Config,
+      StpVersion::Mstp => BpduType::Mst,
+      _ => BpduType::Rst,
+    };
+    let message_age = if self.is_root(CIST) { 0 } else { self.trees[CIST].root_age + 1 };
+    let mut bpdu = Bpdu{kind, flags: self.message_flags(CIST, i, rapid), vector: self.designated_vector(CIST, i),
+      message_age, max_age: self.max_age, hello_time: self.hello_time, forward_delay: self.forward_delay, mst: None};
+    if self.ports[i].tc_ack {
+      bpdu.flags |= FLAG_TC_ACK;
+      self.ports[i].tc_ack = false;
+    }
+    if rapid {
+      bpdu.set_role(self.bpdu_role(CIST, i));
+    }
+    if kind == BpduType::Mst {
+      let instances = (1..self.trees.len()).map(|t| {
+        let age = if self.is_root(t) { 0 } else { self.trees[t].root_age + 1 };
+        let mut msti = MstiMessage{instance: self.trees[t].instance, flags: self.message_flags(t, i, true),
+          vector: self.designated_vector(t, i), remaining_hops: self.max_hops.saturating_sub(age as u8)};
+        msti.set_role(self.bpdu_role(t, i));
+        msti
+      }).collect();
+      bpdu.mst = Some(MstBpdu{config_id: self.region.config_id().clone(), remaining_hops: self.max_hops, instances});
+    }
+    let intf = &self.ports[i].intf;
+    if let Err(err) = intf.send(bpdu.to_frame(intf.mac)) {
+      eprintln!("Error: unable to send BPDU on {}: {}", intf.name, err);
+    }
+  }
+
+  fn send_tcn(&self, i: usize) {
+    if self.ports[i].bpdu_filter {
+      return;
+    }
+    let intf = &self.ports[i].intf;
+    if let Err(err) = intf.send(Bpdu::tcn().to_frame(intf.mac)) {
+      eprintln!("Error: unable to send BPDU on {}: {}", intf.name, err);
+    }
+  }
+
+  fn format_tree(&self, t: usize) -> String {
+    let tree = &self.trees[t];
+    let mut output = if t == CIST {
+      format!("Spanning tree enabled protocol {}\n", self.version)
+    } else {
+      format!("MST instance {}, vlans mapped: {}\n", tree.instance, format_vlan_list(&self.region.vlans(tree.instance)))
+    };
+    let root_name = if t == CIST { "Root ID   " } else { "Regional Root ID" };
+    output += &format!("{} {}, cost {}", root_name, format_bridge_id(tree.root.root), tree.root.root_cost);
+    match tree.root_port {
+      Some(i) => output += &format!(", port {}\n", self.ports[i].intf.name),
+      None => output += "\n           This bridge is the root\n",
+    }
+    output += &format!("Bridge ID  {}\n", format_bridge_id(self.bridge_id(t)));
+    if t == CIST {
+      output += &format!("           Hello Time {} sec, Max Age {} sec, Forward Delay {} sec\n",
+        self.hello_time, self.max_age, self.forward_delay);
+    }
+    output += &format!("Topology changes {}", tree.topology_changes);
+    if let Some(last) = tree.last_topology_change {
+      output += &format!(", last change {} sec ago", last.elapsed().as_secs());
+    }
+    output += "\n\n";
+
+    output += &format!("{:<16} {:<10} {:<11} {:<10} {:<9} Protocol Notes\n", "Interface", "Role", "State", "Cost", "Prio.Nbr");
+    output += "---------------- ---------- ----------- ---------- --------- -------- -------------------\n";
+    for (i, port) in tree.ports.iter().enumerate() {
+      let protocol = match self.version {
+        _ if !self.is_rapid(i) => StpVersion::Stp,
+        StpVersion::Mstp if !self.ports[i].internal => StpVersion::Rstp, // region boundary
+        version => version,
+      };
+      let line = format!("{:<16} {:<10} {:<11} {:<10} {:<9} {:<8} {}", self.ports[i].intf.name, port.role.to_string(),
+        port.state.to_string(), port.cost, format!("{}.{}", port.priority, self.ports[i].number), protocol.to_string(),
+        self.port_notes(t, i).join(" "));
+      output += line.trim_end();
+      output += "\n";
+    }
+    output
+  }
+
+  // Edge and protection features of port, guard inconsistencies are starred
+  fn port_notes(&self, t: usize, i: usize) -> Vec<&'static str> {
+    let port = &self.ports[i];
+    let mut notes = Vec::new();
+    if port.oper_edge {
+      notes.push("edge");
+    }
+    if port.bpdu_guard {
+      notes.push("bpduguard");
+    }
+    if port.bpdu_filter {
+      notes.push("bpdufilter");
+    }
+    match self.trees[t].ports[i].inconsistent {
+      Some(Inconsistency::Root) => notes.push("*ROOT_Inc"),
+      Some(Inconsistency::Loop) => notes.push("*LOOP_Inc"),
+      None if port.guard == PortGuard::Root => notes.push("root-guard"),
+      None if port.guard == PortGuard::Loop => notes.push("loop-guard"),
+      None => (),
+    }
+    notes
+  }
+}
+
+impl StpTree {
+  fn new(instance: u16, ports: usize) -> Self {
+    StpTree{instance, priority: DEFAULT_BRIDGE_PRIORITY, root: PriorityVector{root: 0, root_cost: 0, bridge: 0, port: 0},
+      root_port: None, root_age: 0, topology_changes: 0, last_topology_change: None,
+      ports: (0..ports).map(|_| TreePort::new(PortState::Forwarding)).collect()}
+  }
+}
+
+impl TreePort {
+  fn new(state: PortState) -> Self {
+    TreePort{priority: DEFAULT_PORT_PRIORITY, cost: DEFAULT_PORT_COST, role: PortRole::Disabled, state,
+      state_since: Instant::now(), received: None, proposing: false, agreed: false, agree: false, tc_while: None,
+      inconsistent: None}
+  }
+}
+
+impl StpPort<'_> {
+  // Channel group members are part of their port-channel port
+  fn is_enabled(&self) -> bool {
+    self.intf.is_up() && !self.intf.is_monitoring() && !self.intf.is_channel_member()
+  }
+}
+
+fn port_index(ports: &[StpPort], if_name: &str) -> io::Result<usize> {
+  ports.iter().position(|port| port.intf.name == if_name)
+    .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("Interface {} not found", if_name)))
+}
+
+impl fmt::Display for StpVersion {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}",
+    match self {
+      StpVersion::Stp => "stp",
+      StpVersion::Rstp => "rstp",
+      StpVersion::Mstp => "mstp",
+    })
+  }
+}
+
+impl fmt::Display for PortRole {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}",
+    match self {
+      PortRole::Root => "Root",
+      PortRole::Designated => "Designated",
+      PortRole::Alternate => "Alternate",
+      PortRole::Backup => "Backup",
+      PortRole::Master => "Master",
+      PortRole::Disabled => "Disabled",
+    })
+  }
+}
+
+impl fmt::Display for PortState {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}",
+    match self {
+      PortState::Discarding => "discarding",
+      PortState::Learning => "learning",
+      PortState::Forwarding => "forwarding",
+    })
+  }
+}
@@ -0,0 +1,268 @@
+use std::fmt;
+use macaddr::MacAddr6;
+
+use crate::network::frame::Frame;
+use super::mst::{MstConfigId, REGION_NAME_LEN};
+
+// Bridge group address BPDUs are sent to, never forwarded by bridges
+pub const BPDU_DST_MAC: MacAddr6 = MacAddr6::new(0x01, 0x80, 0xc2, 0x00, 0x00, 0x00);
+
+const LLC_HEADER: [u8; 3] = [0x42, 0x42, 0x03];
+const CONFIG_BPDU_LEN: usize = 35;
+const TCN_BPDU_LEN: usize = 4;
+const MST_BPDU_LEN: usize = 102;
+const MSTI_MESSAGE_LEN: usize = 16;
+const MST_VERSION_3_BASE_LEN: usize = 64;
+const MIN_FRAME_DATA_LEN: usize = 46;
+
+pub const FLAG_TC: u8 = 0x01;
+pub const FLAG_PROPOSAL: u8 = 0x02;
+pub const FLAG_LEARNING: u8 = 0x10;
+pub const FLAG_FORWARDING: u8 = 0x20;
+pub const FLAG_AGREEMENT: u8 = 0x40;
+pub const FLAG_TC_ACK: u8 = 0x80;
+const FLAG_ROLE_SHIFT: u8 = 2;
+const FLAG_ROLE_MASK: u8 = 0x0c;
+const BRIDGE_MAC_MASK: u64 = 0xffff_ffff_ffff;
+const MSTI_ID_MASK: u64 = 0x0fff;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BpduType {
+  Config, // 802.1D configuration
+  Tcn,    // 802.1D topology change notification
+  Rst,    // 802.1w rapid spanning tree
+  Mst,    // 802.1s multiple spanning tree
+}
+
+// Role carried in RST BPDU flags
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BpduRole {
+  Unknown,
+  AlternateBackup,
+  Root,
+  Designated,
+}
+
+// Spanning tree priority vector, lower is better
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PriorityVector {
+  pub root: u64,
+  pub root_cost: u32,
+  pub bridge: u64,
+  pub port: u16,
+}
+
+#[derive(Debug, Clone)]
+pub struct Bpdu {
+  pub kind: BpduType,
+  pub flags: u8,
+  pub vector: PriorityVector,
+  // Timers in seconds
+  pub message_age: u16,
+  pub max_age: u16,
+  pub hello_time: u16,
+  pub forward_delay: u16,
+  pub mst: Option<MstBpdu>,
+}
+
+// MST BPDU fields following the RST ones. Regions are not collapsed into a
+// single bridge: the CIST path cost is the sum of external and internal costs
+// and the designated bridge is the CIST bridge identifier.
+#[derive(Debug, Clone)]
+pub struct MstBpdu {
+  pub config_id: MstConfigId,
+  pub remaining_hops: u8,
+  pub instances: Vec<MstiMessage>,
+}
+
+// Priority vector of a spanning tree instance: regional root, internal
+// root path cost, designated bridge and port
+#[derive(Debug, Clone, Copy)]
+pub struct MstiMessage {
+  pub instance: u16,
+  pub flags: u8,
+  pub vector: PriorityVector,
+  pub remaining_hops: u8,
+}
+
+impl Bpdu {
+  pub fn tcn() -> Self {
+    Bpdu{kind: BpduType::Tcn, flags: 0, vector: PriorityVector{root: 0, root_cost: 0, bridge: 0, port: 0},
+      message_age: 0, max_age: 0, hello_time: 0, forward_delay: 0, mst: None}
+  }
+
+  pub fn role(&self) -> BpduRole {
+    match self.kind {
+      BpduType::Rst | BpduType::Mst => flags_role(self.flags),
+      // Configuration BPDUs are only sent by designated ports
+      BpduType::Config => BpduRole::Designated,
+      BpduType::Tcn => BpduRole::Unknown,
+    }
+  }
+
+  pub fn set_role(&mut self, role: BpduRole) {
+    self.flags = flags_with_role(self.flags, role);
+  }
+
+  // BPDU from LLC payload of a frame sent to the bridge group address
+  pub fn parse(frame: &Frame) -> Option<Bpdu> {
+    let data = frame.get_data();
+    if frame.get_ether_type() > 1500 || data.len() < LLC_HEADER.len() + TCN_BPDU_LEN || data[..3] != LLC_HEADER {
+      return None;
+    }
+    let bpdu = &data[3..];
+    if bpdu[0] != 0 || bpdu[1] != 0 {
+      return None; // not spanning tree protocol
+    }
+    let kind = match bpdu[3] {
+      0x00 => BpduType::Config,
+      0x80 => return Some(Bpdu::tcn()),
+      0x02 if bpdu[2] >= 3 && bpdu.len() >= MST_BPDU_LEN => BpduType::Mst,
+      0x02 => BpduType::Rst,
+      _ => return None,
+    };
+    if bpdu.len() < CONFIG_BPDU_LEN {
+      return None;
+    }
+    let u16_at = |i: usize| u16::from_be_bytes([bpdu[i], bpdu[i + 1]]);
+    let u32_at = |i: usize| u32::from_be_bytes([bpdu[i], bpdu[i + 1], bpdu[i + 2], bpdu[i + 3]]);
+    let u64_at = |i: usize| (u32_at(i) as u64) << 32 | u32_at(i + 4) as u64;
+    let mut parsed = Bpdu{
+      kind,
+      flags: bpdu[4],
+      vector: PriorityVector{root: u64_at(5), root_cost: u32_at(13), bridge: u64_at(17), port: u16_at(25)},
+      message_age: u16_at(27) / 256,
+      max_age: u16_at(29) / 256,
+      hello_time: u16_at(31) / 256,
+      forward_delay: u16_at(33) / 256,
+      mst: None,
+    };
+    if kind == BpduType::Mst {
+      let length = (u16_at(36) as usize).min(bpdu.len() - 38);
+      let bridge = u64_at(93);
+      parsed.vector.root_cost = parsed.vector.root_cost.saturating_add(u32_at(89));
+      parsed.vector.bridge = bridge;
+      let mut name = [0; REGION_NAME_LEN];
+      name.copy_from_slice(&bpdu[39..39 + REGION_NAME_LEN]);
+      let mut digest = [0; 16];
+      digest.copy_from_slice(&bpdu[73..89]);
+      let instances = (MST_BPDU_LEN..38 + length).step_by(MSTI_MESSAGE_LEN)
+        .take_while(|i| i + MSTI_MESSAGE_LEN <= bpdu.len())
+        .map(|i| {
+          let root = u64_at(i + 1);
+          let instance = root >> 48 & MSTI_ID_MASK;
+          MstiMessage{
+            instance: instance as u16,
+            flags: bpdu[i],
+            vector: PriorityVector{root, root_cost: u32_at(i + 9),
+              bridge: (bpdu[i + 13] as u64 & 0xf0) << 56 | instance << 48 | (bridge & BRIDGE_MAC_MASK),
+              port: (bpdu[i + 14] as u16 & 0xf0) << 8 | (parsed.vector.port & 0x0fff)},
+            remaining_hops: bpdu[i + 15],
+          }
+        })
+        .collect();
+      parsed.mst = Some(MstBpdu{config_id: MstConfigId{name, revision: u16_at(71), digest},
+        remaining_hops: bpdu[101], instances});
+    }
+    Some(parsed)
+  }
+
+  // 802.3 frame carrying the BPDU
+  pub fn to_frame(&self, src_mac: MacAddr6) -> Frame {
+    let mut data = LLC_HEADER.to_vec();
+    data.extend([0, 0]); // protocol identifier
+    match self.kind {
+      BpduType::Tcn => data.extend([0, 0x80]),
+      BpduType::Config => data.extend([0, 0x00]),
+      BpduType::Rst => data.extend([2, 0x02]),
+      BpduType::Mst => data.extend([3, 0x02]),
+    }
+    if self.kind != BpduType::Tcn {
+      data.push(self.flags);
+      data.extend(self.vector.root.to_be_bytes());
+      data.extend(self.vector.root_cost.to_be_bytes());
+      // The CIST root stands for the regional root in MST BPDUs
+      let bridge = if self.kind == BpduType::Mst { self.vector.root } else { self.vector.bridge };
+      data.extend(bridge.to_be_bytes());
+      data.extend(self.vector.port.to_be_bytes());
+      for timer in [self.message_age, self.max_age, self.hello_time, self.forward_delay] {
+        data.extend((timer * 256).to_be_bytes());
+      }
+      if self.kind != BpduType::Config {
+        data.push(0); // version 1 length
+      }
+    }
+    if self.kind == BpduType::Mst && let Some(mst) = &self.mst {
+      data.extend(((MST_VERSION_3_BASE_LEN + mst.instances.len() * MSTI_MESSAGE_LEN) as u16).to_be_bytes());
+      data.push(0); // configuration identifier format selector
+      data.extend(mst.config_id.name);
+      data.extend(mst.config_id.revision.to_be_bytes());
+      data.extend(mst.config_id.digest);
+      data.extend(0u32.to_be_bytes()); // internal root path cost
+      data.extend(self.vector.bridge.to_be_bytes());
+      data.push(mst.remaining_hops);
+      for msti in &mst.instances {
+        data.push(msti.flags);
+        data.extend(msti.vector.root.to_be_bytes());
+        data.extend(msti.vector.root_cost.to_be_bytes());
+        data.push((msti.vector.bridge >> 56) as u8 & 0xf0);
+        data.push((msti.vector.port >> 8) as u8 & 0xf0);
+        data.push(msti.remaining_hops);
+      }
+    }
+    let length = data.len() as u16;
+    data.resize(data.len().max(MIN_FRAME_DATA_LEN), 0);
+    Frame::new(BPDU_DST_MAC, src_mac, length, data)
+  }
+}
+
+impl MstiMessage {
+  pub fn role(&self) -> BpduRole {
+    flags_role(self.flags)
+  }
+
+  pub fn set_role(&mut self, role: BpduRole) {
+    self.flags = flags_with_role(self.flags, role);
+  }
+}
+
+fn flags_role(flags: u8) -> BpduRole {
+  match (flags & FLAG_ROLE_MASK) >> FLAG_ROLE_SHIFT {
+    1 => BpduRole::AlternateBackup,
+    2 => BpduRole::Root,
+    3 => BpduRole::Designated,
+    _ => BpduRole::Unknown,
+  }
+}
+
+fn flags_with_role(flags: u8, role: BpduRole) -> u8 {
+  let role = match role {
+    BpduRole::Unknown => 0,
+    BpduRole::AlternateBackup => 1,
+    BpduRole::Root => 2,
+    BpduRole::Designated => 3,
+  };
+  (flags & !FLAG_ROLE_MASK) | (role << FLAG_ROLE_SHIFT)
+}
+
+// Bridge identifier, priority followed by MAC address
+pub fn bridge_id(priority: u16, mac: &MacAddr6) -> u64 {
+  mac.as_bytes().iter().fold(priority as u64, |acc, byte| (acc << 8) | *byte as u64)
+}
+
+pub fn format_bridge_id(id: u64) -> String {
+  let bytes = id.to_be_bytes();
+  format!("{}.{}", id >> 48, MacAddr6::new(bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]))
+}
+
+impl fmt::Display for BpduType {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}",
+    match self {
+      BpduType::Config => "config",
+      BpduType::Tcn => "tcn",
+      BpduType::Rst => "rst",
+      BpduType::Mst => "mst",
+    })
+  }
+}
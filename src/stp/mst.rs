@@ -0,0 +1,147 @@
+pub const MAX_MST_INSTANCES: usize = 64;
+pub const MAX_MST_INSTANCE_ID: u16 = 4094;
+const VLAN_COUNT: usize = 4096;
+pub const REGION_NAME_LEN: usize = 32;
+
+// Key of the configuration digest HMAC
+const DIGEST_KEY: [u8; 16] = [0x13, 0xac, 0x06, 0xa6, 0x2e, 0x47, 0xfd, 0x51,
+  0xf9, 0x5d, 0x2b, 0xa2, 0x43, 0xcd, 0x03, 0x46];
+
+// MST configuration identifier, bridges with the same one form a region
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MstConfigId {
+  pub name: [u8; REGION_NAME_LEN],
+  pub revision: u16,
+  pub digest: [u8; 16],
+}
+
+// Region configuration, vlans not mapped to an instance belong to the CIST (instance 0)
+pub struct MstRegion {
+  name: String,
+  revision: u16,
+  instances: Box<[u16]>, // vlan -> instance
+  config_id: MstConfigId,
+}
+
+impl Default for MstRegion {
+  fn default() -> Self {
+    let mut region = MstRegion{name: String::new(), revision: 0, instances: vec![0; VLAN_COUNT].into_boxed_slice(),
+      config_id: MstConfigId{name: [0; REGION_NAME_LEN], revision: 0, digest: [0; 16]}};
+    region.update_config_id();
+    region
+  }
+}
+
+impl MstRegion {
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  pub fn revision(&self) -> u16 {
+    self.revision
+  }
+
+  pub fn set_name(&mut self, name: String) {
+    self.name = name;
+    self.update_config_id();
+  }
+
+  pub fn set_revision(&mut self, revision: u16) {
+    self.revision = revision;
+    self.update_config_id();
+  }
+
+  pub fn instance(&self, vlan: u16) -> u16 {
+    self.instances[vlan as usize]
+  }
+
+  pub fn map_vlans(&mut self, instance: u16, vlans: &[u16]) {
+    for vlan in vlans {
+      self.instances[*vlan as usize] = instance;
+    }
+    self.update_config_id();
+  }
+
+  // Give vlans of instance back to the CIST
+  pub fn unmap_vlans(&mut self, instance: u16, vlans: Option<&[u16]>) {
+    for (vlan, other) in self.instances.iter_mut().enumerate() {
+      if *other == instance && vlans.is_none_or(|vlans| vlans.contains(&(vlan as u16))) {
+        *other = 0;
+      }
+    }
+    self.update_config_id();
+  }
+
+  pub fn vlans(&self, instance: u16) -> Vec<u16> {
+    (0..VLAN_COUNT as u16).filter(|vlan| self.instance(*vlan) == instance).collect()
+  }
+
+  pub fn config_id(&self) -> &MstConfigId {
+    &self.config_id
+  }
+
+  // Digest of the vlan to instance table, 802.1Q 13.8
+  fn update_config_id(&mut self) {
+    let mut name = [0; REGION_NAME_LEN];
+    let len = self.name.len().min(REGION_NAME_LEN);
+    name[..len].copy_from_slice(&self.name.as_bytes()[..len]);
+    let table: Vec<u8> = self.instances.iter().flat_map(|instance| instance.to_be_bytes()).collect();
+    self.config_id = MstConfigId{name, revision: self.revision, digest: hmac_md5(&DIGEST_KEY, &table)};
+  }
+}
+
+impl MstConfigId {
+  pub fn format_digest(&self) -> String {
+    self.digest.iter().map(|byte| format!("{:02X}", byte)).collect()
+  }
+}
+
+fn hmac_md5(key: &[u8; 16], data: &[u8]) -> [u8; 16] {
+  let mut inner = [0x36; 64].to_vec();
+  let mut outer = [0x5c; 64].to_vec();
+  for (i, byte) in key.iter().enumerate() {
+    inner[i] ^= byte;
+    outer[i] ^= byte;
+  }
+  inner.extend_from_slice(data);
+  outer.extend_from_slice(&md5(&inner));
+  md5(&outer)
+}
+
+// RFC 1321 message digest
+fn md5(data: &[u8]) -> [u8; 16] {
+  const SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+  let constants: Vec<u32> = (1..=64).map(|i| ((i as f64).sin().abs() * 4294967296.0) as u32).collect();
+
+  let mut message = data.to_vec();
+  message.push(0x80);
+  while message.len() % 64 != 56 {
+    message.push(0);
+  }
+  message.extend(((data.len() as u64) * 8).to_le_bytes());
+
+  let mut state: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+  for block in message.chunks(64) {
+    let words: Vec<u32> = block.chunks(4).map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]])).collect();
+    let [mut a, mut b, mut c, mut d] = state;
+    for i in 0..64 {
+      let (f, g) = match i / 16 {
+        0 => ((b & c) | (!b & d), i),
+        1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+        2 => (b ^ c ^ d, (3 * i + 5) % 16),
+        _ => (c ^ (b | !d), (7 * i) % 16),
+      };
+      let rotated = a.wrapping_add(f).wrapping_add(constants[i]).wrapping_add(words[g])
+        .rotate_left(SHIFTS[(i / 16) * 4 + i % 4]);
+      (a, b, c, d) = (d, b.wrapping_add(rotated), b, c);
+    }
+    for (value, add) in state.iter_mut().zip([a, b, c, d]) {
+      *value = value.wrapping_add(add);
+    }
+  }
+  let mut digest = [0; 16];
+  for (i, value) in state.iter().enumerate() {
+    digest[i * 4..i * 4 + 4].copy_from_slice(&value.to_le_bytes());
+  }
+  digest
+}
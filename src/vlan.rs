@@ -0,0 +1,323 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::io::{self, Error, ErrorKind};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use dashmap::DashMap;
+
+use super::network::interface::{InterfaceView, PortMode, DEFAULT_VLAN};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VlanState {
+  Active,
+  Suspend, // frames of the vlan are dropped
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrivateVlan {
+  Primary(BTreeSet<u16>), // associated secondary vlans
+  Isolated,  // hosts only talk to promiscuous ports
+  Community, // hosts talk to their community and promiscuous ports
+}
+
+#[derive(Debug, Clone)]
+pub struct Vlan {
+  pub name: String,
+  pub state: VlanState,
+  pub private_vlan: Option<PrivateVlan>,
+}
+
+#[derive(Debug, Default)]
+struct VlanCounters {
+  in_pkts: AtomicU64,
+  in_bytes: AtomicU64,
+  out_pkts: AtomicU64,
+  out_bytes: AtomicU64,
+  forwarded: AtomicU64, // unicast frames sent to a single interface
+  flooded: AtomicU64,
+}
+
+// Vlans configured on the switch, frames of other vlans are dropped
+pub struct VlanDb {
+  vlans: DashMap<u16, Vlan>,
+  primaries: DashMap<u16, u16>, // secondary vlan -> primary vlan
+  counters: Box<[VlanCounters]>, // indexed by vlan id
+}
+
+impl VlanDb {
+  pub fn new() -> Self {
+    let vlans = DashMap::new();
+    vlans.insert(DEFAULT_VLAN, Vlan{name: "default".to_string(), state: VlanState::Active, private_vlan: None});
+    let counters = (0..4096).map(|_| VlanCounters::default()).collect();
+    VlanDb{vlans, primaries: DashMap::new(), counters}
+  }
+
+  // Create vlan with default name if missing, returns whether it was created
+  pub fn create(&self, id: u16) -> bool {
+    debug_assert!(id > 0 && id < 4096);
+    let mut created = false;
+    self.vlans.entry(id).or_insert_with(|| {
+      created = true;
+      Vlan{name: format!("VLAN{:04}", id), state: VlanState::Active, private_vlan: None}
+    });
+    created
+  }
+
+  pub fn remove(&self, id: u16) -> io::Result<()> {
+    if id == DEFAULT_VLAN {
+      return Err(Error::new(ErrorKind::PermissionDenied, "Default vlan cannot be deleted"));
+    }
+    if !self.vlans.contains_key(&id) {
+      return Err(Error::new(ErrorKind::NotFound, format!("Vlan {} not found", id)));
+    }
+    self.clear_private_vlan(id);
+    self.vlans.remove(&id);
+    self.counters[id as usize].reset();
+    Ok(())
+  }
+
+  pub fn set_name(&self, id: u16, name: &str) -> io::Result<()> {
+    self.update(id, |vlan| vlan.name = name.to_string())
+  }
+
+  pub fn set_state(&self, id: u16, state: VlanState) -> io::Result<()> {
+    self.update(id, |vlan| vlan.state = state)
+  }
+
+  // Changing the private vlan type drops associations of the vlan
+  pub fn set_private_vlan(&self, id: u16, private_vlan: Option<PrivateVlan>) -> io::Result<()> {
+    if !self.vlans.contains_key(&id) {
+      return Err(Error::new(ErrorKind::NotFound, format!("Vlan {} not found", id)));
+    }
+    if id == DEFAULT_VLAN && private_vlan.is_some() {
+      return Err(Error::new(ErrorKind::PermissionDenied, "Default vlan cannot be a private vlan"));
+    }
+    self.clear_private_vlan(id);
+    self.update(id, |vlan| vlan.private_vlan = private_vlan)
+  }
+
+  // Associate isolated or community vlans to primary vlan
+  pub fn associate_private_vlans(&self, primary: u16, secondaries: &[u16]) -> io::Result<()> {
+    if !self.vlans.get(&primary).is_some_and(|vlan| matches!(vlan.private_vlan, Some(PrivateVlan::Primary(_)))) {
+      return Err(Error::new(ErrorKind::InvalidInput, format!("Vlan {} is not a primary private vlan", primary)));
+    }
+    for secondary in secondaries {
+      if !self.vlans.get(secondary).is_some_and(|vlan| matches!(vlan.private_vlan, Some(PrivateVlan::Isolated | PrivateVlan::Community))) {
+        return Err(Error::new(ErrorKind::InvalidInput, format!("Vlan {} is not an isolated or community private vlan", secondary)));
+      }
+      if let Some(other) = self.primaries.get(secondary).map(|other| *other) && other != primary {
+        return Err(Error::new(ErrorKind::InvalidInput, format!("Vlan {} is already associated to primary vlan {}", secondary, other)));
+      }
+    }
+    self.update(primary, |vlan| if let Some(PrivateVlan::Primary(associated)) = &mut vlan.private_vlan {
+      associated.extend(secondaries);
+    })?;
+    for secondary in secondaries {
+      self.primaries.insert(*secondary, primary);
+    }
+    Ok(())
+  }
+
+  pub fn dissociate_private_vlans(&self, primary: u16) -> io::Result<()> {
+    self.update(primary, |vlan| if let Some(PrivateVlan::Primary(associated)) = &mut vlan.private_vlan {
+      associated.clear();
+    })?;
+    self.primaries.retain(|_, other| *other != primary);
+    Ok(())
+  }
+
+  fn clear_private_vlan(&self, id: u16) {
+    self.primaries.retain(|secondary, primary| *secondary != id && *primary != id);
+    for mut vlan in self.vlans.iter_mut() {
+      if let Some(PrivateVlan::Primary(associated)) = &mut vlan.private_vlan {
+        associated.remove(&id);
+      }
+    }
+  }
+
+  // Primary vlan of an associated secondary vlan, the vlan itself otherwise.
+  // Addresses of a private vlan domain are learnt in its primary vlan.
+  pub fn primary_vlan(&self, id: u16) -> u16 {
+    self.primaries.get(&id).map_or(id, |primary| *primary)
+  }
+
+  pub fn get_private_vlan(&self, id: u16) -> Option<PrivateVlan> {
+    self.vlans.get(&id).and_then(|vlan| vlan.private_vlan.clone())
+  }
+
+  // Whether frame of vlan may egress on interface. Isolated hosts only reach
+  // promiscuous and trunk ports, other restrictions are enforced by the
+  // vlans allowed on private vlan ports.
+  pub fn private_vlan_allows(&self, id: u16, egr_intf: &InterfaceView) -> bool {
+    !(egr_intf.is_private_vlan_host()
+      && self.vlans.get(&id).is_some_and(|vlan| vlan.private_vlan == Some(PrivateVlan::Isolated)))
+  }
+
+  pub fn count_in(&self, id: u16, bytes: usize) {
+    let counters = &self.counters[id as usize];
+    counters.in_pkts.fetch_add(1, Ordering::Relaxed);
+    counters.in_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+  }
+
+  pub fn count_out(&self, id: u16, bytes: usize) {
+    let counters = &self.counters[id as usize];
+    counters.out_pkts.fetch_add(1, Ordering::Relaxed);
+    counters.out_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+  }
+
+  pub fn count_forwarded(&self, id: u16) {
+    self.counters[id as usize].forwarded.fetch_add(1, Ordering::Relaxed);
+  }
+
+  pub fn count_flooded(&self, id: u16) {
+    self.counters[id as usize].flooded.fetch_add(1, Ordering::Relaxed);
+  }
+
+  pub fn reset_counters(&self) {
+    for counters in self.counters.iter() {
+      counters.reset();
+    }
+  }
+
+  // Counters of all vlans sorted by id, or of given vlan
+  pub fn format_counters(&self, id: Option<u16>) -> io::Result<String> {
+    let ids: Vec<u16> = match id {
+      Some(id) if !self.vlans.contains_key(&id) => {
+        return Err(Error::new(ErrorKind::NotFound, format!("Vlan {} not found", id)));
+      },
+      Some(id) => vec![id],
+      None => {
+        let mut ids: Vec<u16> = self.vlans.iter().map(|vlan| *vlan.key()).collect();
+        ids.sort();
+        ids
+      }
+    };
+
+    let mut output = format!("{:<4} {:>12} {:>14} {:>12} {:>14} {:>12} {:>12}\n",
+      "VLAN", "In Pkts", "In Bytes", "Out Pkts", "Out Bytes", "Unicast", "Flooded");
+    for id in ids {
+      let counters = &self.counters[id as usize];
+      output += &format!("{:<4} {:>12} {:>14} {:>12} {:>14} {:>12} {:>12}\n", id,
+        counters.in_pkts.load(Ordering::Relaxed), counters.in_bytes.load(Ordering::Relaxed),
+        counters.out_pkts.load(Ordering::Relaxed), counters.out_bytes.load(Ordering::Relaxed),
+        counters.forwarded.load(Ordering::Relaxed), counters.flooded.load(Ordering::Relaxed));
+    }
+    Ok(output)
+  }
+
+  fn update<F: FnOnce(&mut Vlan)>(&self, id: u16, update: F) -> io::Result<()> {
+    let mut vlan = self.vlans.get_mut(&id)
+      .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("Vlan {} not found", id)))?;
+    update(&mut vlan);
+    Ok(())
+  }
+
+  // Whether frames of vlan can be switched
+  pub fn is_active(&self, id: u16) -> bool {
+    self.vlans.get(&id).is_some_and(|vlan| vlan.state == VlanState::Active)
+  }
+
+  // Vlans sorted by id along with their member interfaces
+  pub fn format(&self, intfs_view: &HashMap<&str, Arc<InterfaceView>>) -> String {
+    let mut ids: Vec<u16> = self.vlans.iter().map(|vlan| *vlan.key()).collect();
+    ids.sort();
+
+    let mut output = String::from("VLAN Name                             Status    Ports\n");
+    output += "---- -------------------------------- --------- -------------------------------\n";
+    for id in ids {
+      let Some(vlan) = self.vlans.get(&id).map(|vlan| vlan.clone()) else {
+        continue;
+      };
+      let mut ports: Vec<&str> = intfs_view.iter()
+        .filter(|(_, intf)| intf.is_vlan_member(id))
+        .map(|(name, _)| *name)
+        .collect();
+      ports.sort();
+      output += &format!("{:<4} {:<32} {:<9} {}\n", id, vlan.name, vlan.state.to_string(), ports.join(", "));
+    }
+    output
+  }
+
+  // Secondary vlans by primary vlan along with their host and promiscuous ports
+  pub fn format_private_vlans(&self, intfs_view: &HashMap<&str, Arc<InterfaceView>>) -> String {
+    let mut primaries: Vec<(u16, BTreeSet<u16>)> = self.vlans.iter()
+      .filter_map(|vlan| match &vlan.private_vlan {
+        Some(PrivateVlan::Primary(associated)) => Some((*vlan.key(), associated.clone())),
+        _ => None,
+      })
+      .collect();
+    primaries.sort();
+
+    let mut output = String::from("Primary Secondary Type              Ports\n");
+    output += "------- --------- ----------------- -------------------------------\n";
+    for (primary, associated) in primaries {
+      for secondary in associated {
+        let kind = self.get_private_vlan(secondary).map_or(String::new(), |kind| kind.to_string());
+        let mut ports: Vec<&str> = intfs_view.iter()
+          .filter(|(_, intf)| match intf.get_port_mode() {
+            PortMode::PrivateVlanHost{primary: port_primary, secondary: port_secondary} =>
+              port_primary == primary && port_secondary == secondary,
+            PortMode::PrivateVlanPromiscuous{primary: port_primary, secondaries} =>
+              port_primary == primary && secondaries.contains(&secondary),
+            _ => false,
+          })
+          .map(|(name, _)| *name)
+          .collect();
+        ports.sort();
+        output += &format!("{:<7} {:<9} {:<17} {}\n", primary, secondary, kind, ports.join(", "));
+      }
+    }
+    output
+  }
+}
+
+impl fmt::Display for PrivateVlan {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}",
+    match self {
+      PrivateVlan::Primary(_) => "primary",
+      PrivateVlan::Isolated => "isolated",
+      PrivateVlan::Community => "community",
+    })
+  }
+}
+
+impl VlanCounters {
+  fn reset(&self) {
+    self.in_pkts.store(0, Ordering::Relaxed);
+    self.in_bytes.store(0, Ordering::Relaxed);
+    self.out_pkts.store(0, Ordering::Relaxed);
+    self.out_bytes.store(0, Ordering::Relaxed);
+    self.forwarded.store(0, Ordering::Relaxed);
+    self.flooded.store(0, Ordering::Relaxed);
+  }
+}
+
+impl fmt::Display for VlanState {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}",
+    match self {
+      VlanState::Active => "active",
+      VlanState::Suspend => "suspend",
+    })
+  }
+}
+
+// Compact rendering of a vlan set, e.g. 10-20,30
+pub fn format_vlan_list<'a, I: IntoIterator<Item = &'a u16>>(vlans: I) -> String {
+  let mut vlans: Vec<u16> = vlans.into_iter().copied().collect();
+  vlans.sort_unstable();
+  vlans.dedup();
+
+  let mut ranges: Vec<String> = Vec::new();
+  let mut i = 0;
+  while i < vlans.len() {
+    let first = vlans[i];
+    while i + 1 < vlans.len() && vlans[i + 1] == vlans[i] + 1 {
+      i += 1;
+    }
+    ranges.push(if vlans[i] == first { first.to_string() } else { format!("{}-{}", first, vlans[i]) });
+    i += 1;
+  }
+  if ranges.is_empty() { "none".to_string() } else { ranges.join(",") }
+}
@@ -0,0 +1,77 @@
+#!/usr/bin/env python3
+
+from utils import *
+
+import sys
+
+fib_file = "/tmp/fib_persist_test.txt"
+
+def start_switch(options):
+  switch = Switch(options)
+  time.sleep(1)
+  return switch
+
+class TestFibPersist:
+  def test_fib_save_restore(self):
+    print("\nTest FIB saved and restored across restarts")
+    run_cmd_on_host("sw", f"rm -f {fib_file}")
+    switch = start_switch([])
+    switch.send_cmds([
+      f"mac address-table static {hosts[2].mac} vlan 1 interface if3-sw",
+      f"mac address-table static {hosts[4].mac} vlan 1 drop",
+      f"fib save {fib_file}",
+    ])
+    assert f"Saved 2 FIB entries to {fib_file}" in switch.read_output()
+    switch.terminate()
+
+    switch = start_switch(["--fib-restore", fib_file])
+    assert f"Restored 2 FIB entries from {fib_file}" in switch.read_output()
+    switch.send_cmds([
+      f"show fib address {hosts[2].mac}",
+      f"show fib address {hosts[4].mac}",
+    ])
+    output = switch.read_output()
+    assert "if3-sw" in output
+    assert "static" in output
+    assert "drop" in output
+
+    # Static entry restored still pins host2 on if3-sw
+    frame = Ether(src=hosts[1].mac, dst=hosts[2].mac)/IP(dst=hosts[2].ip, src=hosts[1].ip)/ICMP()
+    exps = [
+      expect_frame(hosts[2], frame, failure=True),
+      expect_frame(hosts[3], frame),
+    ]
+
+    send_frame(hosts[1], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    switch.terminate()
+
+  def test_fib_restore_missing_file(self):
+    print("\nTest switch starts with an empty FIB when restore file is missing")
+    run_cmd_on_host("sw", f"rm -f {fib_file}")
+    switch = start_switch(["--fib-restore", fib_file])
+    assert f"Error: unable to restore FIB from {fib_file}" in switch.read_output()
+    switch.send_cmds([
+      "show fib count",
+    ])
+    assert "Total: 0" in switch.read_output()
+    switch.terminate()
+
+  def test_fib_restore_malformed_file(self):
+    print("\nTest nothing is restored from a malformed file")
+    run_cmd_on_host("sw", f"sh -c \"printf '1 {hosts[2].mac} static if3-sw 0\\nnot a fib entry\\n' > {fib_file}\"")
+    switch = start_switch(["--fib-restore", fib_file])
+    output = switch.read_output()
+    assert f"{fib_file}:2: invalid FIB entry" in output
+    switch.send_cmds([
+      "show fib count",
+    ])
+    assert "Total: 0" in switch.read_output()
+    switch.terminate()
+    run_cmd_on_host("sw", f"rm -f {fib_file}")
+
+if __name__ == "__main__":
+  sys.exit(pytest.main([__file__, "-v", "-s"]))
@@ -0,0 +1,316 @@
+#!/usr/bin/env python3
+
+from utils import *
+
+import sys
+
+class TestFib:
+  def test_fib_aging(self, ctx):
+    print("\nTest FIB entry aging")
+    ctx["switch"].send_cmds([
+      "mac address-table aging-time 2",
+    ])
+
+    # Learn host2 on if2-sw
+    frame = Ether(src=hosts[2].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[2].mac, hwdst="00:00:00:00:00:00", pdst=hosts[1].ip, psrc=hosts[2].ip)
+    exps = [
+      expect_frame(hosts[1], frame),
+    ]
+
+    send_frame(hosts[2], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    time.sleep(4)
+
+    # Entry aged out, frame to host2 is flooded again
+    frame = Ether(src=hosts[1].mac, dst=hosts[2].mac)/IP(dst=hosts[2].ip, src=hosts[1].ip)/ICMP()
+    exps = [
+      expect_frame(hosts[2], frame),
+      expect_frame(hosts[3], frame),
+      expect_frame(hosts[4], frame)
+    ]
+
+    send_frame(hosts[1], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    ctx["switch"].send_cmds([
+      "no mac address-table aging-time",
+    ])
+
+  def test_fib_static(self, ctx):
+    print("\nTest static FIB entry")
+    ctx["switch"].send_cmds([
+      f"mac address-table static {hosts[2].mac} vlan 1 interface if3-sw",
+    ])
+
+    # host2 is pinned on if3-sw and learning does not move it
+    frame = Ether(src=hosts[2].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[2].mac, hwdst="00:00:00:00:00:00", pdst=hosts[1].ip, psrc=hosts[2].ip)
+    send_frame(hosts[2], frame)
+
+    frame = Ether(src=hosts[1].mac, dst=hosts[2].mac)/IP(dst=hosts[2].ip, src=hosts[1].ip)/ICMP()
+    exps = [
+      expect_frame(hosts[2], frame, failure=True),
+      expect_frame(hosts[3], frame),
+      expect_frame(hosts[4], frame, failure=True)
+    ]
+
+    send_frame(hosts[1], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    ctx["switch"].send_cmds([
+      f"no mac address-table static {hosts[2].mac} vlan 1",
+    ])
+
+  def test_fib_drop(self, ctx):
+    print("\nTest drop FIB entry")
+    ctx["switch"].send_cmds([
+      f"mac address-table static {hosts[4].mac} vlan 1 drop",
+    ])
+
+    # Traffic to blackholed MAC
+    frame = Ether(src=hosts[1].mac, dst=hosts[4].mac)/IP(dst=hosts[4].ip, src=hosts[1].ip)/ICMP()
+    exps = [
+      expect_frame(hosts[2], frame, failure=True),
+      expect_frame(hosts[3], frame, failure=True),
+      expect_frame(hosts[4], frame, failure=True)
+    ]
+
+    send_frame(hosts[1], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    # Traffic from blackholed MAC
+    frame = Ether(src=hosts[4].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[4].mac, hwdst="00:00:00:00:00:00", pdst=hosts[1].ip, psrc=hosts[4].ip)
+    exps = [
+      expect_frame(hosts[1], frame, failure=True),
+      expect_frame(hosts[2], frame, failure=True),
+      expect_frame(hosts[3], frame, failure=True)
+    ]
+
+    send_frame(hosts[4], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    ctx["switch"].send_cmds([
+      f"no mac address-table static {hosts[4].mac} vlan 1",
+    ])
+
+  def test_fib_clear(self, ctx):
+    print("\nTest FIB clear")
+
+    # Learn host2 on if2-sw
+    frame = Ether(src=hosts[2].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[2].mac, hwdst="00:00:00:00:00:00", pdst=hosts[1].ip, psrc=hosts[2].ip)
+    send_frame(hosts[2], frame)
+
+    ctx["switch"].read_output()
+    ctx["switch"].send_cmds([
+      f"show fib address {hosts[2].mac}",
+    ])
+    assert "if2-sw" in ctx["switch"].read_output()
+
+    ctx["switch"].send_cmds([
+      "clear fib interface if2-sw",
+    ])
+
+    frame = Ether(src=hosts[1].mac, dst=hosts[2].mac)/IP(dst=hosts[2].ip, src=hosts[1].ip)/ICMP()
+    exps = [
+      expect_frame(hosts[2], frame),
+      expect_frame(hosts[3], frame),
+      expect_frame(hosts[4], frame)
+    ]
+
+    send_frame(hosts[1], frame)
+
+    for exp in exps:
+      exp.receive()
+
+  def test_fib_monitor(self, ctx):
+    print("\nTest FIB events monitoring")
+    ctx["switch"].send_cmds([
+      "clear fib",
+      "terminal monitor fib",
+    ])
+    ctx["switch"].read_output()
+
+    frame = Ether(src=hosts[3].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[3].mac, hwdst="00:00:00:00:00:00", pdst=hosts[1].ip, psrc=hosts[3].ip)
+    send_frame(hosts[3], frame)
+    time.sleep(0.2)
+
+    assert f"learned {hosts[3].mac} vlan 1 on if3-sw" in ctx["switch"].read_output().lower()
+
+    ctx["switch"].send_cmds([
+      "no terminal monitor fib",
+    ])
+
+  def test_fib_learning_group(self, ctx):
+    print("\nTest FIB shared vlan learning group")
+    ctx["switch"].send_cmds([
+      "clear fib",
+      "mac address-table learning-group shared vlans 1,10",
+      "interface if2-sw",
+      "switchport access vlan 10",
+      "exit",
+    ])
+
+    # host2 learned in vlan 10 is known in vlan 1 as well
+    frame = Ether(src=hosts[2].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[2].mac, hwdst="00:00:00:00:00:00", pdst=hosts[1].ip, psrc=hosts[2].ip)
+    send_frame(hosts[2], frame)
+    time.sleep(0.2)
+
+    ctx["switch"].read_output()
+    ctx["switch"].send_cmds([
+      "show fib vlan 1",
+    ])
+    assert hosts[2].mac.lower() in ctx["switch"].read_output().lower()
+
+    ctx["switch"].send_cmds([
+      "no mac address-table learning-group shared",
+      "interface if2-sw",
+      "switchport access vlan 1",
+      "exit",
+    ])
+
+  def test_fib_hits(self, ctx):
+    print("\nTest FIB entry forwarding hits and last use")
+    ctx["switch"].send_cmds([
+      "clear fib",
+    ])
+
+    # Learn host2 on if2-sw, entry is not used yet
+    frame = Ether(src=hosts[2].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[2].mac, hwdst="00:00:00:00:00:00", pdst=hosts[1].ip, psrc=hosts[2].ip)
+    send_frame(hosts[2], frame)
+
+    def host2_entry():
+      ctx["switch"].read_output()
+      ctx["switch"].send_cmds([
+        "show fib detail",
+      ])
+      lines = [line for line in ctx["switch"].read_output().lower().splitlines() if hosts[2].mac in line]
+      assert len(lines) == 1
+      return lines[0].split()
+
+    # vlan, mac, type, interface, age, hits, last use
+    entry = host2_entry()
+    assert entry[3] == "if2-sw"
+    assert entry[5:] == ["0", "never"]
+
+    frame = Ether(src=hosts[1].mac, dst=hosts[2].mac)/IP(dst=hosts[2].ip, src=hosts[1].ip)/ICMP()
+    for _ in range(3):
+      exps = [
+        expect_frame(hosts[2], frame),
+      ]
+
+      send_frame(hosts[1], frame)
+
+      for exp in exps:
+        exp.receive()
+
+    entry = host2_entry()
+    assert entry[5] == "3"
+    assert entry[6] != "never" and int(entry[6].rstrip("s")) < 5
+
+  def test_fib_limit(self, ctx):
+    print("\nTest FIB capacity limit with refuse and evict-lru overflow policies")
+    ctx["switch"].send_cmds([
+      "clear fib",
+      "counters reset",
+      "mac address-table limit 2",
+      "mac address-table limit action refuse",
+    ])
+
+    for host in hosts[1:4]:
+      frame = Ether(src=host.mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=host.mac, hwdst="00:00:00:00:00:00", pdst=hosts[5].ip, psrc=host.ip)
+      send_frame(host, frame)
+
+    # host3 is not learned, table is full
+    ctx["switch"].read_output()
+    ctx["switch"].send_cmds([
+      "show fib count",
+      f"show fib address {hosts[3].mac}",
+    ])
+    output = ctx["switch"].read_output()
+    assert "Learn failures (table full): 1, Evictions: 0" in output
+    assert "Total: 2" in output
+    assert "if3-sw" not in output
+
+    ctx["switch"].send_cmds([
+      "mac address-table limit action evict-lru",
+    ])
+    frame = Ether(src=hosts[4].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[4].mac, hwdst="00:00:00:00:00:00", pdst=hosts[5].ip, psrc=hosts[4].ip)
+    send_frame(hosts[4], frame)
+
+    # host1, least recently seen, makes room for host4
+    ctx["switch"].read_output()
+    ctx["switch"].send_cmds([
+      "show fib count",
+      f"show fib address {hosts[1].mac}",
+      f"show fib address {hosts[4].mac}",
+    ])
+    output = ctx["switch"].read_output()
+    assert "Learn failures (table full): 1, Evictions: 1" in output
+    assert "Total: 2" in output
+    assert "if1-sw" not in output
+    assert "if4-sw" in output
+
+    ctx["switch"].send_cmds([
+      "no mac address-table limit",
+      "mac address-table limit action refuse",
+      "counters reset",
+    ])
+
+  def test_fib_mac_moves(self, ctx):
+    print("\nTest MAC moves log and flap dampening")
+    ctx["switch"].send_cmds([
+      "clear fib",
+      "mac address-table learning-group shared vlans 1,10",
+      "mac address-table move-detection threshold 2 window 10",
+      "mac address-table move-detection action suppress 30",
+      "interface if3-sw",
+      "switchport access vlan 10",
+      "exit",
+    ])
+
+    # host2 address moves back and forth between if2-sw (vlan 1) and if3-sw (vlan 10)
+    frame = Ether(src=hosts[2].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[2].mac, hwdst="00:00:00:00:00:00", pdst=hosts[1].ip, psrc=hosts[2].ip)
+    for host in [hosts[2], hosts[3], hosts[2], hosts[3]]:
+      send_frame(host, frame)
+
+    ctx["switch"].read_output()
+    ctx["switch"].send_cmds([
+      "show mac address-table moves",
+    ])
+    output = ctx["switch"].read_output().lower()
+    moves = [line for line in output.splitlines() if hosts[2].mac in line]
+    assert len(moves) == 3
+    # Vlan of the frame is logged, not the learning group filtering database
+    assert moves[0].startswith(f"10     {hosts[2].mac}")
+    assert moves[1].startswith(f"1      {hosts[2].mac}")
+
+    # Third move within the window suppressed learning, this one is not recorded
+    send_frame(hosts[2], frame)
+
+    ctx["switch"].read_output()
+    ctx["switch"].send_cmds([
+      "show mac address-table moves",
+    ])
+    output = ctx["switch"].read_output().lower()
+    assert len([line for line in output.splitlines() if hosts[2].mac in line]) == 3
+
+    ctx["switch"].send_cmds([
+      "no mac address-table move-detection",
+      "no mac address-table learning-group shared",
+      "interface if3-sw",
+      "switchport access vlan 1",
+      "exit",
+    ])
+
+if __name__ == "__main__":
+  sys.exit(pytest.main([__file__, "-v", "-s"]))
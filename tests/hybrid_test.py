@@ -0,0 +1,80 @@
+#!/usr/bin/env python3
+
+from utils import *
+
+import sys
+
+class TestHybrid:
+  def test_hybrid_untagged_ingress(self, ctx):
+    print("\nTest hybrid untagged ingress in PVID")
+    ctx["switch"].send_cmds([
+      "interface if5-sw",
+      "switchport mode hybrid",
+      "switchport hybrid pvid 20",
+      "switchport hybrid untagged vlans 20,33",
+      "switchport hybrid tagged vlans 42",
+      "exit",
+      "interface if1-sw",
+      "switchport access vlan 20",
+      "exit",
+      "interface if2-sw",
+      "switchport access vlan 33",
+      "exit",
+      "interface if3-sw",
+      "switchport access vlan 42",
+      "exit"
+    ])
+
+    frame = Ether(src=hosts[5].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[5].mac, hwdst="00:00:00:00:00:00", pdst=hosts[1].ip, psrc=hosts[5].ip)
+    exps = [
+     expect_frame(hosts[1], frame),
+     expect_frame(hosts[2], frame, failure=True),
+     expect_frame(hosts[3], frame, failure=True),
+     expect_frame(hosts[4], frame, failure=True)
+    ]
+
+    send_frame(hosts[5], frame)
+
+    for exp in exps:
+      exp.receive()
+
+  def test_hybrid_tagged_ingress(self, ctx):
+    print("\nTest hybrid tagged ingress")
+    frame = Ether(src=hosts[5].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[5].mac, hwdst="00:00:00:00:00:00", pdst=hosts[2].ip, psrc=hosts[5].ip)
+    exps = [
+     expect_frame(hosts[1], frame, failure=True),
+     expect_frame(hosts[2], frame),
+     expect_frame(hosts[3], frame, failure=True),
+     expect_frame(hosts[4], frame, failure=True)
+    ]
+
+    send_frame(hosts[5], frame, vlan=33)
+
+    for exp in exps:
+      exp.receive()
+
+  def test_hybrid_egress(self, ctx):
+    print("\nTest hybrid untagged and tagged egress")
+    frame = Ether(src=hosts[2].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[2].mac, hwdst="00:00:00:00:00:00", pdst=hosts[5].ip, psrc=hosts[2].ip)
+    exps = [
+     expect_frame(hosts[5], frame),
+     expect_frame(hosts[5], frame, vlan=33, failure=True)
+    ]
+
+    send_frame(hosts[2], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    frame = Ether(src=hosts[3].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[3].mac, hwdst="00:00:00:00:00:00", pdst=hosts[5].ip, psrc=hosts[3].ip)
+    exps = [
+     expect_frame(hosts[5], frame, vlan=42)
+    ]
+
+    send_frame(hosts[3], frame)
+
+    for exp in exps:
+      exp.receive()
+
+if __name__ == "__main__":
+  sys.exit(pytest.main([__file__, "-v", "-s"]))
@@ -0,0 +1,66 @@
+#!/usr/bin/env python3
+
+from utils import *
+
+import struct
+import sys
+
+def lacp_info(kind, system, key, port, state):
+  return struct.pack("!BBH6sHHHB3x", kind, 20, 32768, bytes.fromhex(system), key, 32768, port, state)
+
+def lacpdu(host, state):
+  # Active partner system 02:00:00:00:00:bb, key 1, port 1
+  data = b"\x01\x01" + lacp_info(1, "0200000000bb", 1, 1, state) + lacp_info(2, "000000000000", 0, 0, 0)
+  data += b"\x03\x10" + bytes(14) + b"\x00\x00"
+  data += bytes(110 - len(data))
+  return Ether(src=host.mac, dst="01:80:c2:00:00:02", type=0x8809)/Raw(data)
+
+class TestLacp:
+  def test_lacp_neighbor(self, ctx):
+    print("\nTest LACP partner is learned on channel group member and LACPDU not flooded")
+    ctx["switch"].send_cmds([
+      "interface if1-sw",
+      "channel-group 1 mode active",
+      "exit"
+    ])
+    # Activity, aggregation, synchronization, collecting and distributing
+    frame = lacpdu(hosts[1], 0x3d)
+    exps = [
+     expect_frame(hosts[2], frame, failure=True),
+     expect_frame(hosts[3], frame, failure=True)
+    ]
+
+    send_frame(hosts[1], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    ctx["switch"].read_output()
+    ctx["switch"].send_cmds([
+      "show lacp neighbor"
+    ])
+    output = ctx["switch"].read_output()
+    assert "Channel group 1 (port-channel1) neighbors" in output
+    assert "32768,02:00:00:00:00:BB" in output.upper()
+
+  def test_etherchannel_summary(self, ctx):
+    print("\nTest channel group member is listed in etherchannel summary")
+    ctx["switch"].read_output()
+    ctx["switch"].send_cmds([
+      "port-channel load-balance src-dst-ip",
+      "show etherchannel summary"
+    ])
+    output = ctx["switch"].read_output()
+    assert "Load balance: src-dst-ip" in output
+    assert "port-channel1" in output
+    assert "if1-sw(" in output
+
+    ctx["switch"].send_cmds([
+      "port-channel load-balance src-dst-mac",
+      "interface if1-sw",
+      "no channel-group",
+      "exit"
+    ])
+
+if __name__ == "__main__":
+  sys.exit(pytest.main([__file__, "-v", "-s"]))
@@ -0,0 +1,62 @@
+#!/usr/bin/env python3
+
+from utils import *
+
+import struct
+import sys
+
+def tlv(kind, value):
+  return struct.pack("!H", kind << 9 | len(value)) + value
+
+def lldpdu(host, ttl):
+  # Neighbour advertising chassis 02:00:00:00:00:aa, port eth0 and system name neighbor1
+  data = tlv(1, b"\x04" + bytes.fromhex("0200000000aa")) + tlv(2, b"\x05eth0") + tlv(3, struct.pack("!H", ttl))
+  data += tlv(5, b"neighbor1") + tlv(127, b"\x00\x80\xc2\x01" + struct.pack("!H", 10)) + tlv(0, b"")
+  return Ether(src=host.mac, dst="01:80:c2:00:00:0e", type=0x88cc)/Raw(data)
+
+class TestLldp:
+  def test_lldp_neighbors(self, ctx):
+    print("\nTest LLDP neighbor is learned and not flooded")
+    ctx["switch"].send_cmds([
+      "lldp run"
+    ])
+    frame = lldpdu(hosts[1], 120)
+    exps = [
+     expect_frame(hosts[2], frame, failure=True),
+     expect_frame(hosts[3], frame, failure=True)
+    ]
+
+    send_frame(hosts[1], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    ctx["switch"].read_output()
+    ctx["switch"].send_cmds([
+      "show lldp neighbors detail"
+    ])
+    output = ctx["switch"].read_output()
+    assert "Local Intf: if1-sw" in output
+    assert "Chassis ID: 02:00:00:00:00:AA" in output.upper()
+    assert "System Name: neighbor1" in output
+    assert "Port VLAN ID: 10" in output
+
+  def test_lldp_shutdown(self, ctx):
+    print("\nTest LLDP neighbor is removed on zero TTL")
+    send_frame(hosts[1], lldpdu(hosts[1], 0))
+    time.sleep(1)
+
+    ctx["switch"].read_output()
+    ctx["switch"].send_cmds([
+      "show lldp neighbors"
+    ])
+    output = ctx["switch"].read_output()
+    assert "neighbor1" not in output
+    assert "Total entries displayed: 0" in output
+
+    ctx["switch"].send_cmds([
+      "no lldp run"
+    ])
+
+if __name__ == "__main__":
+  sys.exit(pytest.main([__file__, "-v", "-s"]))
@@ -0,0 +1,121 @@
+#!/usr/bin/env python3
+
+from utils import *
+
+import hashlib
+import hmac
+import sys
+
+REGION = "lab"
+REVISION = 1
+INSTANCES = {1: 33, 2: 42}
+
+def bridge_id(priority, mac):
+  return priority.to_bytes(2, "big") + bytes.fromhex(mac.replace(":", ""))
+
+def mst_bpdu(host, bridgemac, mstis):
+  # MST BPDU from designated port of a neighbour bridge of the region, CIST root is 0.00:00:00:00:00:01
+  table = bytearray(8192)
+  for instance, vlan in INSTANCES.items():
+    table[vlan * 2:vlan * 2 + 2] = instance.to_bytes(2, "big")
+  digest = hmac.new(bytes.fromhex("13ac06a62e47fd51f95d2ba243cd0346"), bytes(table), hashlib.md5).digest()
+
+  root = bridge_id(0, "00:00:00:00:00:01")
+  bpdu = b"\x00\x00\x03\x02\x3c" + root + (0).to_bytes(4, "big") + root + b"\x80\x01"
+  bpdu += b"".join((seconds * 256).to_bytes(2, "big") for seconds in [1, 20, 5, 15])
+  bpdu += b"\x00" + (64 + 16 * len(mstis)).to_bytes(2, "big")
+  bpdu += b"\x00" + REGION.encode().ljust(32, b"\x00") + REVISION.to_bytes(2, "big") + digest
+  bpdu += (0).to_bytes(4, "big") + bridge_id(0x8000, bridgemac) + b"\x14"
+  for instance, (rootmac, cost) in mstis.items():
+    bpdu += b"\x3c" + bridge_id(0x1000 | instance, rootmac) + cost.to_bytes(4, "big") + b"\x80\x80\x14"
+  return Dot3(src=host.mac, dst="01:80:c2:00:00:00")/LLC(dsap=0x42, ssap=0x42, ctrl=3)/Raw(bpdu)
+
+def send_bpdus():
+  # host1 bridge is regional root of instance 1, host2 bridge of instance 2
+  send_frame(hosts[1], mst_bpdu(hosts[1], "00:00:00:00:00:01", {1: ("00:00:00:00:00:01", 0), 2: ("00:00:00:00:00:02", 20000)}))
+  send_frame(hosts[2], mst_bpdu(hosts[2], "00:00:00:00:00:02", {1: ("00:00:00:00:00:01", 20000), 2: ("00:00:00:00:00:02", 0)}))
+
+def port_roles(output):
+  return {line.split()[0]: line.split()[1:3] for line in output.splitlines() if line.startswith("if")}
+
+class TestMstp:
+  def test_mstp_roles(self, ctx):
+    print("\nTest multiple spanning tree roles per instance")
+    ctx["switch"].send_cmds([
+      "interface if1-sw",
+      "switchport mode trunk",
+      "switchport trunk vlans add 33,42",
+      "exit",
+      "interface if2-sw",
+      "switchport mode trunk",
+      "switchport trunk vlans add 33,42",
+      "exit",
+      "interface if3-sw",
+      "switchport access vlan 33",
+      "exit",
+      "interface if4-sw",
+      "switchport access vlan 42",
+      "exit",
+      "spanning-tree mode mst",
+      f"spanning-tree mst name {REGION}",
+      f"spanning-tree mst revision {REVISION}",
+      "spanning-tree mst instance 1 vlan 33",
+      "spanning-tree mst instance 2 vlan 42",
+      "spanning-tree hello-time 5",
+      "spanning-tree forward-time 4",
+      "spanning-tree"
+    ])
+
+    send_bpdus()
+
+    # Wait for designated ports forward delay
+    time.sleep(9)
+    send_bpdus()
+
+    ctx["switch"].read_output()
+    ctx["switch"].send_cmds([
+      "show spanning-tree mst 1"
+    ])
+    roles = port_roles(ctx["switch"].read_output())
+    assert roles["if1-sw"] == ["Root", "forwarding"]
+    assert roles["if2-sw"] == ["Alternate", "discarding"]
+
+    ctx["switch"].send_cmds([
+      "show spanning-tree mst 2"
+    ])
+    roles = port_roles(ctx["switch"].read_output())
+    assert roles["if1-sw"] == ["Alternate", "discarding"]
+    assert roles["if2-sw"] == ["Root", "forwarding"]
+
+  def test_mstp_blocking_per_vlan(self, ctx):
+    print("\nTest multiple spanning tree blocks each vlan on its instance alternate port")
+    frame = Ether(src=hosts[3].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[3].mac, hwdst="00:00:00:00:00:00", pdst=hosts[1].ip, psrc=hosts[3].ip)
+    exp_frame = Ether(src=hosts[3].mac, dst="ff:ff:ff:ff:ff:ff")/Dot1Q(vlan=33)/ARP(hwsrc=hosts[3].mac, hwdst="00:00:00:00:00:00", pdst=hosts[1].ip, psrc=hosts[3].ip)
+    exps = [
+     expect_frame(hosts[1], exp_frame),
+     expect_frame(hosts[2], exp_frame, failure=True),
+    ]
+
+    send_frame(hosts[3], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    frame = Ether(src=hosts[4].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[4].mac, hwdst="00:00:00:00:00:00", pdst=hosts[2].ip, psrc=hosts[4].ip)
+    exp_frame = Ether(src=hosts[4].mac, dst="ff:ff:ff:ff:ff:ff")/Dot1Q(vlan=42)/ARP(hwsrc=hosts[4].mac, hwdst="00:00:00:00:00:00", pdst=hosts[2].ip, psrc=hosts[4].ip)
+    exps = [
+     expect_frame(hosts[1], exp_frame, failure=True),
+     expect_frame(hosts[2], exp_frame),
+    ]
+
+    send_frame(hosts[4], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    ctx["switch"].send_cmds([
+      "no spanning-tree"
+    ])
+
+if __name__ == "__main__":
+  sys.exit(pytest.main([__file__, "-v", "-s"]))
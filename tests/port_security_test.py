@@ -0,0 +1,80 @@
+#!/usr/bin/env python3
+
+from utils import *
+
+import sys
+
+class TestPortSecurity:
+  def test_port_security_maximum(self, ctx):
+    print("\nTest port security maximum")
+    ctx["switch"].send_cmds([
+      "interface if1-sw",
+      "switchport port-security maximum 1",
+      "switchport port-security violation restrict",
+      "exit"
+    ])
+
+    frame = Ether(src=hosts[1].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[1].mac, hwdst="00:00:00:00:00:00", pdst=hosts[2].ip, psrc=hosts[1].ip)
+    exps = [
+      expect_frame(hosts[2], frame),
+      expect_frame(hosts[3], frame),
+      expect_frame(hosts[4], frame)
+    ]
+
+    send_frame(hosts[1], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    # Second MAC address on the port is over the limit
+    frame = Ether(src="aa:aa:aa:aa:aa:01", dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc="aa:aa:aa:aa:aa:01", hwdst="00:00:00:00:00:00", pdst=hosts[2].ip, psrc=hosts[1].ip)
+    exps = [
+      expect_frame(hosts[2], frame, failure=True),
+      expect_frame(hosts[3], frame, failure=True),
+      expect_frame(hosts[4], frame, failure=True)
+    ]
+
+    send_frame(hosts[1], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    ctx["switch"].send_cmds([
+      "interface if1-sw",
+      "no switchport port-security",
+      "exit"
+    ])
+
+  def test_port_security_sticky(self, ctx):
+    print("\nTest sticky MAC addresses are flushed with port security")
+    ctx["switch"].send_cmds([
+      "clear fib",
+      "interface if1-sw",
+      "switchport port-security maximum 2",
+      "switchport port-security mac-address sticky",
+      "exit"
+    ])
+
+    frame = Ether(src=hosts[1].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[1].mac, hwdst="00:00:00:00:00:00", pdst=hosts[2].ip, psrc=hosts[1].ip)
+    send_frame(hosts[1], frame)
+    time.sleep(0.2)
+
+    ctx["switch"].read_output()
+    ctx["switch"].send_cmds([
+      f"show fib address {hosts[1].mac}",
+    ])
+    output = ctx["switch"].read_output()
+    assert "sticky" in output
+    assert "if1-sw" in output
+
+    ctx["switch"].send_cmds([
+      "interface if1-sw",
+      "no switchport port-security",
+      "exit",
+      f"show fib address {hosts[1].mac}",
+    ])
+    output = ctx["switch"].read_output()
+    assert "if1-sw" not in output
+
+if __name__ == "__main__":
+  sys.exit(pytest.main([__file__, "-v", "-s"]))
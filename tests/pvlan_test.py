@@ -0,0 +1,111 @@
+#!/usr/bin/env python3
+
+from utils import *
+
+import sys
+
+class TestPrivateVlan:
+  def test_pvlan_isolated(self, ctx):
+    print("\nTest private vlan isolated host")
+    ctx["switch"].send_cmds([
+      "vlan 100",
+      "private-vlan primary",
+      "exit",
+      "vlan 101",
+      "private-vlan isolated",
+      "exit",
+      "vlan 102",
+      "private-vlan community",
+      "exit",
+      "vlan 100",
+      "private-vlan association 101-102",
+      "exit",
+      "interface if1-sw",
+      "switchport private-vlan mapping 100 101,102",
+      "exit",
+      "interface if2-sw",
+      "switchport private-vlan host-association 100 101",
+      "exit",
+      "interface if3-sw",
+      "switchport private-vlan host-association 100 101",
+      "exit",
+      "interface if4-sw",
+      "switchport private-vlan host-association 100 102",
+      "exit",
+      "interface if5-sw",
+      "switchport private-vlan host-association 100 102",
+      "exit"
+    ])
+
+    frame = Ether(src=hosts[2].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[2].mac, hwdst="00:00:00:00:00:00", pdst=hosts[1].ip, psrc=hosts[2].ip)
+    exps = [
+     expect_frame(hosts[1], frame),
+     expect_frame(hosts[3], frame, failure=True),
+     expect_frame(hosts[4], frame, failure=True),
+     expect_frame(hosts[5], frame, failure=True)
+    ]
+
+    send_frame(hosts[2], frame)
+
+    for exp in exps:
+      exp.receive()
+
+  def test_pvlan_community(self, ctx):
+    print("\nTest private vlan community host")
+    frame = Ether(src=hosts[4].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[4].mac, hwdst="00:00:00:00:00:00", pdst=hosts[1].ip, psrc=hosts[4].ip)
+    exps = [
+     expect_frame(hosts[1], frame),
+     expect_frame(hosts[2], frame, failure=True),
+     expect_frame(hosts[3], frame, failure=True),
+     expect_frame(hosts[5], frame)
+    ]
+
+    send_frame(hosts[4], frame)
+
+    for exp in exps:
+      exp.receive()
+
+  def test_pvlan_promiscuous(self, ctx):
+    print("\nTest private vlan promiscuous port")
+    frame = Ether(src=hosts[1].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[1].mac, hwdst="00:00:00:00:00:00", pdst=hosts[2].ip, psrc=hosts[1].ip)
+    exps = [
+     expect_frame(hosts[2], frame),
+     expect_frame(hosts[3], frame),
+     expect_frame(hosts[4], frame),
+     expect_frame(hosts[5], frame)
+    ]
+
+    send_frame(hosts[1], frame)
+
+    for exp in exps:
+      exp.receive()
+
+  def test_pvlan_unicast(self, ctx):
+    print("\nTest private vlan unicast learnt in primary vlan")
+    # host2 MAC was learnt in isolated vlan 101, promiscuous port finds it in primary vlan 100
+    frame = Ether(src=hosts[1].mac, dst=hosts[2].mac)/ARP(hwsrc=hosts[1].mac, hwdst=hosts[2].mac, pdst=hosts[2].ip, psrc=hosts[1].ip)
+    exps = [
+     expect_frame(hosts[2], frame),
+     expect_frame(hosts[3], frame, failure=True),
+     expect_frame(hosts[4], frame, failure=True),
+     expect_frame(hosts[5], frame, failure=True)
+    ]
+
+    send_frame(hosts[1], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    # Isolated hosts cannot reach each other even when the destination is known
+    frame = Ether(src=hosts[3].mac, dst=hosts[2].mac)/ARP(hwsrc=hosts[3].mac, hwdst=hosts[2].mac, pdst=hosts[2].ip, psrc=hosts[3].ip)
+    exps = [
+     expect_frame(hosts[2], frame, failure=True),
+    ]
+
+    send_frame(hosts[3], frame)
+
+    for exp in exps:
+      exp.receive()
+
+if __name__ == "__main__":
+  sys.exit(pytest.main([__file__, "-v", "-s"]))
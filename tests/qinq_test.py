@@ -82,5 +82,111 @@ class TestQinQ:
     for exp in exps:
       exp.receive()
 
+  def test_selective_qinq(self, ctx):
+    print("\nTest selective QinQ")
+    ctx["switch"].send_cmds([
+      "interface if5-sw",
+      "switchport dot1q-tunnel map 33 svlan 6",
+      "switchport dot1q-tunnel default-action drop",
+      "exit",
+      "interface if1-sw",
+      "switchport mode access",
+      "switchport access vlan 6",
+      "exit"
+    ])
+
+    frame = Ether(src=hosts[5].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[5].mac, hwdst="00:00:00:00:00:00", pdst=hosts[1].ip, psrc=hosts[5].ip)
+    exp_frame = Ether(src=hosts[5].mac, dst="ff:ff:ff:ff:ff:ff")/Dot1Q(vlan=33)/ARP(hwsrc=hosts[5].mac, hwdst="00:00:00:00:00:00", pdst=hosts[1].ip, psrc=hosts[5].ip)
+    exps = [
+     expect_frame(hosts[1], exp_frame),
+     expect_frame(hosts[2], exp_frame, failure=True),
+     expect_frame(hosts[3], exp_frame, failure=True),
+     expect_frame(hosts[4], exp_frame, failure=True),
+    ]
+
+    send_frame(hosts[5], frame, vlan=33)
+    for exp in exps:
+      exp.receive()
+
+    # Unmapped c-vlan is dropped
+    exp_frame = Ether(src=hosts[5].mac, dst="ff:ff:ff:ff:ff:ff")/Dot1Q(vlan=42)/ARP(hwsrc=hosts[5].mac, hwdst="00:00:00:00:00:00", pdst=hosts[1].ip, psrc=hosts[5].ip)
+    exps = [
+     expect_frame(hosts[1], exp_frame, failure=True),
+    ]
+
+    send_frame(hosts[5], frame, vlan=42)
+    for exp in exps:
+      exp.receive()
+
+    # S-tag is stripped on egress
+    ctx["switch"].send_cmds([
+      "interface if1-sw",
+      "switchport mode trunk",
+      "switchport trunk vlans add 6",
+      "exit"
+    ])
+    frame = Ether(src=hosts[1].mac, dst="ff:ff:ff:ff:ff:ff")/Dot1Q(vlan=6)/Dot1Q(vlan=33)/ARP(hwsrc=hosts[1].mac, hwdst="00:00:00:00:00:00", pdst=hosts[5].ip, psrc=hosts[1].ip)
+    exp_frame = Ether(src=hosts[1].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[1].mac, hwdst="00:00:00:00:00:00", pdst=hosts[5].ip, psrc=hosts[1].ip)
+    exps = [
+     expect_frame(hosts[5], exp_frame, vlan=33),
+    ]
+
+    send_frame(hosts[1], frame)
+    for exp in exps:
+      exp.receive()
+
+  def test_qinq_ethertype(self, ctx):
+    print("\nTest QinQ -> Trunk with 802.1ad ether type")
+    ctx["switch"].send_cmds([
+      "interface if5-sw",
+      "switchport mode dot1q-tunnel",
+      "switchport access vlan 5",
+      "exit",
+      "interface if1-sw",
+      "switchport mode trunk",
+      "switchport trunk vlans add 5",
+      "switchport dot1q ethertype 0x88a8",
+      "exit"
+    ])
+    frame = Ether(src=hosts[5].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[5].mac, hwdst="00:00:00:00:00:00", pdst=hosts[1].ip, psrc=hosts[5].ip)
+    exp_frame = Ether(src=hosts[5].mac, dst="ff:ff:ff:ff:ff:ff")/Dot1AD(vlan=5)/Dot1Q(vlan=33)/ARP(hwsrc=hosts[5].mac, hwdst="00:00:00:00:00:00", pdst=hosts[1].ip, psrc=hosts[5].ip)
+    exps = [
+     expect_frame(hosts[1], exp_frame),
+    ]
+
+    send_frame(hosts[5], frame, vlan=33)
+    for exp in exps:
+      exp.receive()
+
+    # Outer tag is recognized with the configured ether type on ingress
+    frame = Ether(src=hosts[1].mac, dst="ff:ff:ff:ff:ff:ff")/Dot1AD(vlan=5)/Dot1Q(vlan=33)/ARP(hwsrc=hosts[1].mac, hwdst="00:00:00:00:00:00", pdst=hosts[5].ip, psrc=hosts[1].ip)
+    exp_frame = Ether(src=hosts[1].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[1].mac, hwdst="00:00:00:00:00:00", pdst=hosts[5].ip, psrc=hosts[1].ip)
+    exps = [
+     expect_frame(hosts[5], exp_frame, vlan=33),
+    ]
+
+    send_frame(hosts[1], frame)
+    for exp in exps:
+      exp.receive()
+
+    ctx["switch"].send_cmds([
+      "interface if1-sw",
+      "no switchport dot1q ethertype",
+      "exit"
+    ])
+
+  def test_qinq_8021ad_default_ethertype(self, ctx):
+    print("\nTest 802.1ad outer tag is recognized with default ether type")
+    # if5-sw tunnels vlan 5, if1-sw trunk is back to 0x8100 outer tags
+    frame = Ether(src=hosts[1].mac, dst="ff:ff:ff:ff:ff:ff")/Dot1AD(vlan=5)/Dot1Q(vlan=33)/ARP(hwsrc=hosts[1].mac, hwdst="00:00:00:00:00:00", pdst=hosts[5].ip, psrc=hosts[1].ip)
+    exp_frame = Ether(src=hosts[1].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[1].mac, hwdst="00:00:00:00:00:00", pdst=hosts[5].ip, psrc=hosts[1].ip)
+    exps = [
+     expect_frame(hosts[5], exp_frame, vlan=33),
+    ]
+
+    send_frame(hosts[1], frame)
+    for exp in exps:
+      exp.receive()
+
 if __name__ == "__main__":
   sys.exit(pytest.main([__file__, "-v", "-s"]))
@@ -0,0 +1,118 @@
+#!/usr/bin/env python3
+
+from utils import *
+
+import sys
+
+def bpdu(host):
+  # RST BPDU from a neighbour bridge claiming to be root
+  return Dot3(src=host.mac, dst="01:80:c2:00:00:00")/LLC(dsap=0x42, ssap=0x42, ctrl=3)/STP(version=2, bpdutype=2,
+    bpduflags=0x0c, rootid=0, rootmac=host.mac, pathcost=0, bridgeid=0, bridgemac=host.mac,
+    portid=0x8001)/Raw(b"\x00")
+
+class TestStpGuard:
+  def test_portfast(self, ctx):
+    print("\nTest edge ports forward right away")
+    ctx["switch"].send_cmds([
+      "interface if4-sw",
+      "spanning-tree portfast",
+      "exit",
+      "interface if5-sw",
+      "spanning-tree portfast",
+      "exit",
+      "spanning-tree forward-time 30",
+      "spanning-tree"
+    ])
+    time.sleep(1)
+
+    frame = Ether(src=hosts[4].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[4].mac, hwdst="00:00:00:00:00:00", pdst=hosts[5].ip, psrc=hosts[4].ip)
+    exps = [
+     expect_frame(hosts[1], frame, failure=True),
+     expect_frame(hosts[5], frame)
+    ]
+
+    send_frame(hosts[4], frame)
+
+    for exp in exps:
+      exp.receive()
+
+  def test_bpduguard(self, ctx):
+    print("\nTest BPDU guard err-disables interface")
+    ctx["switch"].send_cmds([
+      "interface if3-sw",
+      "spanning-tree portfast",
+      "spanning-tree bpduguard",
+      "exit"
+    ])
+
+    send_frame(hosts[3], bpdu(hosts[3]))
+    time.sleep(1)
+
+    ctx["switch"].read_output()
+    ctx["switch"].send_cmds([
+      "show interfaces"
+    ])
+    output = ctx["switch"].read_output()
+    assert "err-disabled (bpduguard" in output
+
+    ctx["switch"].send_cmds([
+      "interface if3-sw",
+      "no spanning-tree bpduguard",
+      "no spanning-tree portfast",
+      "no shutdown",
+      "exit",
+      "interface if4-sw",
+      "no spanning-tree portfast",
+      "exit",
+      "interface if5-sw",
+      "no spanning-tree portfast",
+      "exit",
+      "spanning-tree forward-time 15",
+      "no spanning-tree"
+    ])
+
+  def test_bpdu_filter_guard_without_stp(self, ctx):
+    print("\nTest BPDU filter and guard apply with spanning tree disabled")
+    ctx["switch"].send_cmds([
+      "interface if1-sw",
+      "spanning-tree bpdufilter",
+      "exit",
+      "interface if3-sw",
+      "spanning-tree bpduguard",
+      "exit"
+    ])
+
+    # Filtered BPDU is dropped instead of flooded
+    frame = bpdu(hosts[1])
+    exps = [
+     expect_frame(hosts[2], frame, failure=True),
+     expect_frame(hosts[4], frame, failure=True)
+    ]
+
+    send_frame(hosts[1], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    send_frame(hosts[3], bpdu(hosts[3]))
+    time.sleep(1)
+
+    ctx["switch"].read_output()
+    ctx["switch"].send_cmds([
+      "show interfaces"
+    ])
+    output = ctx["switch"].read_output()
+    assert "err-disabled (bpduguard" in output
+
+    ctx["switch"].send_cmds([
+      "interface if1-sw",
+      "no spanning-tree bpdufilter",
+      "exit",
+      "interface if3-sw",
+      "no spanning-tree bpduguard",
+      "no shutdown",
+      "exit"
+    ])
+
+if __name__ == "__main__":
+  sys.exit(pytest.main([__file__, "-v", "-s"]))
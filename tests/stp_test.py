@@ -0,0 +1,61 @@
+#!/usr/bin/env python3
+
+from utils import *
+
+import sys
+
+def bpdu(host, bridgemac, flags):
+  # RST BPDU from designated port of a neighbour bridge advertising root 0.00:00:00:00:00:01
+  return Dot3(src=host.mac, dst="01:80:c2:00:00:00")/LLC(dsap=0x42, ssap=0x42, ctrl=3)/STP(version=2, bpdutype=2,
+    bpduflags=flags, rootid=0, rootmac="00:00:00:00:00:01", pathcost=0, bridgeid=0, bridgemac=bridgemac,
+    portid=0x8001, hellotime=5)/Raw(b"\x00")
+
+class TestStp:
+  def test_stp_roles(self, ctx):
+    print("\nTest spanning tree root and alternate ports")
+    ctx["switch"].send_cmds([
+      "spanning-tree hello-time 5",
+      "spanning-tree forward-time 4",
+      "spanning-tree"
+    ])
+
+    # host1 bridge proposes, it is better than host2 bridge towards the same root
+    send_frame(hosts[1], bpdu(hosts[1], "00:00:00:00:00:01", 0x0e))
+    send_frame(hosts[2], bpdu(hosts[2], "00:00:00:00:00:02", 0x0c))
+
+    # Wait for designated ports forward delay
+    time.sleep(9)
+    send_frame(hosts[1], bpdu(hosts[1], "00:00:00:00:00:01", 0x2c))
+    send_frame(hosts[2], bpdu(hosts[2], "00:00:00:00:00:02", 0x2c))
+
+    ctx["switch"].read_output()
+    ctx["switch"].send_cmds([
+      "show spanning-tree"
+    ])
+    output = ctx["switch"].read_output()
+    assert "port if1-sw" in output
+    lines = output.splitlines()
+    assert any(line.split()[:3] == ["if1-sw", "Root", "forwarding"] for line in lines if line)
+    assert any(line.split()[:3] == ["if2-sw", "Alternate", "discarding"] for line in lines if line)
+
+  def test_stp_blocking(self, ctx):
+    print("\nTest spanning tree alternate port blocks traffic")
+    frame = Ether(src=hosts[3].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[3].mac, hwdst="00:00:00:00:00:00", pdst=hosts[1].ip, psrc=hosts[3].ip)
+    exps = [
+     expect_frame(hosts[1], frame),
+     expect_frame(hosts[2], frame, failure=True),
+     expect_frame(hosts[4], frame),
+     expect_frame(hosts[5], frame)
+    ]
+
+    send_frame(hosts[3], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    ctx["switch"].send_cmds([
+      "no spanning-tree"
+    ])
+
+if __name__ == "__main__":
+  sys.exit(pytest.main([__file__, "-v", "-s"]))
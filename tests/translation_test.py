@@ -194,5 +194,149 @@ class TestVlanTranslation:
     for exp in exps:
       exp.receive()
 
+  def test_ingress_translation(self, ctx):
+    print("\nTest N:1 ingress only translation")
+    ctx["switch"].send_cmds([
+      "interface if5-sw",
+      "switchport vlan translation ingress 33,42 5",
+      "exit"
+    ])
+
+    # Both vlans are translated to 5 on ingress
+    for vlan in [33, 42]:
+      frame = Ether(src=hosts[5].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[5].mac, hwdst="00:00:00:00:00:00", pdst=hosts[2].ip, psrc=hosts[5].ip)
+      exps = [
+       expect_frame(hosts[1], frame, failure=True),
+       expect_frame(hosts[2], frame),
+       expect_frame(hosts[3], frame, failure=True),
+      ]
+
+      send_frame(hosts[5], frame, vlan=vlan)
+
+      for exp in exps:
+        exp.receive()
+
+    # Vlan 5 is not translated back on egress
+    frame = Ether(src=hosts[2].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[2].mac, hwdst="00:00:00:00:00:00", pdst=hosts[5].ip, psrc=hosts[2].ip)
+    exps = [
+     expect_frame(hosts[5], frame, vlan=33, failure=True),
+     expect_frame(hosts[5], frame, vlan=42, failure=True)
+    ]
+
+    send_frame(hosts[2], frame)
+
+    for exp in exps:
+      exp.receive()
+
+  def test_egress_translation(self, ctx):
+    print("\nTest egress only translation")
+    ctx["switch"].send_cmds([
+      "interface if5-sw",
+      "switchport vlan translation egress 5 42",
+      "switchport vlan translation egress 10 42",
+      "exit"
+    ])
+
+    frame = Ether(src=hosts[2].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[2].mac, hwdst="00:00:00:00:00:00", pdst=hosts[5].ip, psrc=hosts[2].ip)
+    exps = [
+     expect_frame(hosts[5], frame, vlan=33, failure=True),
+     expect_frame(hosts[5], frame, vlan=42)
+    ]
+
+    send_frame(hosts[2], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    # Ambiguous egress translation of vlan 10 was rejected
+    frame = Ether(src=hosts[3].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[3].mac, hwdst="00:00:00:00:00:00", pdst=hosts[5].ip, psrc=hosts[3].ip)
+    exps = [
+     expect_frame(hosts[5], frame, vlan=42, failure=True)
+    ]
+
+    send_frame(hosts[3], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    ctx["switch"].send_cmds([
+      "interface if5-sw",
+      "no switchport vlan translation",
+      "exit"
+    ])
+
+  def test_ingress_egress_single_deletion(self, ctx):
+    print("\nTest removal of a single ingress and a single egress translation")
+    ctx["switch"].send_cmds([
+      "interface if5-sw",
+      "switchport vlan translation ingress 33,42 5",
+      "switchport vlan translation egress 5 42",
+      "no switchport vlan translation ingress 42",
+      "exit"
+    ])
+
+    # Vlan 42 is no longer translated on ingress, 33 still is
+    frame = Ether(src=hosts[5].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[5].mac, hwdst="00:00:00:00:00:00", pdst=hosts[2].ip, psrc=hosts[5].ip)
+    exps = [
+     expect_frame(hosts[2], frame, failure=True)
+    ]
+
+    send_frame(hosts[5], frame, vlan=42)
+
+    for exp in exps:
+      exp.receive()
+
+    exps = [
+     expect_frame(hosts[2], frame)
+    ]
+
+    send_frame(hosts[5], frame, vlan=33)
+
+    for exp in exps:
+      exp.receive()
+
+    # Egress translation is kept until removed
+    frame = Ether(src=hosts[2].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[2].mac, hwdst="00:00:00:00:00:00", pdst=hosts[5].ip, psrc=hosts[2].ip)
+    exps = [
+     expect_frame(hosts[5], frame, vlan=42)
+    ]
+
+    send_frame(hosts[2], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    ctx["switch"].send_cmds([
+      "interface if5-sw",
+      "no switchport vlan translation egress 5",
+      "exit"
+    ])
+
+    exps = [
+     expect_frame(hosts[5], frame, vlan=42, failure=True)
+    ]
+
+    send_frame(hosts[2], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    # Ingress translation of vlan 33 survived the egress removal
+    frame = Ether(src=hosts[5].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[5].mac, hwdst="00:00:00:00:00:00", pdst=hosts[2].ip, psrc=hosts[5].ip)
+    exps = [
+     expect_frame(hosts[2], frame)
+    ]
+
+    send_frame(hosts[5], frame, vlan=33)
+
+    for exp in exps:
+      exp.receive()
+
+    ctx["switch"].send_cmds([
+      "interface if5-sw",
+      "no switchport vlan translation",
+      "exit"
+    ])
+
 if __name__ == "__main__":
   sys.exit(pytest.main([__file__, "-v", "-s"]))
@@ -90,5 +90,82 @@ class TestTrunk:
     for exp in exps:
       exp.receive()
 
+  def test_trunk_native_vlan(self, ctx):
+    print("\nTest trunk native Vlan 42")
+    ctx["switch"].send_cmds([
+      "interface if5-sw",
+      "switchport trunk native vlan 42",
+      "exit",
+    ])
+
+    # Untagged ingress is classified in native vlan
+    frame = Ether(src=hosts[5].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[5].mac, hwdst="00:00:00:00:00:00", pdst=hosts[2].ip, psrc=hosts[5].ip)
+    exps = [
+     expect_frame(hosts[1], frame, failure=True),
+     expect_frame(hosts[2], frame),
+     expect_frame(hosts[3], frame, failure=True),
+     expect_frame(hosts[4], frame, failure=True),
+    ]
+
+    send_frame(hosts[5], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    # Native vlan egress is untagged
+    frame = Ether(src=hosts[2].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[2].mac, hwdst="00:00:00:00:00:00", pdst=hosts[5].ip, psrc=hosts[2].ip)
+    exps = [
+     expect_frame(hosts[5], frame),
+     expect_frame(hosts[5], frame, vlan=42, failure=True)
+    ]
+
+    send_frame(hosts[2], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    ctx["switch"].send_cmds([
+      "interface if5-sw",
+      "no switchport trunk native vlan",
+      "exit",
+    ])
+
+  def test_trunk_vlan_range(self, ctx):
+    print("\nTest trunk allowed Vlan range")
+    ctx["switch"].send_cmds([
+      "interface if5-sw",
+      "switchport trunk vlans add 30-35,40",
+      "exit",
+    ])
+
+    frame = Ether(src=hosts[5].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[5].mac, hwdst="00:00:00:00:00:00", pdst=hosts[1].ip, psrc=hosts[5].ip)
+    exps = [
+     expect_frame(hosts[1], frame),
+     expect_frame(hosts[2], frame, failure=True),
+     expect_frame(hosts[3], frame, failure=True),
+     expect_frame(hosts[4], frame, failure=True),
+    ]
+
+    send_frame(hosts[5], frame, vlan=33)
+
+    for exp in exps:
+      exp.receive()
+
+    ctx["switch"].read_output()
+    ctx["switch"].send_cmds([
+      "interface if5-sw",
+      "show",
+    ])
+    assert "Allowed Vlans: 30-35,40,42" in ctx["switch"].read_output()
+
+    ctx["switch"].send_cmds([
+      "switchport trunk vlans except 33",
+      "exit",
+    ])
+
+    exp = expect_frame(hosts[1], frame, failure=True)
+    send_frame(hosts[5], frame, vlan=33)
+    exp.receive()
+
 if __name__ == "__main__":
   sys.exit(pytest.main([__file__, "-v", "-s"]))
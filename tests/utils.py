@@ -3,7 +3,7 @@ import select
 import subprocess
 import time
 import textwrap
-from scapy.all import ARP, Dot1Q, Ether, IP, ICMP, raw
+from scapy.all import ARP, Dot1AD, Dot1Q, Dot3, Ether, IP, ICMP, LLC, Raw, STP, raw
 from inspect import cleandoc
 
 container_prefix = "bs-lab"
@@ -41,11 +41,13 @@ def run_cmd_on_host(host, cmd):
   run_cmd(f"docker exec {container_prefix}-{host} {cmd}")
 
 class Switch:
-  def __init__(self):
-    cmd = ["docker", "exec", "-i", f"{container_prefix}-sw", "/app/blair_switch" ]
+  # Errors are read along with the output when the switch runs with options
+  def __init__(self, options=[]):
+    cmd = ["docker", "exec", "-i", f"{container_prefix}-sw", "/app/blair_switch" ] + options
     for host in hosts[1:]:
       cmd.append(host.iface.split('-')[0] + "-sw")
-    self.process = subprocess.Popen(cmd, stdin=subprocess.PIPE, stdout=subprocess.PIPE, text=True)
+    stderr = subprocess.STDOUT if options else None
+    self.process = subprocess.Popen(cmd, stdin=subprocess.PIPE, stdout=subprocess.PIPE, stderr=stderr, text=True)
 
   def send_cmd(self, cmd):
     self.process.stdin.write(cmd.strip() + "\n")
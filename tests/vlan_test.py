@@ -0,0 +1,226 @@
+#!/usr/bin/env python3
+
+from utils import *
+
+import sys
+
+class TestVlan:
+  def test_vlan_suspend(self, ctx):
+    print("\nTest suspended vlan")
+    ctx["switch"].send_cmds([
+      "interface if1-sw",
+      "switchport access vlan 20",
+      "exit",
+      "interface if2-sw",
+      "switchport access vlan 20",
+      "exit",
+      "vlan 20",
+      "name users",
+      "state suspend",
+      "exit",
+    ])
+
+    frame = Ether(src=hosts[1].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[1].mac, hwdst="00:00:00:00:00:00", pdst=hosts[2].ip, psrc=hosts[1].ip)
+    exps = [
+      expect_frame(hosts[2], frame, failure=True),
+      expect_frame(hosts[3], frame, failure=True),
+      expect_frame(hosts[4], frame, failure=True)
+    ]
+
+    send_frame(hosts[1], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    ctx["switch"].send_cmds([
+      "vlan 20",
+      "state active",
+      "exit",
+    ])
+
+    exps = [
+      expect_frame(hosts[2], frame),
+      expect_frame(hosts[3], frame, failure=True),
+      expect_frame(hosts[4], frame, failure=True)
+    ]
+
+    send_frame(hosts[1], frame)
+
+    for exp in exps:
+      exp.receive()
+
+  def test_show_vlan(self, ctx):
+    print("\nTest show vlan")
+    ctx["switch"].read_output()
+    ctx["switch"].send_cmds([
+      "show vlan",
+    ])
+    output = ctx["switch"].read_output()
+    assert "users" in output
+    assert "if1-sw, if2-sw" in output
+
+  def test_vlan_counters(self, ctx):
+    print("\nTest vlan counters")
+    ctx["switch"].send_cmds([
+      "clear vlan counters",
+    ])
+
+    frame = Ether(src=hosts[1].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[1].mac, hwdst="00:00:00:00:00:00", pdst=hosts[2].ip, psrc=hosts[1].ip)
+    exps = [
+      expect_frame(hosts[2], frame),
+    ]
+
+    send_frame(hosts[1], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    ctx["switch"].read_output()
+    ctx["switch"].send_cmds([
+      "show vlan counters 20",
+    ])
+    output = ctx["switch"].read_output()
+    counters = [line.split() for line in output.splitlines() if line.split()[:1] == ["20"]]
+    assert len(counters) == 1
+    vlan, in_pkts, in_bytes, out_pkts, out_bytes, unicast, flooded = counters[0]
+    assert int(in_pkts) >= 1
+    assert int(out_pkts) >= 1
+    assert int(flooded) >= 1
+
+  def test_vlan_deletion(self, ctx):
+    print("\nTest deleted vlan")
+    ctx["switch"].send_cmds([
+      "no vlan 20",
+    ])
+
+    frame = Ether(src=hosts[1].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[1].mac, hwdst="00:00:00:00:00:00", pdst=hosts[2].ip, psrc=hosts[1].ip)
+    exps = [
+      expect_frame(hosts[2], frame, failure=True),
+    ]
+
+    send_frame(hosts[1], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    ctx["switch"].send_cmds([
+      "interface if1-sw",
+      "no switchport access vlan",
+      "exit",
+      "interface if2-sw",
+      "no switchport access vlan",
+      "exit",
+    ])
+
+  def test_vlan_classify_mac(self, ctx):
+    print("\nTest source MAC vlan classification")
+    ctx["switch"].send_cmds([
+      "interface if1-sw",
+      "vlan classify mac aa:aa:aa:00:00:00/24 vlan 30",
+      "exit",
+      "interface if2-sw",
+      "switchport access vlan 30",
+      "exit",
+    ])
+
+    frame = Ether(src=hosts[1].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[1].mac, hwdst="00:00:00:00:00:00", pdst=hosts[2].ip, psrc=hosts[1].ip)
+    exps = [
+      expect_frame(hosts[2], frame),
+      expect_frame(hosts[3], frame, failure=True),
+      expect_frame(hosts[4], frame, failure=True)
+    ]
+
+    send_frame(hosts[1], frame)
+
+    for exp in exps:
+      exp.receive()
+
+  def test_vlan_classify_protocol(self, ctx):
+    print("\nTest ether type vlan classification")
+    ctx["switch"].send_cmds([
+      "interface if1-sw",
+      "no vlan classify mac aa:aa:aa:00:00:00/24",
+      "vlan classify protocol 0x0806 vlan 40",
+      "exit",
+      "interface if3-sw",
+      "switchport access vlan 40",
+      "exit",
+    ])
+
+    frame = Ether(src=hosts[1].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[1].mac, hwdst="00:00:00:00:00:00", pdst=hosts[3].ip, psrc=hosts[1].ip)
+    exps = [
+      expect_frame(hosts[2], frame, failure=True),
+      expect_frame(hosts[3], frame),
+      expect_frame(hosts[4], frame, failure=True)
+    ]
+
+    send_frame(hosts[1], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    # Other ether types fall back to the access vlan
+    frame = Ether(src=hosts[1].mac, dst="ff:ff:ff:ff:ff:ff")/IP(src=hosts[1].ip, dst=hosts[4].ip)/ICMP()
+    exps = [
+      expect_frame(hosts[2], frame, failure=True),
+      expect_frame(hosts[3], frame, failure=True),
+      expect_frame(hosts[4], frame)
+    ]
+
+    send_frame(hosts[1], frame)
+
+    for exp in exps:
+      exp.receive()
+
+    ctx["switch"].send_cmds([
+      "interface if1-sw",
+      "no vlan classify protocol 0x0806",
+      "exit",
+      "interface if2-sw",
+      "no switchport access vlan",
+      "exit",
+      "interface if3-sw",
+      "no switchport access vlan",
+      "exit",
+    ])
+
+  def test_vlan_trunk_only(self, ctx):
+    print("\nTest vlan only configured on trunks is created and switched")
+    ctx["switch"].read_output()
+    ctx["switch"].send_cmds([
+      "interface if4-sw",
+      "switchport mode trunk",
+      "switchport trunk vlans add 30",
+      "exit",
+      "interface if5-sw",
+      "switchport mode trunk",
+      "switchport trunk vlans add 30",
+      "exit",
+    ])
+    assert "Vlan 30 does not exist, creating it" in ctx["switch"].read_output()
+
+    frame = Ether(src=hosts[4].mac, dst="ff:ff:ff:ff:ff:ff")/ARP(hwsrc=hosts[4].mac, hwdst="00:00:00:00:00:00", pdst=hosts[5].ip, psrc=hosts[4].ip)
+    exps = [
+      expect_frame(hosts[5], frame, vlan=30),
+      expect_frame(hosts[1], frame, failure=True)
+    ]
+
+    send_frame(hosts[4], frame, vlan=30)
+
+    for exp in exps:
+      exp.receive()
+
+    ctx["switch"].send_cmds([
+      "interface if4-sw",
+      "no switchport trunk vlans",
+      "switchport mode access",
+      "exit",
+      "interface if5-sw",
+      "no switchport trunk vlans",
+      "switchport mode access",
+      "exit",
+      "no vlan 30",
+    ])
+
+if __name__ == "__main__":
+  sys.exit(pytest.main([__file__, "-v", "-s"]))